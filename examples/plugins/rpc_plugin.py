@@ -0,0 +1,75 @@
+#!/usr/bin/env python3
+"""A minimal Hyperpack plugin speaking JSON-RPC over stdio.
+
+Resolves `@rpc/<name>` to the virtual module `virtual:rpc/<name>`, loads those modules, and
+replaces `__RPC__` with `"rpc"`, warning about each line with a `TODO` and failing on
+`__RPC_FAIL__`. Modules whose path contains `slow` take five seconds to transform, to exercise
+the host's timeout.
+
+    [[plugins]]
+    type = "process"
+    command = "python3"
+    args = ["examples/plugins/rpc_plugin.py"]
+"""
+
+import json
+import sys
+import time
+
+
+def initialize(params):
+    return {"hooks": ["resolve", "load", "transform"], "cacheKey": "rpc_plugin 1"}
+
+
+def resolve(params):
+    if params["path"].startswith("@rpc/"):
+        return {"path": "virtual:rpc/" + params["path"][len("@rpc/"):]}
+    return None
+
+
+def load(params):
+    if params["path"].startswith("virtual:rpc/"):
+        name = params["path"][len("virtual:rpc/"):]
+        return {"code": "export const name = %s;\n" % json.dumps(name)}
+    return None
+
+
+def transform(params):
+    if "slow" in params["path"]:
+        time.sleep(5)
+    lines = params["code"].split("\n")
+    for number, line in enumerate(lines, 1):
+        if "__RPC_FAIL__" in line:
+            column = line.index("__RPC_FAIL__") + 1
+            return {"error": {"message": "asked to fail", "line": number, "column": column}}
+    warnings = [
+        {"message": "unfinished code", "line": number, "column": line.index("TODO") + 1}
+        for number, line in enumerate(lines, 1)
+        if "TODO" in line
+    ]
+    if "__RPC__" not in params["code"]:
+        return {"warnings": warnings}
+    return {"code": params["code"].replace("__RPC__", '"rpc"'), "warnings": warnings}
+
+
+METHODS = {"initialize": initialize, "resolve": resolve, "load": load, "transform": transform}
+
+
+def handle(request):
+    method = METHODS.get(request.get("method"))
+    if method is None:
+        error = {"code": -32601, "message": "unknown method %r" % request.get("method")}
+        return {"jsonrpc": "2.0", "id": request.get("id"), "error": error}
+    return {"jsonrpc": "2.0", "id": request["id"], "result": method(request.get("params"))}
+
+
+for line in sys.stdin:
+    if not line.strip():
+        continue
+    message = json.loads(line)
+    if isinstance(message, list):
+        response = [handle(request) for request in message]
+    else:
+        response = handle(message)
+    sys.stdout.write(json.dumps(response) + "\n")
+    sys.stdout.flush()
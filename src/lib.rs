@@ -0,0 +1,129 @@
+//! Hyperpack as a library.
+//!
+//! [`build`], [`build_watch`], [`watch`], [`lint`] and [`format`] are what the `hyperpack` binary runs, but they
+//! report their results as values instead of printing them or exiting the process.
+
+// Failures are reported as a `Diagnostic`, which is deliberately rich rather than small.
+#![allow(clippy::result_large_err)]
+
+use std::path::PathBuf;
+
+#[path = "src/config.rs"]
+pub mod config;
+#[path = "src/diagnostic.rs"]
+pub mod diagnostic;
+#[path = "src/define.rs"]
+pub mod define;
+#[path = "src/env.rs"]
+pub mod env;
+#[path = "src/loader.rs"]
+pub mod loader;
+#[path = "src/plugin.rs"]
+pub mod plugin;
+#[path = "src/plugins/mod.rs"]
+pub mod plugins;
+#[path = "src/resolver.rs"]
+pub mod resolver;
+#[path = "src/bundler/explicit-bundler.rs"]
+pub mod bundler;
+#[path = "src/bundler/cache.rs"]
+pub mod cache;
+#[path = "src/bundler/sourcemap.rs"]
+pub mod sourcemap;
+#[path = "src/bundler/registry.rs"]
+pub mod registry;
+#[path = "src/bundler/explicit-minifier.rs"]
+pub mod minifier;
+#[path = "src/bundler/splitter.rs"]
+pub mod splitter;
+#[path = "src/stats.rs"]
+pub mod stats;
+#[path = "src/chunker.rs"]
+pub mod chunker;
+#[path = "src/linter.rs"]
+pub mod linter;
+#[path = "src/format.rs"]
+pub mod format;
+#[path = "src/docs/docgen.rs"]
+pub mod docgen;
+#[path = "src/hotreload.rs"]
+pub mod hotreload;
+#[path = "src/livecompiler.rs"]
+pub mod livecompiler;
+#[path = "src/tsc.rs"]
+pub mod tsc;
+#[path = "src/typecheck.rs"]
+pub mod typecheck;
+#[cfg(test)]
+#[path = "src/testing.rs"]
+mod testing;
+
+pub use config::{Config, ConfigError};
+pub use diagnostic::{Diagnostic, Severity, Span};
+pub use plugin::{
+    HookResult, Plugin, PluginContext, PluginFilter, PluginManager, ResolveArgs, ResolveKind, ResolveResult,
+};
+pub use resolver::{ResolveError, ResolveErrorKind, Resolved};
+pub use bundler::{BuildResult, Bundler, ModuleInfo, OutputFile, Timings};
+pub use hotreload::{WatchError, WatchEvent, WatchOptions};
+
+/// Bundles every entry in `config` with only the plugins listed in it.
+pub fn build(config: Config) -> BuildResult {
+    build_with_plugins(config, PluginManager::new())
+}
+
+/// Bundles every entry in `config` with `plugins`, followed by the plugins listed in `config`.
+pub fn build_with_plugins(config: Config, mut plugins: PluginManager) -> BuildResult {
+    if let Err(diagnostic) = register_configured_plugins(&config, &mut plugins) {
+        return BuildResult { errors: vec![diagnostic], ..Default::default() };
+    }
+    Bundler::new(config, plugins).bundle()
+}
+
+/// Bundles `config`, then rebuilds in-process whenever one of its modules changes, passing each
+/// result and the changed files to `on_build`. See [`Bundler::watch`].
+pub fn build_watch(config: Config, on_build: impl FnMut(&BuildResult, &[PathBuf])) -> Result<(), String> {
+    let mut plugins = PluginManager::new();
+    register_configured_plugins(&config, &mut plugins).map_err(|diagnostic| diagnostic.to_string())?;
+    Bundler::new(config, plugins).watch(on_build)
+}
+
+fn register_configured_plugins(config: &Config, manager: &mut PluginManager) -> Result<(), Diagnostic> {
+    for plugin in plugins::from_config(config)? {
+        manager.register(plugin);
+    }
+    Ok(())
+}
+
+/// Watches `options.directories` until the watcher stops, passing each debounced change to
+/// `on_event`.
+pub fn watch(options: &WatchOptions, on_event: impl FnMut(&WatchEvent)) -> Result<(), WatchError> {
+    hotreload::watch(options, on_event)
+}
+
+/// Lints every file, inferring each file's type from its extension when `file_type` is `None`.
+/// Files that cannot be linted are reported as error diagnostics.
+pub fn lint(files: &[PathBuf], file_type: Option<&str>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for file in files {
+        match linter::lint_file(file, file_type) {
+            Ok(issues) => diagnostics.extend(issues),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    diagnostics
+}
+
+/// Formats every file and returns the ones whose formatting changed, or would change when
+/// `write` is false.
+pub fn format(files: &[PathBuf], write: bool) -> Result<Vec<PathBuf>, String> {
+    let mut changed = vec![];
+    for file in files {
+        if format::format_file(file, write)
+            .map_err(|err| format!("Error formatting {}: {}", file.display(), err))?
+        {
+            changed.push(file.clone());
+        }
+    }
+    Ok(changed)
+}
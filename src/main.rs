@@ -1,17 +1,638 @@
-include!(concat!(env!("OUT_DIR"), "/modules.rs"));
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use log::{error, info, LevelFilter};
+use simple_logger::SimpleLogger;
+
+use hyperpack::{chunker, docgen, hotreload, livecompiler, minifier, splitter};
+use hyperpack::config::Mode;
+use hyperpack::{BuildResult, Config, Diagnostic, WatchEvent};
+
+/// Hyperpack, a high-performance module bundler.
+#[derive(Parser)]
+#[command(name = "hyperpack", version, about)]
+struct Cli {
+    /// Project configuration file; defaults to hyperpack.config.json or hyperpack.toml.
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Log level: off, error, warn, info, debug or trace.
+    #[arg(short, long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+
+    /// Run as if Hyperpack was started in this directory.
+    #[arg(short = 'C', long, global = true)]
+    cwd: Option<PathBuf>,
+
+    /// How errors and warnings are printed.
+    #[arg(long, global = true, value_enum, default_value_t = DiagnosticFormat::Human)]
+    diagnostics: DiagnosticFormat,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiagnosticFormat {
+    /// Code frames on stderr, colored when stderr is a terminal.
+    Human,
+    /// One JSON object per line on stdout.
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Bundle each entry file and everything it imports into its output file.
+    Build(BuildArgs),
+    /// Watch directories and run the configured commands when files change.
+    Dev(DevArgs),
+    /// Check HTML, CSS and JavaScript files for common issues.
+    Lint(LintArgs),
+    /// Format TypeScript, JavaScript, HTML and CSS files in place.
+    Fmt(FmtArgs),
+    /// Generate documentation from JSDoc comments.
+    Docs(DocsArgs),
+    /// Minify a JavaScript file or every file in a directory.
+    Minify(MinifyArgs),
+    /// Split an entry file's imports into separate chunk files.
+    Split(SplitArgs),
+    /// Cut every JS, CSS and HTML file in a directory into fixed-size chunks.
+    Chunk(ChunkArgs),
+    /// Start the live compilation server.
+    Serve(ServeArgs),
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Entry files to bundle, optionally named as `name=path`; unnamed entries are named
+    /// after their file stem.
+    entries: Vec<String>,
+
+    /// `development` or `production`; picks the defaults for the flags below.
+    #[arg(short, long)]
+    mode: Option<Mode>,
+
+    /// File the bundle is written to.
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Rebuild whenever a module of the build changes.
+    #[arg(short, long)]
+    watch: bool,
+
+    /// Number of bundler worker threads.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Minify the bundle (`--minify=false` to disable).
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    minify: Option<bool>,
+
+    /// Emit a source map, of the kind configured in `sourcemap.kind`.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    sourcemap: Option<bool>,
+
+    /// Drop unused imports.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    tree_shaking: Option<bool>,
+
+    /// Write module, chunk and timing statistics to `stats.file`.
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    stats: Option<bool>,
+
+    /// Reuse transformed modules from earlier builds (`--cache=false` to disable).
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    cache: Option<bool>,
+
+    /// Stop at the first error a plugin reports.
+    #[arg(long)]
+    bail: bool,
+}
+
+#[derive(Args)]
+struct DevArgs {
+    /// Directories to watch.
+    #[arg(
+        short = 'w',
+        long = "watch",
+        env = "WATCH_DIRECTORIES",
+        value_delimiter = ','
+    )]
+    directories: Vec<PathBuf>,
+
+    /// Directories that are never watched.
+    #[arg(short, long, env = "EXCLUDE_DIRECTORIES", value_delimiter = ',')]
+    exclude: Vec<PathBuf>,
+
+    /// Seconds to wait for further changes before reacting to an event.
+    #[arg(short, long, env = "DEBOUNCE_TIME")]
+    debounce: Option<u64>,
+
+    /// Truncate the watcher log file on start.
+    #[arg(long, env = "CLEAR_LOG_ON_START")]
+    clear_log: bool,
+}
+
+#[derive(Args)]
+struct LintArgs {
+    /// Files to check.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// File type (html, css or js); inferred from the extension when omitted.
+    #[arg(short = 't', long = "type")]
+    file_type: Option<String>,
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    /// Files to format.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Report files that would be reformatted without writing them.
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Args)]
+struct DocsArgs {
+    /// Directory containing the JavaScript and TypeScript sources.
+    dir: PathBuf,
+
+    /// Output format: markdown, html or both.
+    #[arg(short, long)]
+    format: Option<String>,
+
+    /// Do not write the INDEX.md file index.
+    #[arg(long)]
+    no_index: bool,
+
+    /// Directory the documentation files are written to.
+    #[arg(short, long, default_value = ".")]
+    out_dir: PathBuf,
+}
+
+#[derive(Args)]
+struct MinifyArgs {
+    /// Input file or directory to minify.
+    input: PathBuf,
+
+    /// Output file or directory for minified code.
+    output: PathBuf,
+
+    /// Use parallel processing.
+    #[arg(short, long)]
+    parallel: bool,
+
+    /// Keep comments in the minified output.
+    #[arg(short, long)]
+    keep_comments: bool,
+
+    /// Perform a dry run without writing output files.
+    #[arg(short, long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct SplitArgs {
+    /// Entry file that starts the splitting process.
+    entry: Option<PathBuf>,
+
+    /// Directory the split files are saved to.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ChunkArgs {
+    /// Input directory.
+    #[arg(short, long, default_value = "input")]
+    input: PathBuf,
+
+    /// Output directory.
+    #[arg(short, long, default_value = "dist/static")]
+    output: PathBuf,
+
+    /// Chunk size in bytes.
+    #[arg(short = 's', long)]
+    chunk_size: Option<usize>,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Address to listen on.
+    #[arg(long)]
+    host: Option<String>,
+
+    /// Port to listen on.
+    #[arg(short, long)]
+    port: Option<u16>,
+}
 
 fn main() {
-    println!("Welcome to Hyperpack.");
-    // let entry_file = "src/index.js";
-    // let output_file = "dist/bundle.js";
+    let cli = Cli::parse();
+
+    // Only Hyperpack's own records; swc reports its internal spans through `log` as well.
+    SimpleLogger::new()
+        .with_level(LevelFilter::Off)
+        .with_module_level("hyperpack", cli.log_level)
+        .init()
+        .expect("Failed to initialize logger");
+
+    if let Some(cwd) = &cli.cwd {
+        if let Err(err) = env::set_current_dir(cwd) {
+            error!("Unable to change directory to {}: {}", cwd.display(), err);
+            process::exit(1);
+        }
+    }
+
+    let reporter = Reporter {
+        format: cli.diagnostics,
+        color: io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            reporter.report(&[err.into()]);
+            process::exit(1);
+        }
+    };
+
+    let result = match cli.command {
+        Commands::Build(args) => build(&reporter, config, args),
+        Commands::Dev(args) => dev(config, args),
+        Commands::Lint(args) => lint(&reporter, args),
+        Commands::Fmt(args) => fmt(args),
+        Commands::Docs(args) => docs(args),
+        Commands::Minify(args) => minify(config, args),
+        Commands::Split(args) => split(config, args),
+        Commands::Chunk(args) => chunk(config, args),
+        Commands::Serve(args) => serve(config, args),
+    };
+
+    if let Err(err) = result {
+        error!("{}", err);
+        process::exit(1);
+    }
+}
+
+/// Layers the flags given on the command line over the loaded configuration.
+fn override_config(config: &mut Config, args: &BuildArgs) -> Result<(), String> {
+    match args.entries.as_slice() {
+        [] => {}
+        [entry] if !entry.contains('=') => {
+            config.entry_file = entry.clone();
+            config.entries.clear();
+        }
+        entries => {
+            config.entries = entries
+                .iter()
+                .map(|entry| match entry.split_once('=') {
+                    Some((name, entry_file)) => (name.to_string(), entry_file.to_string()),
+                    None => {
+                        let name = Path::new(entry).file_stem().unwrap_or_default();
+                        (name.to_string_lossy().to_string(), entry.clone())
+                    }
+                })
+                .collect();
+        }
+    }
+    if let Some(mode) = args.mode {
+        config.mode = mode;
+    }
+    if let Some(output) = &args.output {
+        config.output_file = Some(output.clone());
+    }
+    if let Some(threads) = args.threads {
+        config.max_threads = Some(threads);
+    }
+    if let Some(minify) = args.minify {
+        config.minify.enabled = Some(minify);
+    }
+    if let Some(sourcemap) = args.sourcemap {
+        config.sourcemap.enabled = Some(sourcemap);
+    }
+    if let Some(tree_shaking) = args.tree_shaking {
+        config.tree_shaking = Some(tree_shaking);
+    }
+    if let Some(stats) = args.stats {
+        config.stats.enabled = stats;
+    }
+    if let Some(cache) = args.cache {
+        config.cache.enabled = cache;
+    }
+    if args.bail {
+        config.bail = true;
+    }
+    config.validate().map_err(|err| err.to_string())
+}
+
+/// Prints diagnostics in the format picked with `--diagnostics`.
+struct Reporter {
+    format: DiagnosticFormat,
+    color: bool,
+}
+
+impl Reporter {
+    fn report(&self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            match self.format {
+                DiagnosticFormat::Human => eprintln!("{}", diagnostic.render(None, self.color)),
+                DiagnosticFormat::Json => println!("{}", diagnostic.to_json()),
+            }
+        }
+    }
+
+    fn is_human(&self) -> bool {
+        matches!(self.format, DiagnosticFormat::Human)
+    }
+}
+
+fn build(reporter: &Reporter, mut config: Config, args: BuildArgs) -> Result<(), String> {
+    override_config(&mut config, &args)?;
+
+    if args.watch {
+        return hyperpack::build_watch(config, |result, changed| {
+            if changed.is_empty() {
+                report_build(reporter, result);
+            } else {
+                report_rebuild(reporter, result, changed);
+            }
+        });
+    }
+
+    let result = hyperpack::build(config);
+    report_build(reporter, &result);
+
+    if result.is_success() {
+        Ok(())
+    } else {
+        Err(format!("Build failed with {} error(s)", result.errors.len()))
+    }
+}
+
+fn report_build(reporter: &Reporter, result: &BuildResult) {
+    reporter.report(&result.warnings);
+    reporter.report(&result.errors);
+    if !reporter.is_human() {
+        return;
+    }
+
+    for output in &result.outputs {
+        println!("{}  {:.1} kB", output.path.display(), output.size as f64 / 1000.0);
+    }
+    if let Some(stats_file) = &result.stats_file {
+        println!("Stats written to {}", stats_file.display());
+    }
+    if result.is_success() {
+        println!("Bundling complete in {:.2?}", result.timings.total);
+    }
+}
+
+/// One line per rebuild: what changed, how long it took and which outputs were rewritten.
+fn report_rebuild(reporter: &Reporter, result: &BuildResult, changed: &[PathBuf]) {
+    reporter.report(&result.warnings);
+    reporter.report(&result.errors);
+    if !reporter.is_human() {
+        return;
+    }
+
+    let changed = match changed {
+        [file] => file.display().to_string(),
+        files => format!("{} files", files.len()),
+    };
+    let time = chrono::Local::now().format("%H:%M:%S");
+    if !result.is_success() {
+        println!("[{}] {} changed, rebuild failed with {} error(s)", time, changed, result.errors.len());
+        return;
+    }
+    let outputs: Vec<String> = result
+        .outputs
+        .iter()
+        .map(|output| format!("{} ({:.1} kB)", output.path.display(), output.size as f64 / 1000.0))
+        .collect();
+    let outputs = if outputs.is_empty() { "no outputs changed".to_string() } else { outputs.join(", ") };
+    println!("[{}] {} changed, rebuilt in {:.2?}: {}", time, changed, result.timings.total, outputs);
+}
+
+fn dev(config: Config, args: DevArgs) -> Result<(), String> {
+    let dev_server = config.dev_server;
+    let options = hotreload::WatchOptions {
+        directories: if args.directories.is_empty() { dev_server.watch } else { args.directories },
+        exclude: if args.exclude.is_empty() { dev_server.exclude } else { args.exclude },
+        debounce: Duration::from_secs(args.debounce.unwrap_or(dev_server.debounce)),
+    };
+    if args.clear_log || dev_server.clear_log {
+        if let Err(e) = fs::write(watch_log_path(), "") {
+            eprintln!("Failed to clear log file: {}", e);
+        }
+    }
+    println!("Watching for file changes...");
+    hyperpack::watch(&options, |event| {
+        let paths = |paths: &[PathBuf], verb: &str| {
+            for path in paths {
+                println!("File {}: {:?}", verb, path);
+                log_watch_event(&path.display().to_string(), &format!("File {}", verb));
+            }
+        };
+        match event {
+            WatchEvent::Watching(dir) => {
+                println!("Watching directory: {:?}", dir);
+                log_watch_event(&dir.display().to_string(), "Started watching");
+            }
+            WatchEvent::Excluded(dir) => {
+                println!("Directory is excluded: {:?}", dir);
+                log_watch_event(&dir.display().to_string(), "Excluded directory");
+            }
+            WatchEvent::Missing(dir) => {
+                eprintln!("Directory does not exist: {:?}", dir);
+                log_watch_event(&dir.display().to_string(), "Directory does not exist");
+            }
+            WatchEvent::Renamed(renamed) => match renamed.as_slice() {
+                [src, dst] => {
+                    println!("File renamed from {:?} to {:?}", src, dst);
+                    log_watch_event(&src.display().to_string(), "File renamed (source)");
+                    log_watch_event(&dst.display().to_string(), "File renamed (destination)");
+                }
+                renamed => paths(renamed, "renamed"),
+            },
+            WatchEvent::Written(written) => paths(written, "written"),
+            WatchEvent::Created(created) => paths(created, "created"),
+            WatchEvent::Removed(removed) => paths(removed, "removed"),
+            WatchEvent::Error(e) => {
+                eprintln!("Watch error: {}", e);
+                log_watch_event("Watch", &format!("Error: {}", e));
+            }
+        }
+        if let Some(change) = event.change_name() {
+            run_watch_command(change);
+        }
+    })
+    .map_err(|err| err.to_string())?;
+    println!("Shutting down gracefully...");
+    Ok(())
+}
+
+/// The watcher log, `LOG_FILE_PATH` or `file_watcher.log`.
+fn watch_log_path() -> PathBuf {
+    PathBuf::from(env::var("LOG_FILE_PATH").unwrap_or_else(|_| "file_watcher.log".to_string()))
+}
+
+/// Appends a timestamped line about `file` to the watcher log.
+fn log_watch_event(file: &str, event: &str) {
+    let log_file = watch_log_path();
+    if let Some(parent) = log_file.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        let _ = fs::create_dir_all(parent);
+    }
+    let mut log = match OpenOptions::new().create(true).append(true).open(&log_file) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Failed to open or create log file: {}", e);
+            return;
+        }
+    };
+    let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    if let Err(e) = writeln!(log, "{} - File: {}, Event: {}", time, file, event) {
+        eprintln!("Failed to write to log file: {}", e);
+    }
+}
+
+/// Runs `<CHANGE>_COMMAND` with the whitespace-separated `<CHANGE>_ARGS`, if set, e.g.
+/// `WRITE_COMMAND` after files are written.
+fn run_watch_command(change: &str) {
+    let Ok(command) = env::var(format!("{}_COMMAND", change)) else {
+        return;
+    };
+    if command.is_empty() {
+        return;
+    }
+    let args = env::var(format!("{}_ARGS", change)).unwrap_or_default();
+    match process::Command::new(&command).args(args.split_whitespace()).stdin(process::Stdio::null()).status() {
+        Ok(status) if status.success() => {
+            println!("{} command succeeded", change);
+            log_watch_event("Command", &format!("{} command succeeded", change));
+        }
+        Ok(status) => {
+            eprintln!("{} command failed with exit code: {:?}", change, status.code());
+            log_watch_event("Command", &format!("{} command failed with exit code: {:?}", change, status.code()));
+        }
+        Err(err) => {
+            eprintln!("Failed to execute command {}: {}", command, err);
+            log_watch_event("Command", &format!("Failed to execute {} command: {}", change, err));
+        }
+    }
+}
+
+fn lint(reporter: &Reporter, args: LintArgs) -> Result<(), String> {
+    let issues = hyperpack::lint(&args.files, args.file_type.as_deref());
+    reporter.report(&issues);
+
+    if issues.is_empty() {
+        if reporter.is_human() {
+            println!("No issues found.");
+        }
+        Ok(())
+    } else {
+        Err(format!("Found {} issue(s)", issues.len()))
+    }
+}
+
+fn fmt(args: FmtArgs) -> Result<(), String> {
+    let changed = hyperpack::format(&args.files, !args.check)?;
+    if !args.check {
+        return Ok(());
+    }
+
+    for file in &changed {
+        println!("Would reformat: {}", file.display());
+    }
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} file(s) need formatting", changed.len()))
+    }
+}
+
+fn docs(args: DocsArgs) -> Result<(), String> {
+    let mut config = if Path::new(docgen::CONFIG_FILE).exists() {
+        docgen::load_config(docgen::CONFIG_FILE)?
+    } else {
+        docgen::Config::default()
+    };
+    if let Some(format) = args.format {
+        config.output_format = format;
+    }
+    if args.no_index {
+        config.include_index = false;
+    }
+
+    let summary = docgen::generate(&args.dir, &args.out_dir, &config)
+        .map_err(|err| format!("Error generating documentation: {}", err))?;
+
+    println!(
+        "Processed {} files with {} errors.",
+        summary.file_count, summary.error_count
+    );
+    Ok(())
+}
+
+fn minify(config: Config, args: MinifyArgs) -> Result<(), String> {
+    let ctx = minifier::MinificationContext::new(
+        args.input,
+        args.output,
+        args.parallel,
+        args.keep_comments || config.minify.keep_comments,
+        args.dry_run,
+    );
+    minifier::run(&ctx)
+}
+
+fn split(config: Config, args: SplitArgs) -> Result<(), String> {
+    let entry = args.entry.unwrap_or_else(|| PathBuf::from(&config.entry_file));
+    let output = args.output.unwrap_or(config.splitting.output_dir);
+    let manifest_path = splitter::split(&entry, &output)
+        .map_err(|err| format!("Code splitting failed: {}", err))?;
+
+    info!("Code splitting complete. Chunks saved to {:?}", output);
+    info!("Chunk metadata saved to {:?}", manifest_path);
+    Ok(())
+}
+
+fn chunk(config: Config, args: ChunkArgs) -> Result<(), String> {
+    let chunk_size = args.chunk_size.unwrap_or(config.splitting.chunk_size);
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than 0".to_string());
+    }
+
+    let processed = chunker::process_files(&args.input, &args.output, chunk_size)
+        .map_err(|err| format!("Chunking failed: {}", err))?;
+
+    for path in processed {
+        println!("Processed: {:?}", path);
+    }
+    Ok(())
+}
 
-    // let config = Config::new(entry_file, output_file);
-    // let mut plugin_manager = PluginManager::new();
-    
-    // plugin_manager.register(Box::new(CommentPlugin));
+fn serve(config: Config, args: ServeArgs) -> Result<(), String> {
+    let host = args.host.unwrap_or(config.dev_server.host);
+    let port = args.port.unwrap_or(config.dev_server.port);
+    livecompiler::serve(&host, port)
+}
 
-    // let bundler = Bundler::new(config, plugin_manager);
-    // bundler.bundle();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
 
-    // println!("Bundling complete!");
-}
\ No newline at end of file
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+}
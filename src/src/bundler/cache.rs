@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::debug;
+use sha2::{Digest, Sha256};
+
+/// A content-addressed store for transform results that survives between builds.
+///
+/// Entries are keyed by [`Cache::key`] over everything the result depends on, so a changed
+/// file, config or plugin simply misses instead of needing invalidation.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// `None` when caching is disabled.
+    dir: Option<PathBuf>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: Some(dir.into()), ..Self::default() }
+    }
+
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// A hex SHA-256 over `parts`, each length-prefixed so `["ab", "c"]` and `["a", "bc"]` differ.
+    pub fn key(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// A hex SHA-256 of the contents of the file at `path`, or `missing` if it cannot be read.
+    pub fn file_key(path: &Path) -> String {
+        match fs::read(path) {
+            Ok(contents) => Sha256::digest(contents).iter().map(|byte| format!("{:02x}", byte)).collect(),
+            Err(_) => "missing".to_string(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let path = self.dir.as_ref()?.join(&key[..2]).join(&key[2..]);
+        match fs::read_to_string(path) {
+            Ok(value) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value)
+            }
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Stores `value` under `key`. Failures only cost a future cache miss, so they are logged
+    /// rather than returned.
+    pub fn put(&self, key: &str, value: &str) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let dir = dir.join(&key[..2]);
+        let path = dir.join(&key[2..]);
+        // Written under a temporary name first so a concurrent reader never sees half an entry.
+        let temp_path = dir.join(format!("{}.{}.tmp", &key[2..], std::process::id()));
+        let written = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&temp_path, value))
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if let Err(err) = written {
+            debug!("Unable to write cache entry {}: {}", path.display(), err);
+            let _ = fs::remove_file(&temp_path);
+        }
+    }
+
+    /// Lookups that found an entry, and lookups that did not.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn stores_entries_by_key() {
+        let dir = TempDir::new("cache");
+        let cache = Cache::new(dir.to_path_buf());
+        let key = Cache::key(&["src/a.js", "export const a = 1;"]);
+        assert_ne!(key, Cache::key(&["src/a.js", "export const a = 2;"]));
+
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, "transformed");
+        assert_eq!(cache.get(&key).as_deref(), Some("transformed"));
+        assert_eq!(cache.stats(), (1, 1));
+
+        assert_eq!(Cache::disabled().get(&key), None);
+
+        let file = dir.join("plugin.config");
+        fs::write(&file, "a").unwrap();
+        let file_key = Cache::file_key(&file);
+        fs::write(&file, "b").unwrap();
+        assert_ne!(Cache::file_key(&file), file_key);
+        assert_eq!(Cache::file_key(&dir.join("gone")), "missing");
+    }
+}
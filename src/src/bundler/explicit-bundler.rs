@@ -1,221 +1,1141 @@
+use std::borrow::Cow;
 use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::collections::{HashSet, HashMap, VecDeque};
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashSet, HashMap};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
 use std::thread;
-use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use notify::{EventKind, RecursiveMode, Watcher};
 use regex::Regex;
-use log::{info, warn, error};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 
-use serde_json::json;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::cache::Cache;
+use crate::config::{Config, SourcemapKind};
+use crate::define;
+use crate::env;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::loader::LoaderRegistry;
+use crate::minifier;
+use crate::plugin::{self, ChangeKind, OutputBundle, PluginContext, PluginManager, RenderedChunk, ResolveKind, TransformResult};
+use crate::registry;
+use crate::resolver::{self, Resolver};
+use crate::sourcemap::{self, ChunkMap};
+use crate::stats::Stats;
+
+/// What a build produced, returned to the caller instead of being printed.
+#[derive(Debug, Default)]
+pub struct BuildResult {
+    pub outputs: Vec<OutputFile>,
+    pub warnings: Vec<Diagnostic>,
+    pub errors: Vec<Diagnostic>,
+    pub timings: Timings,
+    /// Every module in the build, keyed by path.
+    pub modules: BTreeMap<String, ModuleInfo>,
+    /// Other files the build wrote, such as those emitted by plugins.
+    pub assets: Vec<PathBuf>,
+    /// Where `stats.json` was written, when enabled.
+    pub stats_file: Option<PathBuf>,
+}
+
+impl BuildResult {
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// A bundle written for one entry.
+#[derive(Debug, Clone)]
+pub struct OutputFile {
+    pub entry: String,
+    pub path: PathBuf,
+    /// Size in bytes.
+    pub size: usize,
+    pub sourcemap: Option<PathBuf>,
+    /// The modules concatenated into the bundle, in order.
+    pub modules: Vec<String>,
+}
+
+/// A module of the build and the modules it imports.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleInfo {
+    pub imports: Vec<String>,
+    /// Size in bytes as read from disk.
+    pub original_size: usize,
+    /// Size in bytes after transforms, as concatenated into the bundle.
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Reading, transforming and resolving every module.
+    pub modules: Duration,
+    /// Concatenating, minifying and writing the outputs.
+    pub emit: Duration,
+    /// Time spent loading and transforming modules, summed over the worker threads.
+    pub transform: Duration,
+    /// Time spent resolving imports, summed over the worker threads.
+    pub resolve: Duration,
+    pub total: Duration,
+}
 
 pub struct Bundler {
     config: Arc<Config>,
     plugins: Arc<PluginManager>,
-    cache: Arc<Mutex<HashMap<String, String>>>,
+    loaders: Arc<LoaderRegistry>,
+    resolver: Arc<Resolver>,
+    cache: Arc<Cache>,
     dependency_graph: Arc<Mutex<HashMap<String, HashSet<String>>>>,
-    sourcemap_generator: Arc<Mutex<SourceMapGenerator>>,
     bundle_strategy: Arc<Mutex<BundleStrategy>>,
+    /// Modules of the last build, reused by [`Bundler::rebuild`].
+    modules: Mutex<HashMap<String, ModuleRecord>>,
+    /// A rebuild after a failed build rewrites every output.
+    last_failed: AtomicBool,
+    /// Files plugins asked to watch; a change to one rebuilds everything.
+    watch_files: Mutex<HashSet<String>>,
+    /// What plugins reported between builds, from `watch_change`, carried into the next build.
+    plugin_context: Mutex<PluginContext>,
+    /// Every chunk written so far, by entry, and the assets written alongside them. A rebuild
+    /// only rewrites some chunks, but its stats cover them all.
+    outputs: Mutex<BTreeMap<String, OutputFile>>,
+    assets: Mutex<BTreeSet<PathBuf>>,
 }
 
+/// How long a watched build waits for further changes before rebuilding.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(50);
+
 impl Bundler {
     pub fn new(config: Config, plugins: PluginManager) -> Self {
+        let cache = if config.cache.enabled {
+            Cache::new(&config.cache.dir)
+        } else {
+            Cache::disabled()
+        };
+        let loaders = LoaderRegistry::new(&config, plugins.loaders());
+        let resolver = Resolver::new(&config.resolve).with_condition(config.mode.as_str());
         Self {
             config: Arc::new(config),
             plugins: Arc::new(plugins),
-            cache: Arc::new(Mutex::new(HashMap::new())),
+            loaders: Arc::new(loaders),
+            resolver: Arc::new(resolver),
+            cache: Arc::new(cache),
             dependency_graph: Arc::new(Mutex::new(HashMap::new())),
-            sourcemap_generator: Arc::new(Mutex::new(SourceMapGenerator::new())),
-            bundle_strategy: Arc::new(Mutex::new(BundleStrategy::default())),
+            bundle_strategy: Arc::new(Mutex::new(BundleStrategy)),
+            modules: Mutex::new(HashMap::new()),
+            last_failed: AtomicBool::new(false),
+            watch_files: Mutex::new(HashSet::new()),
+            plugin_context: Mutex::new(PluginContext::default()),
+            outputs: Mutex::new(BTreeMap::new()),
+            assets: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Bundles every configured entry. Failures are collected in the returned
+    /// [`BuildResult`] rather than aborting the process.
+    pub fn bundle(&self) -> BuildResult {
+        self.modules.lock().unwrap().clear();
+        self.dependency_graph.lock().unwrap().clear();
+        self.build(None)
+    }
+
+    /// Rebuilds after the files in `changed` changed. Only those modules and the modules that
+    /// import them, directly or not, are processed again, and only the outputs that contain
+    /// one of them are rewritten and returned.
+    pub fn rebuild(&self, changed: &[PathBuf]) -> BuildResult {
+        // The defines, and with them every module, depend on the env files. What depends on
+        // a plugin's watch files is unknown.
+        let watch_files = self.watch_files.lock().unwrap().clone();
+        if changed
+            .iter()
+            .any(|path| Self::is_env_file(path) || watch_files.contains(&Self::module_key(path)))
+        {
+            return self.bundle();
+        }
+
+        let changed: Vec<String> = changed.iter().map(|path| Self::module_key(path)).collect();
+        let mut dirty: HashSet<String> = changed.iter().cloned().collect();
+        let mut queue = changed;
+        {
+            let graph = self.dependency_graph.lock().unwrap();
+            while let Some(file_path) = queue.pop() {
+                for (importer, imports) in graph.iter() {
+                    if imports.contains(&file_path) && dirty.insert(importer.clone()) {
+                        queue.push(importer.clone());
+                    }
+                }
+            }
+        }
+
+        {
+            let mut modules = self.modules.lock().unwrap();
+            let mut graph = self.dependency_graph.lock().unwrap();
+            for file_path in &dirty {
+                modules.remove(file_path);
+                graph.remove(file_path);
+            }
+        }
+        self.build(Some(&dirty))
+    }
+
+    /// Whether a change to `path` can affect the next build.
+    pub fn affects_build(&self, path: &Path) -> bool {
+        let key = Self::module_key(path);
+        if self.modules.lock().unwrap().contains_key(&key)
+            || self.watch_files.lock().unwrap().contains(&key)
+            || self.dependency_graph.lock().unwrap().values().any(|imports| imports.contains(&key))
+            || Self::is_env_file(path)
+        {
+            return true;
+        }
+        // A new or renamed source file may be what a failed import was looking for.
+        self.last_failed.load(Ordering::Relaxed)
+            && !path.starts_with(&self.config.cache.dir)
+            && path
+                .extension()
+                .is_some_and(|ext| self.config.resolve.extensions.iter().any(|known| ext == known.as_str()))
+    }
+
+    /// Bundles once, then rebuilds whenever a file the build depends on changes. `on_build`
+    /// receives every result along with the files that triggered it, none for the first build.
+    /// Returns when the file watcher stops.
+    pub fn watch(&self, mut on_build: impl FnMut(&BuildResult, &[PathBuf])) -> Result<(), String> {
+        let root = std::env::current_dir().map_err(|err| format!("Unable to read the current directory: {}", err))?;
+        let (tx, rx) = channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|err| format!("Failed to create watcher: {}", err))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|err| format!("Failed to watch {}: {}", root.display(), err))?;
+
+        on_build(&self.bundle(), &[]);
+
+        let mut changed: Vec<PathBuf> = vec![];
+        loop {
+            // Block for the first change, then keep collecting until the files are quiet.
+            let received = if changed.is_empty() {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                rx.recv_timeout(WATCH_DEBOUNCE)
+            };
+
+            match received {
+                Ok(Ok(event)) => {
+                    let kind = match event.kind {
+                        EventKind::Create(_) => ChangeKind::Create,
+                        EventKind::Modify(_) => ChangeKind::Update,
+                        EventKind::Remove(_) => ChangeKind::Delete,
+                        _ => continue,
+                    };
+                    for path in event.paths {
+                        let path = path.strip_prefix(&root).map(Path::to_path_buf).unwrap_or(path);
+                        if self.affects_build(&path) && !changed.contains(&path) {
+                            self.plugins.watch_change(&mut self.plugin_context.lock().unwrap(), &path, kind);
+                            changed.push(path);
+                        }
+                    }
+                }
+                Ok(Err(err)) => warn!("Watch error: {}", err),
+                Err(RecvTimeoutError::Timeout) => {
+                    let result = self.rebuild(&changed);
+                    on_build(&result, &changed);
+                    changed.clear();
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
         }
+        Ok(())
     }
 
-    pub fn bundle(&self) {
+    fn module_key(path: &Path) -> String {
+        resolver::normalize_path(path).to_string_lossy().to_string()
+    }
+
+    fn is_env_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name == ".env" || name.starts_with(".env."))
+    }
+
+    /// Builds the entries, reusing the retained modules and rewriting every output, or with
+    /// `dirty`, only the outputs containing a dirty module.
+    fn build(&self, dirty: Option<&HashSet<String>>) -> BuildResult {
+        let start = Instant::now();
+        let mut result = BuildResult::default();
+        let mut ctx = std::mem::take(&mut *self.plugin_context.lock().unwrap());
+        self.resolver.clear();
+        self.plugins.build_start(&mut ctx);
+        result.errors.extend(ctx.take_errors());
+        if let Err(diagnostic) = self.resolver.load_tsconfig() {
+            result.errors.push(diagnostic);
+        }
+
+        let entries: Vec<(String, String)> = self
+            .config
+            .entries()
+            .into_iter()
+            .map(|(name, entry_file)| {
+                let entry_file = resolver::normalize_path(Path::new(&entry_file));
+                (name, entry_file.to_string_lossy().to_string())
+            })
+            .collect();
+
+        let defines = match env::load(&self.config) {
+            Ok(vars) => Arc::new(self.config.defines(&vars)),
+            Err(diagnostic) => {
+                result.errors.push(diagnostic);
+                Arc::default()
+            }
+        };
+
+        // Everything besides a module's own content that its transformed output depends on.
+        let fingerprint = Arc::new(Cache::key(&[
+            env!("CARGO_PKG_VERSION"),
+            &serde_json::to_string(&*self.config).unwrap_or_default(),
+            &self.plugins.fingerprint(),
+            &serde_json::to_string(&*defines).unwrap_or_default(),
+        ]));
+
+        let retained = std::mem::take(&mut *self.modules.lock().unwrap());
+        let modules = if result.errors.is_empty() {
+            self.build_module_graph(&entries, &defines, &fingerprint, retained, &mut result)
+        } else {
+            HashMap::new()
+        };
+        let (hits, misses) = self.cache.stats();
+        debug!("Transform cache: {} hits, {} misses", hits, misses);
+        result.modules = modules
+            .iter()
+            .map(|(file_path, module)| {
+                let info = ModuleInfo {
+                    imports: module.imports.iter().map(|import| import.resolved_path.clone()).collect(),
+                    original_size: module.original_size,
+                    size: module.content.len(),
+                };
+                (file_path.clone(), info)
+            })
+            .collect();
+        result.timings.modules = start.elapsed();
+
+        if result.errors.is_empty() {
+            let emit_start = Instant::now();
+            let rewrite_all = dirty.is_none() || self.last_failed.load(Ordering::Relaxed);
+            let mut bundle = OutputBundle::new();
+            let mut chunks = vec![];
+            for (name, entry_file) in &entries {
+                let module_order = Self::module_order(entry_file, &modules);
+                let touched = dirty.is_some_and(|dirty| module_order.iter().any(|file_path| dirty.contains(file_path)));
+                if !rewrite_all && !touched {
+                    continue;
+                }
+                match self.render_entry(name, entry_file, module_order, &modules, &mut ctx, &mut bundle) {
+                    Ok(output) => chunks.push(output),
+                    Err(diagnostic) => {
+                        debug!("Failed to render {}: {}", name, diagnostic);
+                        result.errors.push(diagnostic);
+                    }
+                }
+                result.errors.extend(ctx.take_errors());
+                if self.bailed(&result) {
+                    break;
+                }
+            }
+
+            if !self.bailed(&result) {
+                bundle.append(&mut ctx.take_emitted());
+                self.plugins.generate_bundle(&mut ctx, &mut bundle);
+                bundle.append(&mut ctx.take_emitted());
+                result.errors.extend(ctx.take_errors());
+            }
+            if !self.bailed(&result) {
+                Self::write_bundle(&bundle, chunks, &mut result);
+            }
+            result.timings.emit = emit_start.elapsed();
+        }
+
+        self.dependency_graph.lock().unwrap().retain(|file_path, _| modules.contains_key(file_path));
+        *self.modules.lock().unwrap() = modules;
+        self.last_failed.store(!result.errors.is_empty(), Ordering::Relaxed);
+
+        self.plugins.build_end(&mut ctx, &result.errors);
+        Self::write_bundle(&ctx.take_emitted(), vec![], &mut result);
+        result.errors.extend(ctx.take_errors());
+        result.warnings.extend(ctx.take_warnings());
+        self.watch_files
+            .lock()
+            .unwrap()
+            .extend(ctx.take_watch_files().iter().map(|path| Self::module_key(path)));
+
+        let mut outputs = self.outputs.lock().unwrap();
+        let mut assets = self.assets.lock().unwrap();
+        if dirty.is_none() {
+            outputs.clear();
+            assets.clear();
+        }
+        outputs.extend(result.outputs.iter().map(|output| (output.entry.clone(), output.clone())));
+        assets.extend(result.assets.iter().cloned());
+
+        result.timings.total = start.elapsed();
+        let stats_file = PathBuf::from(&self.config.stats.file);
+        if self.config.stats.enabled && !result.errors.is_empty() {
+            // Stats of an earlier build would describe outputs this one did not produce.
+            if let Err(err) = fs::remove_file(&stats_file) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    result.errors.push(
+                        Diagnostic::error("stats/write", format!("Unable to remove stale stats file: {}", err))
+                            .with_file(&stats_file),
+                    );
+                }
+            }
+        } else if self.config.stats.enabled {
+            let outputs: Vec<OutputFile> = outputs.values().cloned().collect();
+            let assets: Vec<PathBuf> = assets.iter().cloned().collect();
+            let stats = Stats::collect(&result.modules, &outputs, &assets, &result.timings);
+            match stats.and_then(|stats| stats.write(&stats_file)) {
+                Ok(()) => result.stats_file = Some(stats_file),
+                Err(diagnostic) => result.errors.push(diagnostic),
+            }
+        }
+        result
+    }
+
+    /// Whether the build stops here because of `bail` and an error a plugin reported.
+    fn bailed(&self, result: &BuildResult) -> bool {
+        self.config.bail && result.errors.iter().any(|error| error.plugin.is_some())
+    }
+
+    /// Processes the entries and everything they import on the worker threads, or with `bail`,
+    /// until a plugin fails.
+    fn build_module_graph(
+        &self,
+        entries: &[(String, String)],
+        defines: &Arc<BTreeMap<String, String>>,
+        fingerprint: &Arc<String>,
+        mut retained: HashMap<String, ModuleRecord>,
+        result: &mut BuildResult,
+    ) -> HashMap<String, ModuleRecord> {
         let (tx, rx): (Sender<BundleTask>, Receiver<BundleTask>) = channel();
+        let (result_tx, result_rx) = channel::<Result<ModuleRecord, Vec<Diagnostic>>>();
+        let rx = Arc::new(Mutex::new(rx));
         let mut workers = vec![];
 
-        for _ in 0..self.config.max_threads {
-            let tx_clone = tx.clone();
-            let rx_clone = rx.clone();
+        for _ in 0..self.config.threads() {
+            let rx_clone = Arc::clone(&rx);
+            let result_tx_clone = result_tx.clone();
             let config_clone = Arc::clone(&self.config);
+            let defines_clone = Arc::clone(defines);
+            let fingerprint_clone = Arc::clone(fingerprint);
             let plugins_clone = Arc::clone(&self.plugins);
+            let loaders_clone = Arc::clone(&self.loaders);
+            let resolver_clone = Arc::clone(&self.resolver);
             let cache_clone = Arc::clone(&self.cache);
-            let dependency_graph_clone = Arc::clone(&self.dependency_graph);
-            let sourcemap_generator_clone = Arc::clone(&self.sourcemap_generator);
-
-            let worker = thread::spawn(move || {
-                while let Ok(task) = rx_clone.recv() {
-                    if let Err(e) = task.process(
-                        &config_clone,
-                        &plugins_clone,
-                        &cache_clone,
-                        &dependency_graph_clone,
-                        &sourcemap_generator_clone,
-                    ) {
-                        error!("Failed to process task: {}", e);
-                    }
+
+            let worker = thread::spawn(move || loop {
+                let task = match rx_clone.lock().unwrap().recv() {
+                    Ok(task) => task,
+                    Err(_) => break,
+                };
+                let result = task.process(
+                    &config_clone,
+                    &defines_clone,
+                    &plugins_clone,
+                    &loaders_clone,
+                    &resolver_clone,
+                    &cache_clone,
+                    &fingerprint_clone,
+                );
+                if result_tx_clone.send(result).is_err() {
+                    break;
                 }
             });
             workers.push(worker);
         }
+        drop(result_tx);
 
         let mut visited = HashSet::new();
-        let mut bundle_content = String::new();
-        let entry_file = self.config.entry_file.clone();
-        tx.send(BundleTask::new(entry_file, &mut visited, &mut bundle_content))
-            .expect("Failed to send initial task");
+        let mut modules = HashMap::new();
+        let mut ready = vec![];
+        let mut pending = 0;
+
+        // Entries share one module graph, so a module imported by several is processed once.
+        for (_, entry_file) in entries {
+            if visited.insert(entry_file.clone()) {
+                Self::schedule(entry_file.clone(), &mut retained, &tx, &mut ready, &mut pending);
+            }
+        }
+
+        loop {
+            let processed = if let Some(module) = ready.pop() {
+                Ok((module, true))
+            } else if pending > 0 {
+                let Ok(processed) = result_rx.recv() else {
+                    result.errors.push(Diagnostic::error("bundle/internal", "Bundler workers exited unexpectedly"));
+                    break;
+                };
+                pending -= 1;
+                processed.map(|module| (module, false))
+            } else {
+                break;
+            };
+
+            match processed {
+                Ok((module, reused)) => {
+                    if !reused {
+                        for dependency in &module.dependencies {
+                            Self::track_dependency(&module.file_path, dependency, &self.dependency_graph);
+                        }
+                    }
+                    for import in &module.imports {
+                        if !reused {
+                            Self::track_dependency(&module.file_path, &import.resolved_path, &self.dependency_graph);
+                        }
+                        if visited.insert(import.resolved_path.clone()) {
+                            Self::schedule(import.resolved_path.clone(), &mut retained, &tx, &mut ready, &mut pending);
+                        }
+                    }
+                    if !reused {
+                        result.warnings.extend(module.warnings.iter().cloned());
+                        result.timings.transform += module.transform_time;
+                        result.timings.resolve += module.resolve_time;
+                    }
+                    modules.insert(module.file_path.clone(), module);
+                }
+                Err(diagnostics) => {
+                    for diagnostic in &diagnostics {
+                        debug!("Failed to process task: {}", diagnostic);
+                    }
+                    result.errors.extend(diagnostics);
+                    if self.bailed(result) {
+                        break;
+                    }
+                }
+            }
+        }
 
+        drop(tx);
+        // Modules still queued when bailing are never processed.
+        for _ in rx.lock().unwrap().try_iter() {}
         for worker in workers {
-            worker.join().expect("Worker thread panicked");
+            if worker.join().is_err() {
+                result.errors.push(Diagnostic::error("bundle/internal", "Bundler worker thread panicked"));
+            }
         }
 
-        let final_content = {
+        modules
+    }
+
+    /// Queues `file_path` for the workers, unless the previous build's record is retained.
+    fn schedule(
+        file_path: String,
+        retained: &mut HashMap<String, ModuleRecord>,
+        tx: &Sender<BundleTask>,
+        ready: &mut Vec<ModuleRecord>,
+        pending: &mut usize,
+    ) {
+        match retained.remove(&file_path) {
+            Some(module) => ready.push(module),
+            None => {
+                if tx.send(BundleTask::new(file_path)).is_ok() {
+                    *pending += 1;
+                }
+            }
+        }
+    }
+
+    /// Concatenates the modules of `entry_file`, in `module_order`, into the entry's output file
+    /// and adds it and its source map to `bundle`.
+    fn render_entry(
+        &self,
+        name: &str,
+        entry_file: &str,
+        module_order: Vec<String>,
+        modules: &HashMap<String, ModuleRecord>,
+        ctx: &mut PluginContext,
+        bundle: &mut OutputBundle,
+    ) -> Result<OutputFile, Diagnostic> {
+        let output_file = PathBuf::from(self.config.output_file(name));
+        let sourcemap_file = PathBuf::from(self.config.sourcemap_file(name));
+        let sourcemap_kind = self.config.sourcemap_kind();
+        let mut chunk_map = self.config.sourcemap_enabled().then(|| {
+            let mut chunk_map = ChunkMap::new(&output_file.file_name().unwrap_or_default().to_string_lossy());
+            // Sources resolve against the map's URL, which for an inline map is the chunk's.
+            let map_file = if sourcemap_kind == SourcemapKind::Inline { &output_file } else { &sourcemap_file };
+            chunk_map.set_source_root(&source_root(map_file.parent().unwrap_or(Path::new(""))));
+            chunk_map
+        });
+
+        let registered = Self::registered_modules(entry_file, &module_order, modules)?;
+        let mut bundle_content = String::new();
+        if !registered.is_empty() {
+            bundle_content.push_str(registry::RUNTIME);
+            if let Some(chunk_map) = &mut chunk_map {
+                chunk_map.add_unmapped(registry::RUNTIME.lines().count() as u32);
+            }
+        }
+        for file_path in &module_order {
+            let module = &modules[file_path];
+            Self::copy_asset(module, bundle)?;
+            let id = plugin::display_id(file_path);
+            let content = Self::resolve_urls(module, modules, bundle)?;
+            let (start, body, end) = if registered.contains(file_path.as_str()) {
+                let (body, exports) = registry::to_commonjs(file_path, &content)?;
+                (registry::factory_start(&id), body, format!("{}{}", exports, registry::FACTORY_END))
+            } else {
+                (String::new(), content.into_owned(), String::new())
+            };
+            bundle_content.push_str(&format!("// {}\n{}", id, start));
+            bundle_content.push_str(&body);
+            if !body.ends_with('\n') {
+                bundle_content.push('\n');
+            }
+            bundle_content.push_str(&end);
+            if let Some(chunk_map) = &mut chunk_map {
+                chunk_map.add_unmapped(1 + start.lines().count() as u32);
+                chunk_map.add_module(&id, &body, module.map.as_deref()).map_err(|e| {
+                    Diagnostic::error("bundle/sourcemap", format!("Invalid source map: {}", e)).with_file(file_path)
+                })?;
+                chunk_map.add_unmapped(end.lines().count() as u32);
+            }
+        }
+        let mut map = chunk_map.map(|chunk_map| sourcemap::to_json(&chunk_map.finish()));
+
+        let mut final_content = {
             let strategy = self.bundle_strategy.lock().unwrap();
             strategy.finalize(&bundle_content, &self.dependency_graph)
         };
 
-        fs::write(&self.config.output_file, final_content)
-            .expect("Unable to write to output file");
+        let chunk = RenderedChunk {
+            entry: name.to_string(),
+            path: output_file.clone(),
+            modules: module_order,
+        };
+        final_content = self.plugins.render_chunk(ctx, &chunk, final_content);
+
+        if self.config.minify_enabled() {
+            let keep_comments = self.config.minify.keep_comments;
+            let minified = match &map {
+                Some(chunk_map) => minifier::minify_code_with_map(&final_content, keep_comments, chunk_map)
+                    .map(|(code, chunk_map)| (code, Some(chunk_map))),
+                None => minifier::minify_code(&final_content, keep_comments).map(|code| (code, None)),
+            };
+            (final_content, map) =
+                minified.map_err(|e| Diagnostic::error("bundle/minify", e).with_file(entry_file))?;
+        }
+
+        let mut sourcemap = None;
+        if let Some(sourcemap_content) = map {
+            if sourcemap_kind == SourcemapKind::Inline {
+                final_content.push_str(&format!(
+                    "\n//# sourceMappingURL=data:application/json;base64,{}\n",
+                    STANDARD.encode(&sourcemap_content)
+                ));
+            } else {
+                bundle.insert(sourcemap_file.clone(), sourcemap_content.into_bytes());
+                if sourcemap_kind == SourcemapKind::External {
+                    let url = sourcemap_file.file_name().unwrap_or_default().to_string_lossy();
+                    final_content.push_str(&format!("\n//# sourceMappingURL={}\n", url));
+                }
+                sourcemap = Some(sourcemap_file);
+            }
+        }
 
-        if self.config.generate_sourcemaps {
-            let sourcemap = self.sourcemap_generator.lock().unwrap().generate();
-            fs::write(&self.config.sourcemap_file, sourcemap)
-                .expect("Unable to write sourcemap file");
+        let size = final_content.len();
+        bundle.insert(output_file.clone(), final_content.into_bytes());
+        Ok(OutputFile {
+            entry: chunk.entry,
+            path: output_file,
+            size,
+            sourcemap,
+            modules: chunk.modules,
+        })
+    }
+
+    /// The modules of a chunk that `require()` or `import()` load, which go in its registry. None
+    /// of them may also be imported statically, whose importers expect its bindings in the
+    /// chunk's scope.
+    fn registered_modules<'a>(
+        entry_file: &'a str,
+        module_order: &'a [String],
+        modules: &'a HashMap<String, ModuleRecord>,
+    ) -> Result<HashSet<&'a str>, Diagnostic> {
+        let mut registered = HashSet::new();
+        let mut hoisted = HashSet::from([entry_file]);
+        for file_path in module_order {
+            for import in &modules[file_path].imports {
+                match import.reference {
+                    Reference::Hoisted => {
+                        hoisted.insert(import.resolved_path.as_str());
+                    }
+                    Reference::Registry => {
+                        registered.insert(import.resolved_path.as_str());
+                    }
+                    Reference::Url => {}
+                }
+            }
+        }
+        match registered.intersection(&hoisted).next() {
+            Some(file_path) => Err(Diagnostic::error(
+                "bundle/mixed-import",
+                format!("'{}' is imported both statically and with require() or import()", plugin::display_id(file_path)),
+            )
+            .with_note("statically imported modules share the chunk's scope, the others are wrapped in a function")
+            .with_fix("import it the same way everywhere")),
+            None => Ok(registered),
+        }
+    }
+
+    /// Copies the file the `file` loader emits for `module` into `bundle`.
+    fn copy_asset(module: &ModuleRecord, bundle: &mut OutputBundle) -> Result<(), Diagnostic> {
+        if let Some(asset) = &module.asset {
+            let contents = fs::read(&module.file_path).map_err(|e| {
+                Diagnostic::error("bundle/read", format!("Unable to read file: {}", e)).with_file(&module.file_path)
+            })?;
+            bundle.insert(asset.clone(), contents);
+        }
+        Ok(())
+    }
+
+    /// `module`'s content with each `url()` pointing at what its module loads as: the URL of the
+    /// file the `file` loader emits, which is copied into `bundle`, or a `dataurl`.
+    fn resolve_urls<'a>(
+        module: &'a ModuleRecord,
+        modules: &HashMap<String, ModuleRecord>,
+        bundle: &mut OutputBundle,
+    ) -> Result<Cow<'a, str>, Diagnostic> {
+        let mut content = Cow::Borrowed(module.content.as_str());
+        for import in module.imports.iter().filter(|import| import.reference == Reference::Url) {
+            let id = plugin::display_id(&import.resolved_path);
+            let target = &modules[&import.resolved_path];
+            let Some(url) = loaded_url(&target.content) else {
+                let extension = Path::new(&import.resolved_path).extension().unwrap_or_default().to_string_lossy();
+                return Err(Diagnostic::error("bundle/css-url", format!("'{}' does not load as a URL", id))
+                    .with_file(&module.file_path)
+                    .with_fix(format!("load it with the `file` or `dataurl` loader, e.g. `\"{}\": \"file\"`", extension)));
+            };
+            Self::copy_asset(target, bundle)?;
+            content = Cow::Owned(content.replace(&url_placeholder(&id), &serde_json::Value::from(url).to_string()));
+        }
+        Ok(content)
+    }
+
+    /// Writes every file of `bundle`, recording the entries' `chunks` among them as outputs and
+    /// the rest as assets. Chunks a plugin removed from the bundle are dropped.
+    fn write_bundle(bundle: &OutputBundle, chunks: Vec<OutputFile>, result: &mut BuildResult) {
+        for (path, contents) in bundle {
+            let written = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+                _ => Ok(()),
+            }
+            .and_then(|_| fs::write(path, contents));
+            if let Err(e) = written {
+                result.errors.push(
+                    Diagnostic::error("bundle/write", format!("Unable to write output file: {}", e)).with_file(path),
+                );
+            }
+        }
+
+        let mut known = HashSet::new();
+        for mut chunk in chunks {
+            let Some(contents) = bundle.get(&chunk.path) else {
+                continue;
+            };
+            info!("Bundling complete: {}", chunk.path.display());
+            chunk.size = contents.len();
+            chunk.sourcemap = chunk.sourcemap.filter(|sourcemap| bundle.contains_key(sourcemap));
+            known.insert(chunk.path.clone());
+            known.extend(chunk.sourcemap.clone());
+            result.outputs.push(chunk);
+        }
+        result
+            .assets
+            .extend(bundle.keys().filter(|path| !known.contains(*path)).cloned());
+    }
+
+    /// Orders modules so every module comes after the modules it imports.
+    fn module_order(entry_file: &str, modules: &HashMap<String, ModuleRecord>) -> Vec<String> {
+        fn visit(
+            file_path: &str,
+            modules: &HashMap<String, ModuleRecord>,
+            seen: &mut HashSet<String>,
+            order: &mut Vec<String>,
+        ) {
+            if !seen.insert(file_path.to_string()) {
+                return;
+            }
+            if let Some(module) = modules.get(file_path) {
+                // What `url()` refers to is not bundled as a module.
+                for import in module.imports.iter().filter(|import| import.reference != Reference::Url) {
+                    visit(&import.resolved_path, modules, seen, order);
+                }
+                order.push(file_path.to_string());
+            }
         }
 
-        info!("Bundling complete: {}", self.config.output_file);
+        let mut seen = HashSet::new();
+        let mut order = vec![];
+        visit(entry_file, modules, &mut seen, &mut order);
+        order
     }
+
+    fn track_dependency(
+        file_path: &str,
+        resolved_path: &str,
+        dependency_graph: &Mutex<HashMap<String, HashSet<String>>>,
+    ) {
+        let mut graph = dependency_graph.lock().unwrap();
+        graph
+            .entry(file_path.to_string())
+            .or_default()
+            .insert(resolved_path.to_string());
+    }
+}
+
+/// A processed module: its bundled content and the imports it pulls in.
+struct ModuleRecord {
+    file_path: String,
+    original_size: usize,
+    content: String,
+    /// Maps `content` back to the module's source; `None` if every line is where it was.
+    map: Option<String>,
+    /// Where the `file` loader copies the module to.
+    asset: Option<PathBuf>,
+    imports: Vec<ImportRecord>,
+    /// Files plugins read to process it, besides its imports.
+    dependencies: Vec<String>,
+    warnings: Vec<Diagnostic>,
+    transform_time: Duration,
+    resolve_time: Duration,
+}
+
+/// A module as loaded and transformed, cached by its contents.
+#[derive(Serialize, Deserialize)]
+struct LoadedModule {
+    /// Size in bytes as read.
+    original_size: usize,
+    result: TransformResult,
+    /// What plugins reported while loading and transforming it.
+    warnings: Vec<Diagnostic>,
+    asset: Option<PathBuf>,
+    /// Files plugins read while loading and transforming it, with the hash of their contents
+    /// then; the cached module is stale once one changes.
+    dependencies: BTreeMap<PathBuf, String>,
+}
+
+struct ImportRecord {
+    resolved_path: String,
+    reference: Reference,
+}
+
+/// How a module refers to one of its imports, which decides how the import is bundled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reference {
+    /// `import ... from` or `@import`: the module is concatenated ahead of its importer.
+    Hoisted,
+    /// `require()` or `import()`: the module is wrapped in the chunk's registry and looked up.
+    Registry,
+    /// `url()` in CSS: replaced with the URL the module loads as.
+    Url,
+}
+
+/// An import in a module's source.
+struct ImportSite {
+    kind: ResolveKind,
+    reference: Reference,
+    /// The text the bundle replaces: the whole statement, call or `url()`.
+    statement: Range<usize>,
+    specifier: Range<usize>,
 }
 
 struct BundleTask {
     file_path: String,
-    visited: HashSet<String>,
-    bundle_content: String,
 }
 
 impl BundleTask {
-    fn new(file_path: String, visited: &mut HashSet<String>, bundle_content: &mut String) -> Self {
-        Self {
-            file_path,
-            visited: visited.clone(),
-            bundle_content: bundle_content.clone(),
-        }
+    fn new(file_path: String) -> Self {
+        Self { file_path }
     }
 
+    /// Reads, transforms and resolves one module, reporting every import that fails to resolve.
+    #[allow(clippy::too_many_arguments)]
     fn process(
         self,
         config: &Config,
+        defines: &BTreeMap<String, String>,
         plugins: &PluginManager,
-        cache: &Mutex<HashMap<String, String>>,
-        dependency_graph: &Mutex<HashMap<String, HashSet<String>>>,
-        sourcemap_generator: &Mutex<SourceMapGenerator>,
-    ) -> Result<(), String> {
+        loaders: &LoaderRegistry,
+        resolver: &Resolver,
+        cache: &Cache,
+        fingerprint: &str,
+    ) -> Result<ModuleRecord, Vec<Diagnostic>> {
         let file_path = self.file_path;
-        let mut visited = self.visited;
-        let mut bundle_content = self.bundle_content;
-
-        if visited.contains(&file_path) {
-            return Ok(());
-        }
+        let mut ctx = PluginContext::default();
 
-        visited.insert(file_path.clone());
+        let transform_start = Instant::now();
+        let loaded = Self::read_and_transform_file(&file_path, plugins, loaders, &mut ctx, cache, fingerprint)
+            .map_err(|e| vec![e])?;
+        let (transformed, mut warnings) = (loaded.result, loaded.warnings);
+        let mut transform_time = transform_start.elapsed();
 
-        let content = Self::read_and_transform_file(&file_path, plugins, cache)?;
+        // Runs before the import scan, so imports in branches the defines make dead are never
+        // followed. Its map on its own takes diagnostic spans back to the module as loaded.
+        let define_start = Instant::now();
+        let define_key = Cache::key(&["define", fingerprint, &file_path, &transformed.code]);
+        let defined = match cache.get(&define_key).and_then(|cached| serde_json::from_str(&cached).ok()) {
+            Some(cached) => cached,
+            None => {
+                let output: TransformResult =
+                    define::apply(&file_path, &transformed.code, None, defines).map_err(|e| vec![e])?;
+                cache.put(&define_key, &serde_json::to_string(&output).unwrap_or_default());
+                output
+            }
+        };
+        let sourcemap_error = |e: String| vec![Diagnostic::error("bundle/sourcemap", e).with_file(&file_path)];
+        let define_map = defined.map.as_deref().map(sourcemap::parse).transpose().map_err(sourcemap_error)?;
+        let map = match &defined.map {
+            Some(map) => Some(sourcemap::compose(map, transformed.map.as_deref()).map_err(sourcemap_error)?),
+            None => transformed.map,
+        };
+        let content = defined.code;
+        transform_time += define_start.elapsed();
+        let mut resolve_time = Duration::ZERO;
+        let mut imports = vec![];
+        let mut errors = vec![];
+        let mut edits = vec![];
 
-        bundle_content.push_str(&format!("// {}\n", file_path));
-        bundle_content.push_str(&content);
+        for site in Self::find_imports(&file_path, &content) {
+            let import_path = content[site.specifier.clone()].to_string();
+            let span = Span::from_offsets(&content, site.specifier.start, site.specifier.end);
+            let span = define_map.as_ref().map_or(span, |map| sourcemap::original_span(map, span));
 
-        let import_re = Regex::new(r#"import\s+.*?from\s+['"](.*?)['"]"#)
-            .map_err(|e| format!("Failed to compile regex: {}", e))?;
-        let mut imports = vec![];
+            let resolve_start = Instant::now();
+            let resolved =
+                resolver::resolve_path(&file_path, &import_path, site.kind, plugins, &mut ctx, resolver);
+            resolve_time += resolve_start.elapsed();
+            let resolved_path = match resolved {
+                Ok(resolved) => resolved.id,
+                Err(error) => {
+                    let diagnostic = Diagnostic::from(error);
+                    // Plugins may point at something more specific than the specifier.
+                    let span = diagnostic.span.unwrap_or(span);
+                    errors.push(diagnostic.with_span(span));
+                    continue;
+                }
+            };
 
-        for cap in import_re.captures_iter(&content) {
-            let import_path = cap[1].to_string();
-            let resolved_path = Self::resolve_import(&file_path, &import_path, plugins)?;
+            let id = plugin::display_id(&resolved_path);
+            let replacement = match site.reference {
+                // The imported module is hoisted into the bundle ahead of this one.
+                Reference::Hoisted => String::new(),
+                Reference::Registry if site.kind == ResolveKind::DynamicImport => registry::import_call(&id),
+                Reference::Registry => registry::require_call(&id),
+                Reference::Url => format!("url({})", url_placeholder(&id)),
+            };
+            edits.push((site.statement.clone(), replacement));
 
-            if config.tree_shaking && Self::is_unused(&resolved_path, &content) {
-                warn!("Tree shaking: removing unused import {}", import_path);
+            let unused = site.kind == ResolveKind::Import && {
+                let statement = site.statement;
+                let rest = [&content[..statement.start], &content[statement.end..]].concat();
+                Self::is_unused(&content[statement], &rest)
+            };
+            if config.tree_shaking_enabled() && unused {
+                warn!("Tree shaking: removing unused import {} from {}", import_path, file_path);
+                warnings.push(
+                    Diagnostic::warning("bundle/unused-import", format!("Removed unused import '{}'", import_path))
+                        .with_file(&file_path)
+                        .with_span(span),
+                );
                 continue;
             }
 
-            imports.push(resolved_path.clone());
-            Self::track_dependency(&file_path, &resolved_path, dependency_graph);
-
-            if config.code_splitting {
-                let split_bundle = Self::split_code(&resolved_path, plugins)?;
-                bundle_content.push_str(&split_bundle);
-            }
+            imports.push(ImportRecord { resolved_path, reference: site.reference });
+        }
 
-            sourcemap_generator
-                .lock()
-                .unwrap()
-                .add_mapping(&file_path, &content);
+        let mut bundle_content = content.clone();
+        for (range, replacement) in edits.into_iter().rev() {
+            bundle_content.replace_range(range, &replacement);
         }
 
-        for import in imports {
-            let task = BundleTask::new(import, &mut visited, &mut bundle_content);
-            let tx_clone = channel::<BundleTask>().0;
-            tx_clone.send(task).expect("Failed to send task");
+        warnings.extend(ctx.take_warnings());
+        let dependencies = loaded
+            .dependencies
+            .keys()
+            .chain(&ctx.take_watch_files())
+            .map(|path| Bundler::module_key(path))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
         }
 
-        Ok(())
+        Ok(ModuleRecord {
+            file_path,
+            original_size: loaded.original_size,
+            content: bundle_content,
+            map,
+            asset: loaded.asset,
+            imports,
+            dependencies,
+            warnings,
+            transform_time,
+            resolve_time,
+        })
     }
 
+    /// Loads and transforms a module: through a plugin, or else the loader for its extension.
     fn read_and_transform_file(
         file_path: &str,
         plugins: &PluginManager,
-        cache: &Mutex<HashMap<String, String>>,
-    ) -> Result<String, String> {
-        {
-            let cache = cache.lock().unwrap();
-            if let Some(cached_content) = cache.get(file_path) {
-                return Ok(cached_content.clone());
+        loaders: &LoaderRegistry,
+        ctx: &mut PluginContext,
+        cache: &Cache,
+        fingerprint: &str,
+    ) -> Result<LoadedModule, Diagnostic> {
+        let virtual_module = plugin::is_virtual(file_path);
+        let bytes = if virtual_module {
+            plugins
+                .load(ctx, file_path, "")?
+                .ok_or_else(|| {
+                    Diagnostic::error("bundle/virtual", format!("No plugin loaded virtual module `{}`", plugin::display_id(file_path)))
+                        .with_note("virtual modules are not read from disk; a plugin's `on_load` must return their code")
+                })?
+                .into_bytes()
+        } else {
+            fs::read(file_path).map_err(|e| {
+                Diagnostic::error("bundle/read", format!("Unable to read file: {}", e)).with_file(file_path)
+            })?
+        };
+        let text = std::str::from_utf8(&bytes);
+        // Binary files are keyed by all of their bytes, which a lossy conversion would not be.
+        let content = match text {
+            Ok(text) => text.to_string(),
+            Err(_) => STANDARD.encode(&bytes),
+        };
+
+        let key = Cache::key(&["transform", fingerprint, file_path, &content]);
+        if let Some(cached) = cache.get(&key).and_then(|cached| serde_json::from_str::<LoadedModule>(&cached).ok()) {
+            if cached.dependencies.iter().all(|(path, hash)| Cache::file_key(path) == *hash) {
+                return Ok(cached);
             }
         }
 
-        let content = fs::read_to_string(file_path)
-            .map_err(|e| format!("Unable to read file {}: {}", file_path, e))?;
+        let mut asset = None;
+        let loaded_content = if virtual_module {
+            content
+        } else if let Some(loaded_content) = plugins.load(ctx, file_path, &String::from_utf8_lossy(&bytes))? {
+            loaded_content
+        } else if let Some(loader) = loaders.for_path(file_path)? {
+            let loaded = loader.load(file_path, &bytes).map_err(|diagnostic| match diagnostic.file {
+                Some(_) => diagnostic,
+                None => diagnostic.with_file(file_path),
+            })?;
+            asset = loaded.asset;
+            loaded.code
+        } else {
+            text.map(str::to_string).map_err(|e| {
+                Diagnostic::error("bundle/read", format!("Unable to read file: {}", e))
+                    .with_file(file_path)
+                    .with_note("pick a loader for the file's extension in `loaders`, e.g. `\"png\": \"file\"`")
+            })?
+        };
+        let result = plugins.transform(ctx, file_path, loaded_content)?;
 
-        let transformed_content = plugins.load(file_path, &content).unwrap_or(content);
+        let dependencies = ctx.take_watch_files().into_iter().map(|path| {
+            let hash = Cache::file_key(&path);
+            (path, hash)
+        });
+        let loaded = LoadedModule {
+            original_size: bytes.len(),
+            result,
+            warnings: ctx.take_warnings(),
+            asset,
+            dependencies: dependencies.collect(),
+        };
+        cache.put(&key, &serde_json::to_string(&loaded).unwrap_or_default());
+        Ok(loaded)
+    }
 
-        cache.lock().unwrap().insert(file_path.to_string(), transformed_content.clone());
+    /// The imports in `content`, in source order: `import ... from`, `require()` and `import()`
+    /// in scripts, and `@import` and `url()` in stylesheets. URLs that point elsewhere, such as
+    /// `data:` and `https:` ones, are left alone.
+    fn find_imports(file_path: &str, content: &str) -> Vec<ImportSite> {
+        let patterns: &[(ResolveKind, &str, Reference)] = if file_path.ends_with(".css") {
+            &[
+                (ResolveKind::CssUrl, r#"@import\s+['"]([^'"]+)['"]\s*;?"#, Reference::Hoisted),
+                (ResolveKind::CssUrl, r#"url\(\s*['"]?([^'")\s]+)['"]?\s*\)"#, Reference::Url),
+            ]
+        } else {
+            &[
+                (ResolveKind::Import, r#"import\s+.*?from\s+['"](.*?)['"];?"#, Reference::Hoisted),
+                (ResolveKind::DynamicImport, r#"\bimport\s*\(\s*['"]([^'"]+)['"]\s*\)"#, Reference::Registry),
+                (ResolveKind::Require, r#"\brequire\s*\(\s*['"]([^'"]+)['"]\s*\)"#, Reference::Registry),
+            ]
+        };
 
-        Ok(transformed_content)
+        let mut sites: Vec<ImportSite> = vec![];
+        for (kind, pattern, reference) in patterns {
+            for cap in Regex::new(pattern).unwrap().captures_iter(content) {
+                let (statement, specifier) = (cap.get(0).unwrap().range(), cap.get(1).unwrap().range());
+                let external = ["data:", "http:", "https:", "//", "#"]
+                    .iter()
+                    .any(|prefix| content[specifier.clone()].starts_with(prefix));
+                // Where two patterns match the same text, the earlier one wins.
+                let overlaps = sites
+                    .iter()
+                    .any(|site| site.statement.start < statement.end && statement.start < site.statement.end);
+                if !external && !overlaps {
+                    sites.push(ImportSite { kind: *kind, reference: *reference, statement, specifier });
+                }
+            }
+        }
+        sites.sort_by_key(|site| site.specifier.start);
+        sites
     }
 
-    fn resolve_import(
-        file_path: &str,
-        import_path: &str,
-        plugins: &PluginManager,
-    ) -> Result<String, String> {
-        // Custom path resolution logic here
-        Ok(format!("{}/{}", file_path, import_path))
+    /// Whether none of the names the import `statement` binds appear in `rest`, the module
+    /// without it. An import that binds nothing is kept for its side effects.
+    fn is_unused(statement: &str, rest: &str) -> bool {
+        let Some(clause) = Regex::new(r#"^import\s+(.*?)\s*from\s+['"]"#).unwrap().captures(statement) else {
+            return false;
+        };
+        // `a`, `* as a`, `{ b as a }` and `{ a }` all bind `a`.
+        let locals: Vec<&str> = clause[1]
+            .split([',', '{', '}'])
+            .filter_map(|binding| binding.split_whitespace().last())
+            .collect();
+        !locals.is_empty()
+            && locals.iter().all(|local| {
+                let pattern = format!(r"(^|[^\w$]){}($|[^\w$])", regex::escape(local));
+                !Regex::new(&pattern).unwrap().is_match(rest)
+            })
     }
+}
 
-    fn track_dependency(
-        file_path: &str,
-        resolved_path: &str,
-        dependency_graph: &Mutex<HashMap<String, HashSet<String>>>,
-    ) {
-        let mut graph = dependency_graph.lock().unwrap();
-        graph
-            .entry(file_path.to_string())
-            .or_insert_with(HashSet::new)
-            .insert(resolved_path.to_string());
-    }
+/// Where the URL of the module `id` goes in a stylesheet until the chunk is rendered.
+fn url_placeholder(id: &str) -> String {
+    format!("__hyperpack_url({})", serde_json::Value::from(id))
+}
 
-    fn is_unused(resolved_path: &str, content: &str) -> bool {
-        // Logic to determine if an import is unused
-        false
-    }
+/// The URL a module loaded by the `file` or `dataurl` loader exports.
+fn loaded_url(content: &str) -> Option<String> {
+    let value = content.trim().strip_prefix("export default ")?.strip_suffix(';')?;
+    serde_json::from_str(value).ok()
+}
 
-    fn split_code(
-        resolved_path: &str,
-        plugins: &PluginManager,
-    ) -> Result<String, String> {
-        // Logic for code splitting
-        Ok(format!("// Code split: {}\n", resolved_path))
+/// The path from `dir` back to the working directory, which module paths are relative to.
+fn source_root(dir: &Path) -> String {
+    let mut root = vec![];
+    for component in dir.components() {
+        match component {
+            Component::Normal(_) => root.push(".."),
+            Component::CurDir => {}
+            _ => return std::env::current_dir().map(|cwd| cwd.display().to_string()).unwrap_or_default(),
+        }
     }
+    root.join("/")
 }
 
 struct BundleStrategy;
@@ -224,7 +1144,7 @@ impl BundleStrategy {
     fn finalize(
         &self,
         bundle_content: &str,
-        dependency_graph: &Arc<Mutex<HashMap<String, HashSet<String>>>>,
+        _dependency_graph: &Arc<Mutex<HashMap<String, HashSet<String>>>>,
     ) -> String {
         // Advanced finalization logic (e.g., combining chunks, handling circular dependencies)
         bundle_content.to_string()
@@ -237,46 +1157,470 @@ impl Default for BundleStrategy {
     }
 }
 
-struct Config {
-    entry_file: String,
-    output_file: String,
-    sourcemap_file: String,
-    max_threads: usize,
-    generate_sourcemaps: bool,
-    minify: bool,
-    tree_shaking: bool,
-    code_splitting: bool,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Mode, PluginConfig, ResolveConfig};
+    use crate::plugin::{HookResult, Plugin, ResolveArgs, ResolveResult};
+    use crate::testing::TempDir;
 
-struct PluginManager;
+    /// Records the kind of every import it is asked to resolve, leaving the resolving to the
+    /// resolver.
+    struct Kinds(Arc<Mutex<Vec<(String, ResolveKind)>>>);
 
-impl PluginManager {
-    fn load(&self, _file_path: &str, content: &str) -> Option<String> {
-        // Plugin logic to transform file content
-        Some(content.to_string())
+    impl Plugin for Kinds {
+        fn name(&self) -> &str {
+            "kinds"
+        }
+        fn resolve_import(&self, _ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+            self.0.lock().unwrap().push((args.path.to_string(), args.kind));
+            Ok(None)
+        }
     }
-}
 
-struct SourceMapGenerator;
+    /// Records the name of every file it transforms, leaving the code as it is.
+    #[derive(Clone, Default)]
+    struct Transforms(Arc<Mutex<Vec<String>>>);
 
-impl SourceMapGenerator {
-    fn new() -> Self {
-        Self
+    impl Transforms {
+        fn take(&self) -> Vec<String> {
+            let mut names = std::mem::take(&mut *self.0.lock().unwrap());
+            names.sort();
+            names
+        }
+    }
+
+    impl Plugin for Transforms {
+        fn name(&self) -> &str {
+            "transforms"
+        }
+        fn on_transform(&self, file_path: &str, _content: &str) -> Option<String> {
+            let name = Path::new(file_path).file_name().unwrap().to_string_lossy().to_string();
+            self.0.lock().unwrap().push(name);
+            None
+        }
     }
 
-    fn add_mapping(&self, _file_path: &str, _content: &str) {
-        // Source map generation logic
+    /// A development config, so outputs stay readable, that bundles `entries`, relative to
+    /// `dir`, into `dir/dist`, keeping everything else the build reads and writes in `dir` too.
+    fn config_in(dir: &Path, entries: &[(&str, &str)]) -> Config {
+        let path = |relative: &str| dir.join(relative).to_string_lossy().to_string();
+        let mut config = Config {
+            mode: Mode::Development,
+            entries: entries.iter().map(|(name, file)| (name.to_string(), path(file))).collect(),
+            output_file: Some(path("dist/[name].js")),
+            ..Default::default()
+        };
+        config.stats.file = path("dist/stats.json");
+        config.cache.dir = dir.join(".cache");
+        config.env.dir = dir.to_path_buf();
+        config.resolve.allowed_dirs = vec![dir.to_path_buf()];
+        config
     }
 
-    fn generate(&self) -> String {
-        // Logic to finalize and generate the source map
-        json!({
-            "version": 3,
-            "file": "out.js",
-            "sources": ["source.js"],
-            "names": [],
-            "mappings": "AAAA"
-        })
-        .to_string()
+    #[test]
+    fn resolves_each_import_with_its_kind() {
+        let root = TempDir::new("import-kinds");
+        let files = [
+            ("a.js", "import b from './b.js';\nconst c = require(\"./c.js\");\nimport('./d.js');\n"),
+            ("b.js", ""),
+            ("c.js", ""),
+            ("d.js", ""),
+            ("a.css", "@import './b.css';\nbody { background: url(./e.png) url(data:image/png;base64,AA==); }\n"),
+            ("b.css", ""),
+            ("e.png", ""),
+        ];
+        root.write(&files);
+
+        // `b` is never used, and tree shaking would drop it.
+        let config = Config { tree_shaking: Some(false), ..Default::default() };
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Kinds(Arc::clone(&seen))));
+        let loaders = LoaderRegistry::new(&config, plugins.loaders());
+        let resolver = Resolver::new(&ResolveConfig { allowed_dirs: vec![root.to_path_buf()], ..Default::default() });
+        let process = |file: &str| {
+            let file_path = root.join(file).to_string_lossy().to_string();
+            BundleTask::new(file_path)
+                .process(&config, &BTreeMap::new(), &plugins, &loaders, &resolver, &Cache::disabled(), "")
+                .map_err(|errors| errors[0].message.clone())
+                .unwrap()
+        };
+
+        let module = process("a.js");
+        assert_eq!(module.imports.len(), 3);
+        let id = |file: &str| root.join(file).to_string_lossy().to_string();
+        let rewritten = format!("\nconst c = {};\n{};\n", registry::require_call(&id("c.js")), registry::import_call(&id("d.js")));
+        assert_eq!(module.content, rewritten);
+        let module = process("a.css");
+        assert_eq!(module.imports.len(), 2);
+        assert!(module.content.starts_with("\nbody"));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ("./b.js".to_string(), ResolveKind::Import),
+                ("./c.js".to_string(), ResolveKind::Require),
+                ("./d.js".to_string(), ResolveKind::DynamicImport),
+                ("./b.css".to_string(), ResolveKind::CssUrl),
+                ("./e.png".to_string(), ResolveKind::CssUrl),
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_stale_stats_when_a_build_fails() {
+        let dir = TempDir::new("stale-stats");
+        dir.write(&[("src/a.js", "export const a = 1;\n")]);
+        let mut config = config_in(&dir, &[("main", "src/a.js")]);
+        config.stats.enabled = true;
+        let bundler = Bundler::new(config, PluginManager::new());
+        let result = bundler.bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+        assert_eq!(result.stats_file, Some(dir.join("dist/stats.json")));
+
+        dir.write(&[("src/a.js", "import b from './missing.js';\n")]);
+        let result = bundler.bundle();
+        assert!(!result.is_success());
+        assert_eq!(result.stats_file, None);
+        assert!(!dir.join("dist/stats.json").exists());
+    }
+
+    #[test]
+    fn bundles_required_modules_and_css_urls_through_the_registry() {
+        let dir = TempDir::new("registry");
+        dir.write(&[
+            ("src/a.js", "const { b } = require('./b.js');\nimport('./c.js').then((c) => c.default(b));\n"),
+            ("src/b.js", "export const b = 1;\n"),
+            ("src/c.js", "export default function log(value) {\n    console.log(value);\n}\n"),
+            ("src/style.css", "body { background: url(./logo.svg); }\n"),
+            ("src/logo.svg", "<svg/>"),
+        ]);
+        let mut config = config_in(&dir, &[("main", "src/a.js"), ("style", "src/style.css")]);
+        config.loaders.insert("svg".to_string(), "file".to_string());
+        let result = Bundler::new(config, PluginManager::new()).bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+
+        // Module ids are absolute here, and the inline source maps are not what this is about.
+        let read = |file: &str| {
+            let code = fs::read_to_string(dir.join(file)).unwrap().replace(&*dir.to_string_lossy(), "");
+            code[..code.find("\n//# sourceMappingURL").unwrap()].to_string()
+        };
+        let main = read("dist/main.js");
+        assert_eq!(
+            main.strip_prefix(registry::RUNTIME).unwrap(),
+            "// /src/b.js\n\
+             __hyperpack_define(\"/src/b.js\", function (module, exports) {\nconst b = 1;\nexports[\"b\"] = b;\n});\n\
+             // /src/c.js\n\
+             __hyperpack_define(\"/src/c.js\", function (module, exports) {\n\
+             function log(value) {\n    console.log(value);\n}\nexports[\"default\"] = log;\n});\n\
+             // /src/a.js\n\
+             const { b } = __hyperpack_require(\"/src/b.js\");\n\
+             __hyperpack_import(\"/src/c.js\").then((c) => c.default(b));\n"
+        );
+        assert_eq!(read("dist/style.js"), "// /src/style.css\nbody { background: url(\"./logo-d4dc5666.svg\"); }\n");
+        assert_eq!(result.assets, [dir.join("dist/logo-d4dc5666.svg")]);
+    }
+
+    #[test]
+    fn applies_defines_before_scanning_imports_and_drops_unused_ones() {
+        let dir = TempDir::new("defines-first");
+        dir.write(&[
+            (
+                "src/a.js",
+                "if (import.meta.env.PROD) {\n  require('./prod.js');\n}\n\
+                 import { used } from './used.js';\nimport unused from './unused.js';\nconsole.log(used);\n",
+            ),
+            ("src/used.js", "export const used = 1;\n"),
+            ("src/unused.js", "export default 2;\n"),
+        ]);
+        let mut config = config_in(&dir, &[("main", "src/a.js")]);
+        config.tree_shaking = Some(true);
+        let bundler = Bundler::new(config, PluginManager::new());
+        let result = bundler.bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+        let main = fs::read_to_string(dir.join("dist/main.js")).unwrap();
+        assert!(main.contains("used.js") && !main.contains("unused.js") && !main.contains("prod.js"));
+        let codes: Vec<_> = result.warnings.iter().map(|warning| warning.code.as_str()).collect();
+        assert_eq!(codes, ["bundle/unused-import"]);
+        assert_eq!(result.warnings[0].span.map(|span| span.line), Some(5));
+
+        // The define step drops the dead branch, moving the import up, but spans still point
+        // at the line it is on in the file.
+        dir.write(&[(
+            "src/a.js",
+            "if (import.meta.env.PROD) {\n  prod();\n}\nimport { missing } from './missing.js';\nmissing();\n",
+        )]);
+        let result = bundler.bundle();
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].span.map(|span| (span.line, span.column)), Some((4, 26)));
+    }
+
+    #[test]
+    fn bundles_each_named_entry_from_one_module_graph() {
+        let dir = TempDir::new("named-entries");
+        dir.write(&[
+            ("src/admin.js", "import { shared } from './shared.js';\nconsole.log('admin', shared);\n"),
+            ("src/public.js", "import { shared } from './shared.js';\nconsole.log('public', shared);\n"),
+            ("src/shared.js", "export const shared = 1;\n"),
+        ]);
+        let config = config_in(&dir, &[("admin", "src/admin.js"), ("public", "src/public.js")]);
+        let transforms = Transforms::default();
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(transforms.clone()));
+        let result = Bundler::new(config, plugins).bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+
+        let outputs: Vec<_> = result.outputs.iter().map(|output| (output.entry.as_str(), output.path.clone())).collect();
+        assert_eq!(outputs, [("admin", dir.join("dist/admin.js")), ("public", dir.join("dist/public.js"))]);
+        for (entry, other) in [("admin", "public"), ("public", "admin")] {
+            let code = fs::read_to_string(dir.join(format!("dist/{}.js", entry))).unwrap();
+            assert!(code.contains("const shared = 1;") && code.contains(&format!("console.log('{}'", entry)));
+            assert!(!code.contains(&format!("console.log('{}'", other)));
+        }
+        // The shared module is processed once, for both outputs.
+        assert_eq!(transforms.take(), ["admin.js", "public.js", "shared.js"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn the_library_build_reports_failures_as_values() {
+        let dir = TempDir::new("library-build");
+        dir.write(&[("src/a.js", "import { b } from './b.js';\nimport { c } from './c.js';\nconsole.log(b, c);\n")]);
+        let result = crate::build(config_in(&dir, &[("main", "src/a.js")]));
+        // Every failure is collected, and nothing is written.
+        let codes: Vec<_> = result.errors.iter().map(|error| error.code.as_str()).collect();
+        assert_eq!(codes, ["resolve/not-found", "resolve/not-found"]);
+        assert!(result.outputs.is_empty() && !dir.join("dist").exists());
+
+        dir.write(&[("src/b.js", "export const b = 1;\n"), ("src/c.js", "export const c = 2;\n")]);
+        let mut config = config_in(&dir, &[("main", "src/a.js")]);
+        config.plugins.push(PluginConfig::Wasm { path: dir.join("missing.wasm"), name: None });
+        let result = crate::build(config);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.outputs.is_empty());
+
+        let result = crate::build(config_in(&dir, &[("main", "src/a.js")]));
+        assert!(result.is_success(), "{:?}", result.errors);
+        assert_eq!(result.outputs[0].path, dir.join("dist/main.js"));
+        assert_eq!(result.modules.len(), 3);
+    }
+
+    #[test]
+    fn a_second_build_reuses_cached_modules() {
+        let dir = TempDir::new("cached-build");
+        dir.write(&[
+            ("src/a.js", "import { b } from './b.js';\nconsole.log(b);\n"),
+            ("src/b.js", "export const b = 1;\n"),
+        ]);
+        let transforms = Transforms::default();
+        // A new bundler each time, as a new `hyperpack build` would start with.
+        let build = || {
+            let mut plugins = PluginManager::new();
+            plugins.register(Box::new(transforms.clone()));
+            let result = Bundler::new(config_in(&dir, &[("main", "src/a.js")]), plugins).bundle();
+            assert!(result.is_success(), "{:?}", result.errors);
+            fs::read_to_string(dir.join("dist/main.js")).unwrap()
+        };
+
+        let first = build();
+        assert_eq!(transforms.take(), ["a.js", "b.js"]);
+        assert_eq!(build(), first);
+        assert_eq!(transforms.take(), Vec::<String>::new());
+
+        dir.write(&[("src/a.js", "import { b } from './b.js';\nconsole.log(b + 1);\n")]);
+        assert!(build().contains("console.log(b + 1);"));
+        assert_eq!(transforms.take(), ["a.js"]);
+    }
+
+    #[test]
+    fn plugin_config_and_version_changes_invalidate_the_cache() {
+        /// Replaces `__GREETING__` with the contents of a config file it watches.
+        struct Greeting {
+            config: PathBuf,
+            version: &'static str,
+            transforms: Transforms,
+        }
+
+        impl Plugin for Greeting {
+            fn name(&self) -> &str {
+                "greeting"
+            }
+            fn cache_key(&self) -> Option<String> {
+                Some(self.version.to_string())
+            }
+            fn transform_module(&self, ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+                self.transforms.on_transform(file_path, code);
+                ctx.add_watch_file(&self.config);
+                let greeting = fs::read_to_string(&self.config).unwrap();
+                Ok(Some(TransformResult { code: code.replace("__GREETING__", greeting.trim()), map: None }))
+            }
+        }
+
+        let dir = TempDir::new("plugin-cache-key");
+        dir.write(&[("src/a.js", "console.log('__GREETING__');\n"), ("greeting.txt", "hello")]);
+        let transforms = Transforms::default();
+        let build = |version| {
+            let mut plugins = PluginManager::new();
+            plugins.register(Box::new(Greeting { config: dir.join("greeting.txt"), version, transforms: transforms.clone() }));
+            let result = Bundler::new(config_in(&dir, &[("main", "src/a.js")]), plugins).bundle();
+            assert!(result.is_success(), "{:?}", result.errors);
+            fs::read_to_string(dir.join("dist/main.js")).unwrap()
+        };
+
+        assert!(build("v1").contains("console.log('hello');"));
+        assert_eq!(transforms.take(), ["a.js"]);
+        build("v1");
+        assert_eq!(transforms.take(), Vec::<String>::new());
+
+        dir.write(&[("greeting.txt", "bye")]);
+        assert!(build("v1").contains("console.log('bye');"));
+        assert_eq!(transforms.take(), ["a.js"]);
+        build("v2");
+        assert_eq!(transforms.take(), ["a.js"]);
+    }
+
+    #[test]
+    fn a_rebuild_processes_only_the_changed_module_and_its_importers() {
+        let dir = TempDir::new("rebuild");
+        dir.write(&[
+            ("src/a.js", "import { b } from './b.js';\nconsole.log(b);\n"),
+            ("src/b.js", "import { c } from './c.js';\nexport const b = c;\n"),
+            ("src/c.js", "export const c = 1;\n"),
+            ("src/other.js", "import { c } from './c.js';\nconsole.log(c);\n"),
+        ]);
+        let mut config = config_in(&dir, &[("main", "src/a.js"), ("other", "src/other.js")]);
+        // Without the cache, every module processed again is transformed again.
+        config.cache.enabled = false;
+        let transforms = Transforms::default();
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(transforms.clone()));
+        let bundler = Bundler::new(config, plugins);
+        assert!(bundler.bundle().is_success());
+        assert_eq!(transforms.take(), ["a.js", "b.js", "c.js", "other.js"]);
+
+        dir.write(&[("src/b.js", "import { c } from './c.js';\nexport const b = c + 1;\n")]);
+        let result = bundler.rebuild(&[dir.join("src/b.js")]);
+        assert!(result.is_success(), "{:?}", result.errors);
+        assert_eq!(transforms.take(), ["a.js", "b.js"]);
+        let outputs: Vec<_> = result.outputs.iter().map(|output| output.entry.as_str()).collect();
+        assert_eq!(outputs, ["main"]);
+        assert!(fs::read_to_string(dir.join("dist/main.js")).unwrap().contains("const b = c + 1;"));
+
+        dir.write(&[("src/c.js", "export const c = 2;\n")]);
+        let result = bundler.rebuild(&[dir.join("src/c.js")]);
+        assert_eq!(transforms.take(), ["a.js", "b.js", "c.js", "other.js"]);
+        assert_eq!(result.outputs.len(), 2);
+    }
+
+    #[test]
+    fn bundles_virtual_modules_a_plugin_loads() {
+        /// Generates `virtual:routes` and the `\0build-info` it imports.
+        struct Routes;
+
+        impl Plugin for Routes {
+            fn name(&self) -> &str {
+                "routes"
+            }
+            fn resolve_import(&self, _ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+                let path = match args.path {
+                    "virtual:routes" => "virtual:routes",
+                    "build-info" => "\0build-info",
+                    _ => return Ok(None),
+                };
+                Ok(Some(ResolveResult { path: path.to_string(), namespace: None }))
+            }
+            fn on_load(&self, file_path: &str, _content: &str) -> Option<String> {
+                match file_path {
+                    "virtual:routes" => Some("import { built } from 'build-info';\nexport const routes = ['/', '/about'];\n".to_string()),
+                    "\0build-info" => Some("export const built = 'today';\n".to_string()),
+                    _ => None,
+                }
+            }
+        }
+
+        let dir = TempDir::new("virtual-modules");
+        dir.write(&[("src/a.js", "import { routes } from 'virtual:routes';\nconsole.log(routes);\n")]);
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Routes));
+        let result = Bundler::new(config_in(&dir, &[("main", "src/a.js")]), plugins).bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+
+        assert_eq!(result.outputs[0].modules[..2], ["\0build-info", "virtual:routes"]);
+        let main = fs::read_to_string(dir.join("dist/main.js")).unwrap();
+        assert!(main.contains("// \\0build-info\nexport const built = 'today';"), "{}", main);
+        assert!(main.contains("// virtual:routes\n\nexport const routes = ['/', '/about'];"), "{}", main);
+        assert!(!dir.join("virtual:routes").exists() && !Path::new("virtual:routes").exists());
+    }
+
+    #[test]
+    fn bail_stops_the_build_at_the_first_plugin_error() {
+        /// Fails every module that calls `fail()`, at the call.
+        struct Strict;
+
+        impl Plugin for Strict {
+            fn name(&self) -> &str {
+                "strict"
+            }
+            fn transform_module(&self, _ctx: &mut PluginContext, _file_path: &str, code: &str) -> HookResult<TransformResult> {
+                match code.find("fail()") {
+                    Some(index) => Err(Diagnostic::error("strict/fail", "Asked to fail")
+                        .with_span(Span::from_offsets(code, index, index + 4))),
+                    None => Ok(None),
+                }
+            }
+        }
+
+        let dir = TempDir::new("bail");
+        dir.write(&[
+            ("src/a.js", "import { b } from './b.js';\nimport { c } from './c.js';\nconsole.log(b, c);\n"),
+            ("src/b.js", "fail();\nexport const b = 1;\n"),
+            ("src/c.js", "fail();\nexport const c = 2;\n"),
+        ]);
+        let build = |bail| {
+            let mut config = config_in(&dir, &[("main", "src/a.js")]);
+            config.bail = bail;
+            let mut plugins = PluginManager::new();
+            plugins.register(Box::new(Strict));
+            Bundler::new(config, plugins).bundle()
+        };
+
+        let result = build(false);
+        assert_eq!(result.errors.len(), 2);
+        let result = build(true);
+        assert_eq!(result.errors.len(), 1);
+        let error = &result.errors[0];
+        assert_eq!((error.code.as_str(), error.plugin.as_deref()), ("strict/fail", Some("strict")));
+        assert_eq!(error.span.map(|span| (span.line, span.column)), Some((1, 1)));
+        assert!(result.outputs.is_empty() && !dir.join("dist/main.js").exists());
+    }
+
+    #[test]
+    fn bundles_files_through_their_loaders() {
+        let dir = TempDir::new("loaders");
+        dir.write(&[
+            (
+                "src/a.js",
+                "import terms from './terms.txt';\nimport data from './data.json';\n\
+                 import config from './config.toml';\nimport logo from './logo.svg';\n\
+                 console.log(terms, data.name, config.port, logo);\n",
+            ),
+            ("src/terms.txt", "Be nice.\n"),
+            ("src/data.json", r#"{"name": "hyperpack"}"#),
+            ("src/config.toml", "port = 8080\n"),
+            ("src/logo.svg", "<svg/>"),
+        ]);
+        let result = Bundler::new(config_in(&dir, &[("main", "src/a.js")]), PluginManager::new()).bundle();
+        assert!(result.is_success(), "{:?}", result.errors);
+        let main = fs::read_to_string(dir.join("dist/main.js")).unwrap().replace(&*dir.to_string_lossy(), "");
+        let main = &main[..main.find("\n//# sourceMappingURL").unwrap()];
+        assert_eq!(
+            main,
+            "// /src/terms.txt\nexport default \"Be nice.\\n\";\n\
+             // /src/data.json\nexport default {\"name\":\"hyperpack\"};\n\
+             // /src/config.toml\nexport default {\"port\":8080};\n\
+             // /src/logo.svg\nexport default \"./logo-d4dc5666.svg\";\n\
+             // /src/a.js\n\n\n\n\nconsole.log(terms, data.name, config.port, logo);\n"
+        );
+        assert_eq!(result.assets, [dir.join("dist/logo-d4dc5666.svg")]);
+        assert_eq!(fs::read_to_string(dir.join("dist/logo-d4dc5666.svg")).unwrap(), "<svg/>");
+    }
+}
@@ -1,50 +1,129 @@
-use clap::{Arg, Command};
-use minify::js::Minifier;
-use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader, Write};
+use std::fs;
 use std::path::{Path, PathBuf};
-use log::{info, warn, error, debug, LevelFilter};
-use simple_logger::SimpleLogger;
+use log::{info, error, debug};
 use rayon::prelude::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Instant;
-
-struct MinificationContext {
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, Globals, Mark, SourceMap, GLOBALS};
+use swc_ecmascript::ast::EsVersion;
+use swc_ecmascript::codegen::text_writer::JsWriter;
+use swc_ecmascript::codegen::{self, Emitter};
+use swc_ecmascript::minifier::option::{ExtraOptions, MinifyOptions};
+use swc_ecmascript::minifier::optimize;
+use swc_ecmascript::parser::{parse_file_as_program, Syntax};
+use swc_ecmascript::transforms::fixer;
+use swc_ecmascript::transforms::resolver;
+use swc_ecmascript::visit::FoldWith;
+
+use crate::sourcemap;
+
+pub struct MinificationContext {
     input_path: PathBuf,
     output_path: PathBuf,
     use_parallel_processing: bool,
     keep_comments: bool,
-    cache: HashMap<String, String>,
+    cache: Mutex<HashMap<String, String>>,
     dry_run: bool,
 }
 
 impl MinificationContext {
-    fn new(input_path: PathBuf, output_path: PathBuf, use_parallel_processing: bool, keep_comments: bool, dry_run: bool) -> Self {
+    pub fn new(input_path: PathBuf, output_path: PathBuf, use_parallel_processing: bool, keep_comments: bool, dry_run: bool) -> Self {
         Self {
             input_path,
             output_path,
             use_parallel_processing,
             keep_comments,
-            cache: HashMap::new(),
+            cache: Mutex::new(HashMap::new()),
             dry_run,
         }
     }
 }
 
-fn minify_code(code: &str, keep_comments: bool) -> Result<String, String> {
-    let mut minifier = Minifier::new();
-    if keep_comments {
-        minifier.keep_comments();
-    }
-    minifier.minify(code).map_err(|err| format!("Minification failed: {}", err))
+/// Compresses and mangles JavaScript source, optionally keeping its comments.
+pub fn minify_code(code: &str, keep_comments: bool) -> Result<String, String> {
+    minify(code, keep_comments, None).map(|(code, _)| code)
 }
 
-fn process_file(ctx: &mut MinificationContext, input_path: &Path, output_path: &Path) -> Result<(), String> {
-    if ctx.cache.contains_key(input_path.to_str().unwrap()) {
+/// Like [`minify_code`], also returning a source map that extends `map`, which maps `code` to
+/// its original sources.
+pub fn minify_code_with_map(code: &str, keep_comments: bool, map: &str) -> Result<(String, String), String> {
+    let (code, map) = minify(code, keep_comments, Some(map))?;
+    Ok((code, map.unwrap_or_default()))
+}
+
+fn minify(code: &str, keep_comments: bool, map: Option<&str>) -> Result<(String, Option<String>), String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Anon), code.to_string());
+    let comments = SingleThreadedComments::default();
+
+    let program = parse_file_as_program(
+        &fm,
+        Syntax::Es(Default::default()),
+        EsVersion::latest(),
+        Some(&comments),
+        &mut vec![],
+    )
+    .map_err(|err| format!("Minification failed: {:?}", err.kind()))?;
+
+    GLOBALS.set(&Globals::new(), || {
+        let unresolved_mark = Mark::new();
+        let top_level_mark = Mark::new();
+
+        let program = program.fold_with(&mut resolver(unresolved_mark, top_level_mark, false));
+        let program = optimize(
+            program,
+            cm.clone(),
+            Some(&comments),
+            None,
+            &MinifyOptions {
+                compress: Some(Default::default()),
+                mangle: Some(Default::default()),
+                ..Default::default()
+            },
+            &ExtraOptions {
+                unresolved_mark,
+                top_level_mark,
+                mangle_name_cache: None,
+            },
+        );
+        let program = program.fold_with(&mut fixer(Some(&comments)));
+
+        let mut buf = Vec::new();
+        let mut mappings = Vec::new();
+        {
+            let mut emitter = Emitter {
+                cfg: codegen::Config::default().with_minify(true),
+                cm: cm.clone(),
+                comments: if keep_comments { Some(&comments) } else { None },
+                wr: JsWriter::new(cm.clone(), "\n", &mut buf, map.is_some().then_some(&mut mappings)),
+            };
+            emitter
+                .emit_program(&program)
+                .map_err(|err| format!("Minification failed: {}", err))?;
+        }
+        let code = String::from_utf8(buf).map_err(|err| format!("Minification failed: {}", err))?;
+        let map = match map {
+            Some(map) => {
+                let input_map = sourcemap::parse(map)?;
+                Some(sourcemap::to_json(&cm.build_source_map_from(&mappings, Some(&input_map))))
+            }
+            None => None,
+        };
+        Ok((code, map))
+    })
+}
+
+fn process_file(ctx: &MinificationContext, input_path: &Path, output_path: &Path) -> Result<(), String> {
+    let cache_key = input_path.to_string_lossy().to_string();
+    let cached = ctx.cache.lock().unwrap().get(&cache_key).cloned();
+    if let Some(minified_code) = cached {
         debug!("Cache hit for file: {:?}", input_path);
         if !ctx.dry_run {
-            fs::write(output_path, ctx.cache.get(input_path.to_str().unwrap()).unwrap())
+            fs::write(output_path, minified_code)
                 .map_err(|err| format!("Failed to write output file: {}", err))?;
         }
         return Ok(());
@@ -52,18 +131,18 @@ fn process_file(ctx: &mut MinificationContext, input_path: &Path, output_path: &
 
     let code = fs::read_to_string(input_path)
         .map_err(|err| format!("Failed to read input file: {}", err))?;
-    
+
     let minified_code = minify_code(&code, ctx.keep_comments)?;
     if !ctx.dry_run {
         fs::write(output_path, &minified_code)
             .map_err(|err| format!("Failed to write output file: {}", err))?;
     }
 
-    ctx.cache.insert(input_path.to_str().unwrap().to_string(), minified_code);
+    ctx.cache.lock().unwrap().insert(cache_key, minified_code);
     Ok(())
 }
 
-fn process_directory(ctx: &mut MinificationContext, input_dir: &Path, output_dir: &Path) -> Result<(), String> {
+fn process_directory(ctx: &MinificationContext, input_dir: &Path, output_dir: &Path) -> Result<(), String> {
     if !output_dir.exists() {
         fs::create_dir_all(output_dir).map_err(|err| format!("Failed to create output directory: {}", err))?;
     }
@@ -77,6 +156,7 @@ fn process_directory(ctx: &mut MinificationContext, input_dir: &Path, output_dir
     let progress_bar = ProgressBar::new(files.len() as u64);
     progress_bar.set_style(ProgressStyle::default_bar()
         .template("{msg} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
+        .map_err(|err| format!("Invalid progress bar template: {}", err))?
         .progress_chars("#>-"));
 
     if ctx.use_parallel_processing {
@@ -90,7 +170,7 @@ fn process_directory(ctx: &mut MinificationContext, input_dir: &Path, output_dir
                 },
                 Err(err) => {
                     error!("Error processing file {:?}: {}", input_path, err);
-                    Err(())
+                    Err(format!("Error processing file {:?}: {}", input_path, err))
                 }
             }
         })?;
@@ -109,88 +189,21 @@ fn process_directory(ctx: &mut MinificationContext, input_dir: &Path, output_dir
     Ok(())
 }
 
-fn main() {
-    SimpleLogger::new().with_level(LevelFilter::Info).init().expect("Failed to initialize logger");
-
-    let matches = Command::new("minify")
-        .arg(
-            Arg::new("input")
-                .about("Input file or directory to minify")
-                .required(true)
-                .index(1),
-        )
-        .arg(
-            Arg::new("output")
-                .about("Output file or directory for minified code")
-                .required(true)
-                .index(2),
-        )
-        .arg(
-            Arg::new("parallel")
-                .short('p')
-                .long("parallel")
-                .about("Use parallel processing"),
-        )
-        .arg(
-            Arg::new("keep-comments")
-                .short('k')
-                .long("keep-comments")
-                .about("Keep comments in the minified output"),
-        )
-        .arg(
-            Arg::new("dry-run")
-                .short('d')
-                .long("dry-run")
-                .about("Perform a dry run without writing output files"),
-        )
-        .arg(
-            Arg::new("log-level")
-                .short('l')
-                .long("log-level")
-                .about("Set the log level (off, error, warn, info, debug, trace)")
-                .takes_value(true),
-        )
-        .get_matches();
-
-    let input_path = PathBuf::from(matches.value_of("input").unwrap());
-    let output_path = PathBuf::from(matches.value_of("output").unwrap());
-    let use_parallel_processing = matches.is_present("parallel");
-    let keep_comments = matches.is_present("keep-comments");
-    let dry_run = matches.is_present("dry-run");
-
-    if let Some(log_level) = matches.value_of("log-level") {
-        match log_level.to_lowercase().as_str() {
-            "off" => log::set_max_level(LevelFilter::Off),
-            "error" => log::set_max_level(LevelFilter::Error),
-            "warn" => log::set_max_level(LevelFilter::Warn),
-            "info" => log::set_max_level(LevelFilter::Info),
-            "debug" => log::set_max_level(LevelFilter::Debug),
-            "trace" => log::set_max_level(LevelFilter::Trace),
-            _ => warn!("Invalid log level specified, using default: info"),
-        }
-    }
-
+/// Minifies `ctx.input_path` into `ctx.output_path`, file to file or directory to directory.
+pub fn run(ctx: &MinificationContext) -> Result<(), String> {
     info!("Starting minification process");
 
-    let mut ctx = MinificationContext::new(input_path.clone(), output_path.clone(), use_parallel_processing, keep_comments, dry_run);
-
     let start_time = Instant::now();
 
-    if input_path.is_file() {
-        if let Err(err) = process_file(&mut ctx, &input_path, &output_path) {
-            error!("{}", err);
-            std::process::exit(1);
-        }
-    } else if input_path.is_dir() {
-        if let Err(err) = process_directory(&mut ctx, &input_path, &output_path) {
-            error!("{}", err);
-            std::process::exit(1);
-        }
+    if ctx.input_path.is_file() {
+        process_file(ctx, &ctx.input_path, &ctx.output_path)?;
+    } else if ctx.input_path.is_dir() {
+        process_directory(ctx, &ctx.input_path, &ctx.output_path)?;
     } else {
-        error!("Invalid input path specified");
-        std::process::exit(1);
+        return Err("Invalid input path specified".to_string());
     }
 
     let elapsed_time = start_time.elapsed();
     info!("Minification completed successfully in {:.2?}", elapsed_time);
-}
\ No newline at end of file
+    Ok(())
+}
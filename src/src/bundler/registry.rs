@@ -0,0 +1,161 @@
+//! The module registry of a chunk. Static imports are concatenated ahead of their importer,
+//! but a module loaded with `require()` or `import()` is wrapped in a factory that runs on first
+//! use, and those calls are rewritten to look it up by id.
+
+use swc_common::sync::Lrc;
+use swc_common::{BytePos, FileName, SourceMap, Spanned};
+use swc_ecmascript::ast::{
+    ClassExpr, Decl, DefaultDecl, EsVersion, FnExpr, ModuleDecl, ModuleExportName, ModuleItem, ExportSpecifier,
+};
+use swc_ecmascript::parser::parse_file_as_module;
+use swc_ecmascript::utils::find_pat_ids;
+
+use crate::define::syntax_for;
+use crate::diagnostic::{Diagnostic, Span};
+
+/// Defines the registry, ahead of the first module of a chunk that uses it.
+pub const RUNTIME: &str = "\
+var __hyperpack_modules = {};
+function __hyperpack_define(id, factory) { __hyperpack_modules[id] = { factory: factory, module: null }; }
+function __hyperpack_require(id) {
+  var record = __hyperpack_modules[id];
+  if (!record.module) { record.module = { exports: {} }; record.factory(record.module, record.module.exports); }
+  return record.module.exports;
+}
+function __hyperpack_import(id) { return Promise.resolve().then(function () { return __hyperpack_require(id); }); }
+";
+
+/// What `require()` of the module `id` becomes.
+pub fn require_call(id: &str) -> String {
+    format!("__hyperpack_require({})", quote(id))
+}
+
+/// What `import()` of the module `id` becomes.
+pub fn import_call(id: &str) -> String {
+    format!("__hyperpack_import({})", quote(id))
+}
+
+/// The line that opens the factory of the module `id`.
+pub fn factory_start(id: &str) -> String {
+    format!("__hyperpack_define({}, function (module, exports) {{\n", quote(id))
+}
+
+/// The line that closes a factory.
+pub const FACTORY_END: &str = "});\n";
+
+/// Rewrites the ES module syntax of `code` to CommonJS, for the body of a factory. `export`
+/// keywords are removed in place so every line stays where it was; the assignments of the
+/// exports are returned separately, to run after the body.
+pub fn to_commonjs(file_path: &str, code: &str) -> Result<(String, String), Diagnostic> {
+    if !code.contains("export") && !code.contains("import") {
+        return Ok((code.to_string(), String::new()));
+    }
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Real(file_path.into())), code.to_string());
+    let span_of = |lo: BytePos, hi: BytePos| {
+        let (lo, hi) = (cm.lookup_char_pos(lo), cm.lookup_char_pos(hi));
+        Span::new(lo.line, lo.col.0 + 1, hi.line, (hi.col.0 + 1).max(lo.col.0 + 2))
+    };
+    let module = parse_file_as_module(&fm, syntax_for(file_path), EsVersion::latest(), None, &mut vec![])
+        .map_err(|err| {
+            Diagnostic::error("bundle/registry", err.kind().msg().to_string())
+                .with_file(file_path)
+                .with_span(span_of(err.span().lo, err.span().hi))
+                .with_note("modules loaded with require() or import() are parsed as JavaScript")
+        })?;
+    let offset = |pos: BytePos| (pos.0 - fm.start_pos.0) as usize;
+    let unsupported = |what: &str, lo: BytePos, hi: BytePos| {
+        Diagnostic::error("bundle/registry", format!("{} are not supported in modules loaded with require() or import()", what))
+            .with_file(file_path)
+            .with_span(span_of(lo, hi))
+    };
+
+    let mut edits = vec![];
+    let mut exports = vec![];
+    for item in &module.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        match decl {
+            ModuleDecl::ExportDecl(export) => {
+                edits.push((offset(export.span.lo)..offset(export.decl.span().lo), ""));
+                let names = match &export.decl {
+                    Decl::Class(class) => vec![class.ident.sym.clone()],
+                    Decl::Fn(function) => vec![function.ident.sym.clone()],
+                    Decl::Var(var) => var.decls.iter().flat_map(|decl| find_pat_ids(&decl.name)).collect(),
+                    _ => vec![],
+                };
+                exports.extend(names.into_iter().map(|name| (name.to_string(), name.to_string())));
+            }
+            ModuleDecl::ExportDefaultExpr(export) => {
+                edits.push((offset(export.span.lo)..offset(export.expr.span().lo), "exports.default = "));
+            }
+            ModuleDecl::ExportDefaultDecl(export) => {
+                let prefix = offset(export.span.lo)..offset(export.decl.span().lo);
+                match &export.decl {
+                    DefaultDecl::Fn(FnExpr { ident: Some(ident), .. })
+                    | DefaultDecl::Class(ClassExpr { ident: Some(ident), .. }) => {
+                        edits.push((prefix, ""));
+                        exports.push(("default".to_string(), ident.sym.to_string()));
+                    }
+                    DefaultDecl::Fn(_) | DefaultDecl::Class(_) => edits.push((prefix, "exports.default = ")),
+                    DefaultDecl::TsInterfaceDecl(_) => {}
+                }
+            }
+            ModuleDecl::ExportNamed(export) if export.src.is_none() => {
+                edits.push((offset(export.span.lo)..offset(export.span.hi), ""));
+                for specifier in &export.specifiers {
+                    if let ExportSpecifier::Named(named) = specifier {
+                        let local = export_name(&named.orig);
+                        let exported = named.exported.as_ref().map_or_else(|| local.clone(), export_name);
+                        exports.push((exported, local));
+                    }
+                }
+            }
+            ModuleDecl::ExportNamed(export) => return Err(unsupported("Re-exports", export.span.lo, export.span.hi)),
+            ModuleDecl::ExportAll(export) => return Err(unsupported("Re-exports", export.span.lo, export.span.hi)),
+            ModuleDecl::Import(import) => {
+                return Err(unsupported("`import` declarations", import.span.lo, import.span.hi))
+            }
+            _ => {}
+        }
+    }
+
+    let mut body = code.to_string();
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, replacement) in edits {
+        body.replace_range(range, replacement);
+    }
+    let assignments = exports
+        .iter()
+        .map(|(exported, local)| format!("exports[{}] = {};\n", quote(exported), local))
+        .collect();
+    Ok((body, assignments))
+}
+
+fn export_name(name: &ModuleExportName) -> String {
+    match name {
+        ModuleExportName::Ident(ident) => ident.sym.to_string(),
+        ModuleExportName::Str(str) => str.value.to_string(),
+    }
+}
+
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_exports_in_place() {
+        let code = "export const a = 1, { b } = {};\nexport function f() {}\nconst c = 2;\nexport { c as d };\nexport default class {}\n";
+        let (body, assignments) = to_commonjs("src/a.js", code).unwrap();
+        assert_eq!(body, "const a = 1, { b } = {};\nfunction f() {}\nconst c = 2;\n\nexports.default = class {}\n");
+        assert_eq!(assignments, "exports[\"a\"] = a;\nexports[\"b\"] = b;\nexports[\"f\"] = f;\nexports[\"d\"] = c;\n");
+
+        let err = to_commonjs("src/b.js", "let a;\nexport * from './c.js';\n").unwrap_err();
+        assert_eq!((err.code.as_str(), err.span.map(|span| span.line)), ("bundle/registry", Some(2)));
+    }
+}
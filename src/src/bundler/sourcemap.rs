@@ -0,0 +1,152 @@
+use sourcemap::{SourceMap, SourceMapBuilder};
+
+use crate::diagnostic::Span;
+
+/// Parses a source map from its JSON form.
+pub fn parse(map: &str) -> Result<SourceMap, String> {
+    SourceMap::from_slice(map.as_bytes()).map_err(|err| err.to_string())
+}
+
+/// Serializes a source map to JSON.
+pub fn to_json(map: &SourceMap) -> String {
+    let mut buf = Vec::new();
+    // Writing to a Vec cannot fail.
+    let _ = map.to_writer(&mut buf);
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+/// Chains two steps of a pipeline: `outer` maps the last step's output to its input and `inner`
+/// maps that input to the original source. Without `inner` the input was the original source.
+pub fn compose(outer: &str, inner: Option<&str>) -> Result<String, String> {
+    let outer = parse(outer)?;
+    let Some(inner) = inner else {
+        return Ok(to_json(&outer));
+    };
+    let mut composed = parse(inner)?;
+    composed.adjust_mappings(&outer);
+    Ok(to_json(&composed))
+}
+
+/// Shifts a map down by `lines`, for code that had lines inserted before it.
+pub fn prepend_lines(map: &str, lines: usize) -> Result<String, String> {
+    let mut map: serde_json::Value = serde_json::from_str(map).map_err(|err| err.to_string())?;
+    let Some(mappings) = map.get("mappings").and_then(|mappings| mappings.as_str().map(str::to_string)) else {
+        return Err("missing `mappings`".to_string());
+    };
+    map["mappings"] = format!("{}{}", ";".repeat(lines), mappings).into();
+    Ok(map.to_string())
+}
+
+/// Maps a span of a step's output back to its input, keeping its width. Spans before the first
+/// mapping are returned as they are.
+pub fn original_span(map: &SourceMap, span: Span) -> Span {
+    let (line, column) = (span.line as u32 - 1, span.column as u32 - 1);
+    let Some(token) = map.lookup_token(line, column) else {
+        return span;
+    };
+    // The nearest mapping may start earlier on the line than the span does.
+    let offset = if token.get_dst_line() == line { column - token.get_dst_col() } else { 0 };
+    let (line, column) = (token.get_src_line() as usize + 1, (token.get_src_col() + offset) as usize + 1);
+    if span.end_line == span.line {
+        Span::new(line, column, line, column + span.end_column - span.column)
+    } else {
+        Span::new(line, column, line + span.end_line - span.line, span.end_column)
+    }
+}
+
+/// Builds the source map of a chunk as its modules are concatenated.
+pub struct ChunkMap {
+    builder: SourceMapBuilder,
+    /// The line the next code appended to the chunk starts on.
+    line: u32,
+}
+
+impl ChunkMap {
+    pub fn new(file: &str) -> Self {
+        Self { builder: SourceMapBuilder::new(Some(file)), line: 0 }
+    }
+
+    /// Resolves `sources` relative to `root` instead of the map's own directory.
+    pub fn set_source_root(&mut self, root: &str) {
+        self.builder.set_source_root(Some(root));
+    }
+
+    /// Appends generated lines that map to no source, such as a module's header comment.
+    pub fn add_unmapped(&mut self, lines: u32) {
+        self.line += lines;
+    }
+
+    /// Appends a module's code, ending in a newline. `map` maps it back to its sources; without
+    /// one, each line maps to the same line of `source`.
+    pub fn add_module(&mut self, source: &str, code: &str, map: Option<&str>) -> Result<(), String> {
+        match map {
+            Some(map) => {
+                let map = parse(map)?;
+                for token in map.tokens() {
+                    let src_id = token.get_source().map(|source| self.builder.add_source(source));
+                    let name_id = token.get_name().map(|name| self.builder.add_name(name));
+                    self.builder.add_raw(
+                        self.line + token.get_dst_line(),
+                        token.get_dst_col(),
+                        token.get_src_line(),
+                        token.get_src_col(),
+                        src_id,
+                        name_id,
+                        false,
+                    );
+                }
+                for (index, contents) in map.source_contents().enumerate() {
+                    if let (Some(source), Some(contents)) = (map.get_source(index as u32), contents) {
+                        let src_id = self.builder.add_source(source);
+                        self.builder.set_source_contents(src_id, Some(contents));
+                    }
+                }
+            }
+            None => {
+                let src_id = self.builder.add_source(source);
+                for line in 0..code.lines().count() as u32 {
+                    self.builder.add_raw(self.line + line, 0, line, 0, Some(src_id), None, false);
+                }
+            }
+        }
+        self.line += code.lines().count() as u32;
+        Ok(())
+    }
+
+    pub fn finish(self) -> SourceMap {
+        self.builder.into_sourcemap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_steps_and_offsets_modules() {
+        // `b.js` is `a.js` with one line inserted at the top, `c.js` is `b.js` with another.
+        let shift = |file: &str, source: &str| {
+            let mut builder = SourceMapBuilder::new(Some(file));
+            for line in 0..3 {
+                builder.add(line + 1, 0, line, 0, Some(source), None, false);
+            }
+            to_json(&builder.into_sourcemap())
+        };
+        let b_to_a = shift("b.js", "a.js");
+        let c_to_b = shift("c.js", "b.js");
+        let composed = parse(&compose(&c_to_b, Some(&b_to_a)).unwrap()).unwrap();
+        let token = composed.lookup_token(2, 0).unwrap();
+        assert_eq!((token.get_source(), token.get_src_line()), (Some("a.js"), 0));
+
+        let mut chunk = ChunkMap::new("out.js");
+        chunk.add_unmapped(1);
+        chunk.add_module("x.js", "let x;\nlet y;\n", None).unwrap();
+        chunk.add_unmapped(1);
+        chunk.add_module("a.js", "\nlet a;\n", Some(&b_to_a)).unwrap();
+        let map = chunk.finish();
+        let token = map.lookup_token(2, 0).unwrap();
+        assert_eq!((token.get_source(), token.get_src_line()), (Some("x.js"), 1));
+        let token = map.lookup_token(5, 0).unwrap();
+        assert_eq!((token.get_source(), token.get_src_line()), (Some("a.js"), 0));
+    }
+}
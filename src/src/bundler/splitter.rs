@@ -4,7 +4,6 @@ use std::collections::HashSet;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use glob::glob;
 
 /// Generates a random string of specified length.
 ///
@@ -121,21 +120,17 @@ fn split_code(
     Ok(())
 }
 
-/// Function to load a chunk dynamically (placeholder for actual loading mechanism).
+/// Splits `entry_file` into chunks under `output_dir` and writes the chunk manifest.
 ///
 /// # Arguments
 ///
-/// * `chunk_name` - The name of the chunk file to load.
-fn load_chunk(chunk_name: &str) {
-    println!("Loading chunk: {}", chunk_name);
-}
-
-fn main() -> io::Result<()> {
-    // Define the entry point for the splitting process (e.g., "src/main.js").
-    let entry_file = Path::new("src/main.js");
-    // Define the output directory for split files.
-    let output_dir = Path::new("dist");
-
+/// * `entry_file` - The entry file that starts the splitting process (e.g., "src/main.js").
+/// * `output_dir` - The output directory for split files.
+///
+/// # Returns
+///
+/// * The path of the written manifest file.
+pub fn split(entry_file: &Path, output_dir: &Path) -> io::Result<PathBuf> {
     // Create the output directory if it doesn't exist
     fs::create_dir_all(output_dir)?;
 
@@ -149,8 +144,5 @@ fn main() -> io::Result<()> {
     let manifest_path = output_dir.join("manifest.txt");
     write_manifest(&manifest_path, chunk_metadata)?;
 
-    println!("Code splitting complete. Chunks saved to {:?}", output_dir);
-    println!("Chunk metadata saved to {:?}", manifest_path);
-
-    Ok(())
-}
\ No newline at end of file
+    Ok(manifest_path)
+}
@@ -3,7 +3,6 @@ use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
 use std::thread;
-use clap::{Arg, Command};
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
 use walkdir::WalkDir;
@@ -41,7 +40,7 @@ fn write_chunks_to_files(base_path: &Path, chunks: Vec<String>, ext: &str) -> io
 
     fs::create_dir_all(&output_dir)?;
 
-    for (i, chunk) in chunks.iter().enumerate() {
+    for chunk in chunks.iter() {
         let file_name = format!("{}.{}", generate_random_name(10), ext);
         let file_path = output_dir.join(file_name);
 
@@ -52,9 +51,12 @@ fn write_chunks_to_files(base_path: &Path, chunks: Vec<String>, ext: &str) -> io
     Ok(())
 }
 
-// Function to process files in a directory
-fn process_files(input_dir: &Path, output_dir: &Path, chunk_size: usize) -> io::Result<()> {
+// Function to process files in a directory, returning the files that were chunked
+pub fn process_files(input_dir: &Path, output_dir: &Path, chunk_size: usize) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(output_dir)?;
+
     let (tx, rx) = channel();
+    let mut handles = Vec::new();
 
     for entry in WalkDir::new(input_dir).into_iter().filter_map(Result::ok) {
         if entry.file_type().is_file() {
@@ -62,64 +64,28 @@ fn process_files(input_dir: &Path, output_dir: &Path, chunk_size: usize) -> io::
             let output_dir = output_dir.to_path_buf();
             let tx = tx.clone();
 
-            thread::spawn(move || {
+            handles.push(thread::spawn(move || {
                 if let Some(ext) = file_path.extension().and_then(|e| e.to_str()) {
                     match ext {
                         "js" | "css" | "html" => {
                             if let Ok(content) = read_file(&file_path) {
                                 let chunks = chunk_file_content(&content, chunk_size);
                                 let _ = write_chunks_to_files(&output_dir, chunks, ext);
-                                let _ = tx.send(format!("Processed: {:?}", file_path));
+                                let _ = tx.send(file_path);
                             }
                         },
                         _ => {},
                     }
                 }
-            });
+            }));
         }
     }
 
     drop(tx); // Close the channel
 
-    for received in rx {
-        println!("{}", received);
+    for handle in handles {
+        let _ = handle.join();
     }
 
-    Ok(())
+    Ok(rx.into_iter().collect())
 }
-
-fn main() -> io::Result<()> {
-    let matches = Command::new("chunker")
-        .arg(Arg::new("input")
-            .short('i')
-            .long("input")
-            .value_name("INPUT_DIR")
-            .about("Sets the input directory to use")
-            .takes_value(true)
-            .default_value("input"))
-        .arg(Arg::new("output")
-            .short('o')
-            .long("output")
-            .value_name("OUTPUT_DIR")
-            .about("Sets the output directory to use")
-            .takes_value(true)
-            .default_value("dist/static"))
-        .arg(Arg::new("chunk_size")
-            .short('c')
-            .long("chunk_size")
-            .value_name("CHUNK_SIZE")
-            .about("Sets the chunk size in bytes")
-            .takes_value(true)
-            .default_value("1024"))
-        .get_matches();
-
-    let input_dir = PathBuf::from(matches.value_of("input").unwrap());
-    let output_dir = PathBuf::from(matches.value_of("output").unwrap());
-    let chunk_size: usize = matches.value_of("chunk_size").unwrap().parse().expect("Invalid chunk size");
-
-    fs::create_dir_all(&output_dir)?;
-
-    process_files(&input_dir, &output_dir, chunk_size)?;
-
-    Ok(())
-}
\ No newline at end of file
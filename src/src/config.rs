@@ -1,13 +1,761 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::define;
+use crate::diagnostic::Diagnostic;
+
+/// Files looked up in the working directory, in order, when no config path is given.
+pub const CONFIG_FILES: [&str; 2] = ["hyperpack.config.json", "hyperpack.toml"];
+
+const NAME_PLACEHOLDER: &str = "[name]";
+
+/// Project configuration, read from `hyperpack.config.json` or `hyperpack.toml`.
+///
+/// Every key is optional; missing keys fall back to the defaults below.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
+    /// Picks the defaults for `minify`, `sourcemap` and `tree_shaking` and the value of
+    /// `process.env.NODE_ENV` and `import.meta.env.MODE`.
+    pub mode: Mode,
     pub entry_file: String,
-    pub output_file: String,
+    /// Named entry points, e.g. `"admin": "src/admin.js"`; takes precedence over `entry_file`.
+    pub entries: BTreeMap<String, String>,
+    /// Output file per entry; `[name]` is replaced with the entry name. Defaults to
+    /// `dist/bundle.js` for a single entry and `dist/[name].js` for several.
+    pub output_file: Option<String>,
+    /// Bundler worker threads; defaults to the available parallelism.
+    pub max_threads: Option<usize>,
+    /// Defaults to on in production mode.
+    pub tree_shaking: Option<bool>,
+    /// Expressions replaced at build time, e.g. `"__VERSION__": "\"1.2.0\""`. Values are
+    /// JavaScript expressions.
+    pub define: BTreeMap<String, String>,
+    pub env: EnvConfig,
+    pub resolve: ResolveConfig,
+    pub minify: MinifyConfig,
+    pub sourcemap: SourcemapConfig,
+    pub splitting: SplittingConfig,
+    pub stats: StatsConfig,
+    pub cache: CacheConfig,
+    pub dev_server: DevServerConfig,
+    /// Loaders by file extension, e.g. `"svg": "dataurl"`, over the defaults in
+    /// [`crate::loader::DEFAULT_LOADERS`]. `js` bundles files as they are.
+    pub loaders: BTreeMap<String, String>,
+    /// Plugins loaded from files, registered in order after the ones passed to the bundler.
+    pub plugins: Vec<PluginConfig>,
+    /// Stops the build at the first error a plugin reports, instead of collecting every error.
+    pub bail: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvConfig {
+    /// Directory holding `.env`, `.env.local`, `.env.[mode]` and `.env.[mode].local`.
+    pub dir: PathBuf,
+    /// Only variables starting with one of these are inlined, so secrets in the same files
+    /// never reach the bundle.
+    pub prefixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResolveConfig {
+    /// Extensions tried, in order, for imports that do not exist as written.
+    pub extensions: Vec<String>,
+    /// Import prefix aliases, e.g. `"@app/": "./src/"`, relative to the project root. Used for
+    /// imports the `paths` and `baseUrl` of the tsconfig do not resolve.
+    pub paths: HashMap<String, String>,
+    /// The `tsconfig.json` whose `paths` and `baseUrl` apply to imports. Defaults to
+    /// `tsconfig.json`, or else `jsconfig.json`, in the project root.
+    pub tsconfig: Option<PathBuf>,
+    /// `package.json` fields naming a package's entry, in order of preference. Listing
+    /// `browser` also applies the replacements in its object form.
+    pub main_fields: Vec<String>,
+    /// Conditions matched in `package.json` `exports` and `imports`, besides `default`,
+    /// `import` or `require` depending on how the module is imported, and the mode.
+    pub conditions: Vec<String>,
+    /// Directories imports may resolve into besides the project and the `node_modules` above it,
+    /// such as a Nix store or vendored sources. Relative to the project root.
+    pub allowed_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinifyConfig {
+    /// Defaults to on in production mode.
+    pub enabled: Option<bool>,
+    pub keep_comments: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcemapConfig {
+    /// Defaults to on in development mode.
+    pub enabled: Option<bool>,
+    /// Defaults to `inline` in development mode and `external` in production mode.
+    pub kind: Option<SourcemapKind>,
+    /// Defaults to the output file with `.map` appended; `[name]` is replaced with the entry name.
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Development,
+    #[default]
+    Production,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourcemapKind {
+    /// A `.map` file referenced from the bundle.
+    External,
+    /// A data URL at the end of the bundle.
+    Inline,
+    /// A `.map` file the bundle does not reference.
+    Hidden,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SplittingConfig {
+    /// Reserved for splitting bundles into chunks; builds reject it until they can.
+    pub enabled: bool,
+    /// Directory `hyperpack split` writes chunks to.
+    pub output_dir: PathBuf,
+    /// Chunk size in bytes used by `hyperpack chunk`.
+    pub chunk_size: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// Write module, chunk and timing statistics after each successful build. A failed build
+    /// removes the file.
+    pub enabled: bool,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Reuse transformed modules from earlier builds.
+    pub enabled: bool,
+    pub dir: PathBuf,
+}
+
+/// A plugin loaded at build time, e.g. `{ "type": "wasm", "path": "plugins/routes.wasm" }`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PluginConfig {
+    /// A WebAssembly module implementing the ABI described in [`crate::plugins::wasm`].
+    Wasm {
+        path: PathBuf,
+        /// Defaults to the file name without its extension.
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// An executable speaking the JSON-RPC protocol described in [`crate::plugins::process`].
+    Process {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Defaults to the command's file name without its extension.
+        #[serde(default)]
+        name: Option<String>,
+        /// Milliseconds to wait for the reply to each call.
+        #[serde(default = "default_plugin_timeout")]
+        timeout_ms: u64,
+    },
+    /// Adds `banner` before and `footer` after every JavaScript output.
+    Banner {
+        #[serde(default)]
+        banner: String,
+        #[serde(default)]
+        footer: String,
+    },
+    /// Redirects imports of each key, or of paths under it, to its value, e.g.
+    /// `"@components": "./src/components"` relative to the project root, or `"react": "preact/compat"`.
+    Alias { entries: BTreeMap<String, String> },
+    /// Replaces each key with its value wherever it appears as a whole word in a module.
+    Replace { values: BTreeMap<String, String> },
+    /// Copies every file under `from` into `to`, which defaults to the output directory.
+    Copy {
+        #[serde(default = "default_copy_from")]
+        from: PathBuf,
+        #[serde(default)]
+        to: Option<PathBuf>,
+    },
+    /// Loads `.json` files as modules whose default export is the parsed value.
+    Json,
+    /// Loads files with one of `extensions` as modules whose default export is their contents.
+    Text {
+        #[serde(default = "default_text_extensions")]
+        extensions: Vec<String>,
+    },
+}
+
+fn default_plugin_timeout() -> u64 {
+    5000
+}
+
+fn default_copy_from() -> PathBuf {
+    PathBuf::from("public")
+}
+
+fn default_text_extensions() -> Vec<String> {
+    vec!["txt".to_string()]
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DevServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Directories watched by `hyperpack dev`.
+    pub watch: Vec<PathBuf>,
+    pub exclude: Vec<PathBuf>,
+    /// Seconds to wait for further changes before reacting to an event.
+    pub debounce: u64,
+    pub clear_log: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            entry_file: "src/index.js".to_string(),
+            entries: BTreeMap::new(),
+            output_file: None,
+            max_threads: None,
+            tree_shaking: None,
+            define: BTreeMap::new(),
+            env: EnvConfig::default(),
+            resolve: ResolveConfig::default(),
+            minify: MinifyConfig::default(),
+            sourcemap: SourcemapConfig::default(),
+            splitting: SplittingConfig::default(),
+            stats: StatsConfig::default(),
+            cache: CacheConfig::default(),
+            dev_server: DevServerConfig::default(),
+            loaders: BTreeMap::new(),
+            plugins: vec![],
+            bail: false,
+        }
+    }
+}
+
+impl Mode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Development => "development",
+            Mode::Production => "production",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "development" | "dev" => Ok(Mode::Development),
+            "production" | "prod" => Ok(Mode::Production),
+            _ => Err(format!("unknown mode '{}', expected development or production", s)),
+        }
+    }
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("."),
+            prefixes: vec!["HYPERPACK_".to_string()],
+        }
+    }
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        Self {
+            extensions: ["js", "jsx", "ts", "tsx", "json"].iter().map(|ext| ext.to_string()).collect(),
+            paths: HashMap::new(),
+            tsconfig: None,
+            main_fields: ["browser", "module", "main"].iter().map(|field| field.to_string()).collect(),
+            conditions: ["browser", "module"].iter().map(|condition| condition.to_string()).collect(),
+            allowed_dirs: vec![],
+        }
+    }
+}
+
+impl Default for SplittingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            output_dir: PathBuf::from("dist"),
+            chunk_size: 1024,
+        }
+    }
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: "dist/stats.json".to_string(),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: PathBuf::from("node_modules/.cache/hyperpack"),
+        }
+    }
+}
+
+impl Default for DevServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3030,
+            watch: vec![PathBuf::from(".")],
+            exclude: vec![],
+            debounce: 2,
+            clear_log: false,
+        }
+    }
 }
 
 impl Config {
-    pub fn new(entry_file: &str, output_file: &str) -> Self {
+    /// Loads `path`, or the first of [`CONFIG_FILES`] that exists when `path` is `None`.
+    /// Falls back to the defaults when no config file is found.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        if let Some(path) = path {
+            return Self::from_file(path);
+        }
+
+        match CONFIG_FILES.iter().map(Path::new).find(|path| path.is_file()) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Reads and validates a config file; `.toml` files are parsed as TOML, anything else as JSON.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|err| ConfigError {
+            file: Some(path.to_path_buf()),
+            key: String::new(),
+            message: format!("Failed to read config file: {}", err),
+        })?;
+
+        let config = if path.extension().is_some_and(|ext| ext == "toml") {
+            Self::from_toml(&contents)
+        } else {
+            Self::from_json(&contents)
+        };
+
+        config
+            .and_then(|config| config.validate().map(|_| config))
+            .map_err(|err| err.in_file(path))
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, ConfigError> {
+        let mut deserializer = serde_json::Deserializer::from_str(contents);
+        deserialize(&mut deserializer)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        deserialize(toml::Deserializer::new(contents))
+    }
+
+    /// Checks the values serde cannot, naming the offending key on failure.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.entry_file.trim().is_empty() {
+            return Err(ConfigError::new("entry_file", "must not be empty"));
+        }
+        for (name, entry_file) in &self.entries {
+            if name.trim().is_empty() {
+                return Err(ConfigError::new("entries", "entry names must not be empty"));
+            }
+            if entry_file.trim().is_empty() {
+                return Err(ConfigError::new(&format!("entries.{}", name), "must not be empty"));
+            }
+        }
+        if self.output_pattern().trim().is_empty() {
+            return Err(ConfigError::new("output_file", "must not be empty"));
+        }
+        if self.entries.len() > 1 && !self.output_pattern().contains(NAME_PLACEHOLDER) {
+            return Err(ConfigError::new(
+                "output_file",
+                "must contain [name] when more than one entry is configured",
+            ));
+        }
+        if self.max_threads == Some(0) {
+            return Err(ConfigError::new("max_threads", "must be at least 1"));
+        }
+
+        for (index, extension) in self.resolve.extensions.iter().enumerate() {
+            let key = format!("resolve.extensions[{}]", index);
+            if extension.is_empty() {
+                return Err(ConfigError::new(&key, "must not be empty"));
+            }
+            if extension.starts_with('.') {
+                return Err(ConfigError::new(&key, "extensions are written without a leading dot"));
+            }
+        }
+        if self.resolve.paths.keys().any(|prefix| prefix.is_empty()) {
+            return Err(ConfigError::new("resolve.paths", "alias prefixes must not be empty"));
+        }
+        if let Some(index) = self.resolve.main_fields.iter().position(|field| field.is_empty()) {
+            return Err(ConfigError::new(&format!("resolve.main_fields[{}]", index), "must not be empty"));
+        }
+        if let Some(index) = self.resolve.conditions.iter().position(|condition| condition.is_empty()) {
+            return Err(ConfigError::new(&format!("resolve.conditions[{}]", index), "must not be empty"));
+        }
+        if let Some(index) = self.resolve.allowed_dirs.iter().position(|dir| dir.as_os_str().is_empty()) {
+            return Err(ConfigError::new(&format!("resolve.allowed_dirs[{}]", index), "must not be empty"));
+        }
+
+        for (key, value) in &self.define {
+            let key_path = format!("define.{}", key);
+            if !is_define_key(key) {
+                return Err(ConfigError::new(
+                    "define",
+                    &format!("'{}' is not an identifier or a dotted member expression", key),
+                ));
+            }
+            if let Err(err) = define::parse_expression(value) {
+                return Err(ConfigError::new(
+                    &key_path,
+                    &format!("'{}' is not a JavaScript expression ({}); quote strings, e.g. \"\\\"text\\\"\"", value, err),
+                ));
+            }
+        }
+
+        for (index, prefix) in self.env.prefixes.iter().enumerate() {
+            if prefix.is_empty() {
+                return Err(ConfigError::new(
+                    &format!("env.prefixes[{}]", index),
+                    "must not be empty, as that would inline every variable including secrets",
+                ));
+            }
+        }
+
+        if let Some(file) = &self.sourcemap.file {
+            if file.trim().is_empty() {
+                return Err(ConfigError::new("sourcemap.file", "must not be empty"));
+            }
+            if self.entries.len() > 1 && !file.contains(NAME_PLACEHOLDER) {
+                return Err(ConfigError::new(
+                    "sourcemap.file",
+                    "must contain [name] when more than one entry is configured",
+                ));
+            }
+        }
+        if self.stats.file.trim().is_empty() {
+            return Err(ConfigError::new("stats.file", "must not be empty"));
+        }
+        if self.splitting.enabled {
+            return Err(ConfigError::new(
+                "splitting.enabled",
+                "is not supported by builds yet; split an entry with `hyperpack split` instead",
+            ));
+        }
+        if self.splitting.chunk_size == 0 {
+            return Err(ConfigError::new("splitting.chunk_size", "must be greater than 0"));
+        }
+        if self.dev_server.host.parse::<IpAddr>().is_err() {
+            return Err(ConfigError::new(
+                "dev_server.host",
+                &format!("'{}' is not an IP address", self.dev_server.host),
+            ));
+        }
+        for (extension, loader) in &self.loaders {
+            if extension.trim_start_matches('.').is_empty() {
+                return Err(ConfigError::new("loaders", "extensions must not be empty"));
+            }
+            if loader.trim().is_empty() {
+                return Err(ConfigError::new(&format!("loaders.{}", extension), "must not be empty"));
+            }
+        }
+        for (index, plugin) in self.plugins.iter().enumerate() {
+            match plugin {
+                PluginConfig::Wasm { path, .. } => {
+                    if path.as_os_str().is_empty() {
+                        return Err(ConfigError::new(&format!("plugins[{}].path", index), "must not be empty"));
+                    }
+                }
+                PluginConfig::Process { command, timeout_ms, .. } => {
+                    if command.trim().is_empty() {
+                        return Err(ConfigError::new(&format!("plugins[{}].command", index), "must not be empty"));
+                    }
+                    if *timeout_ms == 0 {
+                        return Err(ConfigError::new(
+                            &format!("plugins[{}].timeout_ms", index),
+                            "must be greater than 0",
+                        ));
+                    }
+                }
+                PluginConfig::Alias { entries: keys } | PluginConfig::Replace { values: keys } => {
+                    if keys.keys().any(|key| key.is_empty()) {
+                        let field = if matches!(plugin, PluginConfig::Alias { .. }) { "entries" } else { "values" };
+                        return Err(ConfigError::new(
+                            &format!("plugins[{}].{}", index, field),
+                            "keys must not be empty",
+                        ));
+                    }
+                }
+                PluginConfig::Text { extensions } => {
+                    if extensions.iter().any(|ext| ext.trim_start_matches('.').is_empty()) {
+                        return Err(ConfigError::new(
+                            &format!("plugins[{}].extensions", index),
+                            "must not contain empty extensions",
+                        ));
+                    }
+                }
+                PluginConfig::Banner { .. } | PluginConfig::Copy { .. } | PluginConfig::Json => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn minify_enabled(&self) -> bool {
+        self.minify.enabled.unwrap_or(self.mode == Mode::Production)
+    }
+
+    pub fn sourcemap_enabled(&self) -> bool {
+        self.sourcemap.enabled.unwrap_or(self.mode == Mode::Development)
+    }
+
+    pub fn sourcemap_kind(&self) -> SourcemapKind {
+        self.sourcemap.kind.unwrap_or(match self.mode {
+            Mode::Development => SourcemapKind::Inline,
+            Mode::Production => SourcemapKind::External,
+        })
+    }
+
+    pub fn tree_shaking_enabled(&self) -> bool {
+        self.tree_shaking.unwrap_or(self.mode == Mode::Production)
+    }
+
+    /// Every build-time replacement: the mode constants, then the allowed environment
+    /// variables `env` as `import.meta.env.NAME` and `process.env.NAME`, then `define`.
+    pub fn defines(&self, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mode = format!("{:?}", self.mode.as_str());
+        let mut defines = BTreeMap::from([
+            ("process.env.NODE_ENV".to_string(), mode.clone()),
+            ("import.meta.env.MODE".to_string(), mode),
+            ("import.meta.env.DEV".to_string(), (self.mode == Mode::Development).to_string()),
+            ("import.meta.env.PROD".to_string(), (self.mode == Mode::Production).to_string()),
+        ]);
+        for (name, value) in env {
+            let value = serde_json::to_string(value).unwrap_or_default();
+            defines.insert(format!("import.meta.env.{}", name), value.clone());
+            defines.insert(format!("process.env.{}", name), value);
+        }
+        defines.extend(self.define.clone());
+        defines
+    }
+
+    pub fn threads(&self) -> usize {
+        self.max_threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(4, |n| n.get()))
+    }
+
+    /// The named entry points, or `entry_file` named `main` when `entries` is empty.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        if self.entries.is_empty() {
+            return vec![("main".to_string(), self.entry_file.clone())];
+        }
+        self.entries
+            .iter()
+            .map(|(name, entry_file)| (name.clone(), entry_file.clone()))
+            .collect()
+    }
+
+    /// `output_file`, or its default for the number of entries.
+    pub fn output_pattern(&self) -> &str {
+        match &self.output_file {
+            Some(output_file) => output_file,
+            None if self.entries.len() > 1 => "dist/[name].js",
+            None => "dist/bundle.js",
+        }
+    }
+
+    pub fn output_file(&self, entry_name: &str) -> String {
+        self.output_pattern().replace(NAME_PLACEHOLDER, entry_name)
+    }
+
+    pub fn sourcemap_file(&self, entry_name: &str) -> String {
+        match &self.sourcemap.file {
+            Some(file) => file.replace(NAME_PLACEHOLDER, entry_name),
+            None => format!("{}.map", self.output_file(entry_name)),
+        }
+    }
+}
+
+fn is_define_key(key: &str) -> bool {
+    let segments: Vec<&str> = key.split('.').collect();
+    segments.iter().enumerate().all(|(index, segment)| {
+        let mut chars = segment.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+        // `import.meta` is the only keyword allowed to start a key.
+        valid && (index > 0 || *segment != "import" || segments.get(1) == Some(&"meta"))
+    })
+}
+
+fn deserialize<'de, D, T>(deserializer: D) -> Result<T, ConfigError>
+where
+    D: serde::Deserializer<'de>,
+    D::Error: fmt::Display,
+    T: DeserializeOwned,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let key = err.path().to_string();
+        ConfigError {
+            file: None,
+            // The path of a syntax error is just the document root.
+            key: if key == "." { String::new() } else { key },
+            message: err.into_inner().to_string().trim().to_string(),
+        }
+    })
+}
+
+/// A config file that could not be read, parsed or validated.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub file: Option<PathBuf>,
+    /// Dotted path of the offending key, e.g. `dev_server.port`; empty for file-level errors.
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: &str, message: &str) -> Self {
         Self {
-            entry_file: entry_file.to_string(),
-            output_file: output_file.to_string(),
+            file: None,
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn in_file(mut self, file: &Path) -> Self {
+        self.file.get_or_insert_with(|| file.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file.display())?;
+        }
+        if !self.key.is_empty() {
+            write!(f, "invalid value for `{}`: ", self.key)?;
         }
+        write!(f, "{}", self.message)
     }
-}
\ No newline at end of file
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for Diagnostic {
+    fn from(err: ConfigError) -> Self {
+        let message = if err.key.is_empty() {
+            err.message
+        } else {
+            format!("invalid value for `{}`: {}", err.key, err.message)
+        };
+        let diagnostic = Diagnostic::error("config/invalid", message);
+        match err.file {
+            Some(file) => diagnostic.with_file(file),
+            None => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_offending_key() {
+        let err = Config::from_json(r#"{ "dev_server": { "port": "80" } }"#).unwrap_err();
+        assert_eq!(err.key, "dev_server.port");
+
+        let err = Config::from_toml("[resolve]\nextensions = [\"js\", 3]\n").unwrap_err();
+        assert_eq!(err.key, "resolve.extensions[1]");
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let err = Config::from_json(r#"{ "minify": { "enable": true } }"#).unwrap_err();
+        assert_eq!(err.key, "minify.enable");
+    }
+
+    #[test]
+    fn validates_values() {
+        let config = Config::from_toml("[splitting]\nchunk_size = 0\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "splitting.chunk_size");
+        let config = Config::from_toml("[splitting]\nenabled = true\n").unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "splitting.enabled");
+    }
+
+    #[test]
+    fn mode_picks_the_defaults() {
+        let config = Config::from_json(r#"{ "mode": "development", "minify": { "enabled": true } }"#).unwrap();
+        assert!(config.minify_enabled());
+        assert!(!config.tree_shaking_enabled());
+        assert_eq!(config.sourcemap_kind(), SourcemapKind::Inline);
+        let env = BTreeMap::from([("HYPERPACK_API".to_string(), "https://api".to_string())]);
+        let defines = config.defines(&env);
+        assert_eq!(defines["process.env.NODE_ENV"], "\"development\"");
+        assert_eq!(defines["import.meta.env.HYPERPACK_API"], "\"https://api\"");
+
+        let config = Config::from_json(r#"{ "define": { "__VERSION__": "1.2.0.3" } }"#).unwrap();
+        assert_eq!(config.validate().unwrap_err().key, "define.__VERSION__");
+    }
+
+    #[test]
+    fn names_outputs_after_entries() {
+        let config = Config::from_json(
+            r#"{ "entries": { "admin": "src/admin.js", "sw": "src/sw.js" }, "output_file": "dist/[name].js" }"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.output_file("sw"), "dist/sw.js");
+        assert_eq!(config.sourcemap_file("admin"), "dist/admin.js.map");
+
+        let config = Config { output_file: Some("dist/bundle.js".to_string()), ..config };
+        assert_eq!(config.validate().unwrap_err().key, "output_file");
+
+        let config = Config { output_file: None, ..config };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.output_file("admin"), "dist/admin.js");
+        assert_eq!(config.output_file("sw"), "dist/sw.js");
+        assert_eq!(Config::default().output_file("main"), "dist/bundle.js");
+    }
+}
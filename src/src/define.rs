@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use swc_common::collections::AHashMap;
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, Globals, Mark, SourceMap, Spanned, GLOBALS};
+use swc_ecmascript::ast::{EsVersion, Expr};
+use swc_ecmascript::codegen::text_writer::JsWriter;
+use swc_ecmascript::codegen::{self, Emitter};
+use swc_ecmascript::parser::{parse_file_as_expr, parse_file_as_module, EsSyntax, Syntax, TsSyntax};
+use swc_ecmascript::transforms::optimization::inline_globals2;
+use swc_ecmascript::transforms::optimization::simplify::{dead_branch_remover, expr_simplifier};
+use swc_ecmascript::transforms::{fixer, resolver};
+use swc_ecmascript::utils::NodeIgnoringSpan;
+use swc_ecmascript::visit::FoldWith;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::plugin::TransformResult;
+use crate::sourcemap;
+
+/// Parses a define key or value, e.g. `process.env.NODE_ENV` or `"production"`.
+pub fn parse_expression(source: &str) -> Result<Expr, String> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Anon), source.trim().to_string());
+    let expr = parse_file_as_expr(&fm, Syntax::Es(Default::default()), EsVersion::latest(), None, &mut vec![])
+        .map_err(|err| err.kind().msg().to_string())?;
+    // The parser stops after the first complete expression; anything left over is an error.
+    if expr.span().hi != fm.end_pos {
+        return Err("unexpected input after the expression".to_string());
+    }
+    Ok(*expr)
+}
+
+/// Replaces every expression in `defines` (key => JavaScript expression) found in the module,
+/// then drops the branches whose conditions became constant. Bindings that shadow a defined
+/// identifier are left alone. `map` maps `code` to the original source, and the returned map
+/// extends it over the rewrite.
+pub fn apply(
+    file_path: &str,
+    code: &str,
+    map: Option<&str>,
+    defines: &BTreeMap<String, String>,
+) -> Result<TransformResult, Diagnostic> {
+    let unchanged = || TransformResult { code: code.to_string(), map: map.map(str::to_string) };
+    if !defines.keys().any(|key| code.contains(key.as_str())) {
+        return Ok(unchanged());
+    }
+
+    let mut globals = AHashMap::default();
+    let mut global_exprs = AHashMap::default();
+    for (key, value) in defines {
+        let invalid = |err: String| {
+            Diagnostic::error("define/invalid", format!("Invalid define `{}`: {}", key, err))
+                .with_note(format!("values are JavaScript expressions, e.g. {:?}", "\"production\""))
+        };
+        let value = parse_expression(value).map_err(invalid)?;
+        match parse_expression(key).map_err(invalid)? {
+            Expr::Ident(ident) => {
+                globals.insert(ident.sym, value);
+            }
+            expr => {
+                global_exprs.insert(NodeIgnoringSpan::owned(expr), value);
+            }
+        }
+    }
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let comments = SingleThreadedComments::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Real(file_path.into())), code.to_string());
+    let module = parse_file_as_module(&fm, syntax_for(file_path), EsVersion::latest(), Some(&comments), &mut vec![])
+        .map_err(|err| {
+            let lo = cm.lookup_char_pos(err.span().lo);
+            Diagnostic::error("define/parse", err.kind().msg().to_string())
+                .with_file(file_path)
+                .with_span(Span::new(lo.line, lo.col.0 + 1, lo.line, lo.col.0 + 2))
+        })?;
+
+    let module = GLOBALS.set(&Globals::new(), || {
+        let unresolved_mark = Mark::new();
+        let top_level_mark = Mark::new();
+        let is_ts = matches!(syntax_for(file_path), Syntax::Typescript(_));
+
+        module
+            .fold_with(&mut resolver(unresolved_mark, top_level_mark, is_ts))
+            .fold_with(&mut inline_globals2(
+                Default::default(),
+                Lrc::new(globals),
+                Lrc::new(global_exprs),
+                Default::default(),
+            ))
+            .fold_with(&mut expr_simplifier(unresolved_mark, Default::default()))
+            .fold_with(&mut dead_branch_remover(unresolved_mark))
+            .fold_with(&mut fixer(Some(&comments)))
+    });
+
+    let codegen_error = |err: String| Diagnostic::error("define/codegen", err).with_file(file_path);
+    let mut buf = Vec::new();
+    let mut mappings = Vec::new();
+    {
+        let mut emitter = Emitter {
+            cfg: codegen::Config::default(),
+            cm: cm.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut mappings)),
+        };
+        emitter.emit_module(&module).map_err(|err| codegen_error(err.to_string()))?;
+    }
+    let input_map = map.map(sourcemap::parse).transpose().map_err(codegen_error)?;
+    Ok(TransformResult {
+        code: String::from_utf8(buf).map_err(|err| codegen_error(err.to_string()))?,
+        map: Some(sourcemap::to_json(&cm.build_source_map_from(&mappings, input_map.as_ref()))),
+    })
+}
+
+/// How to parse a module, by its extension.
+pub(crate) fn syntax_for(file_path: &str) -> Syntax {
+    match Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+        Some("ts") | Some("mts") | Some("cts") => Syntax::Typescript(TsSyntax::default()),
+        Some("tsx") => Syntax::Typescript(TsSyntax { tsx: true, ..Default::default() }),
+        _ => Syntax::Es(EsSyntax { jsx: true, ..Default::default() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_defines_and_drops_dead_branches() {
+        let defines = BTreeMap::from([
+            ("process.env.NODE_ENV".to_string(), "\"production\"".to_string()),
+            ("import.meta.env.MODE".to_string(), "\"production\"".to_string()),
+            ("__DEV__".to_string(), "false".to_string()),
+        ]);
+        let code = "if (process.env.NODE_ENV !== \"production\") { debug(); }\n\
+                    export const mode = import.meta.env.MODE;\n\
+                    if (__DEV__) { check(); }\n\
+                    function local(__DEV__) { return __DEV__; }\n";
+
+        let output = apply("src/a.js", code, None, &defines).unwrap().code;
+        assert!(!output.contains("debug"));
+        assert!(!output.contains("check"));
+        assert!(output.contains("export const mode = \"production\";"));
+        assert!(output.contains("return __DEV__;"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A source range. Lines and columns are 1-based; columns count characters, and the end
+/// column is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, end_line: usize, end_column: usize) -> Self {
+        Self { line, column, end_line, end_column }
+    }
+
+    /// The span of the bytes `start..end` of `source`.
+    pub fn from_offsets(source: &str, start: usize, end: usize) -> Self {
+        let (line, column) = line_column(source, start);
+        let (end_line, end_column) = line_column(source, end.max(start));
+        Self { line, column, end_line, end_column }
+    }
+
+    /// The span of the bytes `start..end` of `text`, which is line `line` of its file.
+    pub fn on_line(line: usize, text: &str, start: usize, end: usize) -> Self {
+        let span = Self::from_offsets(text, start, end);
+        Self::new(line, span.column, line, span.end_column)
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// An error, warning or note reported by any part of Hyperpack.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier such as `resolve/not-found` or `lint/console-log`.
+    pub code: String,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    /// A suggested fix, shown as `help:`.
+    pub fix: Option<String>,
+    /// The plugin that reported it, if any.
+    pub plugin: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: code.to_string(),
+            message: message.into(),
+            file: None,
+            span: None,
+            notes: vec![],
+            fix: None,
+            plugin: None,
+        }
+    }
+
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_file(mut self, file: impl AsRef<Path>) -> Self {
+        self.file = Some(file.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    pub fn with_plugin(mut self, plugin: &str) -> Self {
+        self.plugin = Some(plugin.to_string());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Renders the diagnostic with a code frame for terminals. `source` is the text the span
+    /// points into; when `None` it is read from `file`.
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+        let (label, label_color) = match self.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+            Severity::Info => ("info", "1;36"),
+        };
+
+        let mut out = format!(
+            "{} {}\n",
+            paint(label_color, &format!("{}[{}]:", label, self.code)),
+            paint("1", &self.message)
+        );
+
+        let line_text = self.span.and_then(|span| {
+            let text = match source {
+                Some(source) => source.to_string(),
+                None => fs::read_to_string(self.file.as_ref()?).ok()?,
+            };
+            text.lines().nth(span.line.checked_sub(1)?).map(str::to_string)
+        });
+        let gutter = self
+            .span
+            .map_or(1, |span| span.line.to_string().len())
+            .max(1);
+        let pad = " ".repeat(gutter);
+
+        if let Some(file) = &self.file {
+            let location = match self.span {
+                Some(span) => format!("{}:{}:{}", file.display(), span.line, span.column),
+                None => file.display().to_string(),
+            };
+            out.push_str(&format!("{}{} {}\n", pad, paint("1;34", "-->"), location));
+        }
+
+        if let (Some(span), Some(line_text)) = (self.span, line_text) {
+            let width = line_text.chars().count();
+            let start = span.column.saturating_sub(1).min(width);
+            let end = if span.end_line == span.line { span.end_column.saturating_sub(1) } else { width };
+            let carets = "^".repeat(end.min(width).saturating_sub(start).max(1));
+            let bar = paint("1;34", "|");
+
+            out.push_str(&format!("{} {}\n", pad, bar));
+            out.push_str(&format!("{} {} {}\n", paint("1;34", &span.line.to_string()), bar, line_text));
+            out.push_str(&format!("{} {} {}{}\n", pad, bar, " ".repeat(start), paint(label_color, &carets)));
+        }
+
+        if let Some(plugin) = &self.plugin {
+            out.push_str(&format!("{} {} plugin: {}\n", pad, paint("1;34", "="), plugin));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} {} note: {}\n", pad, paint("1;34", "="), note));
+        }
+        if let Some(fix) = &self.fix {
+            out.push_str(&format!("{} {} help: {}\n", pad, paint("1;34", "="), fix));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file.display())?;
+            if let Some(span) = self.span {
+                write!(f, ":{}:{}", span.line, span.column)?;
+            }
+            write!(f, ": ")?;
+        }
+        if let Some(plugin) = &self.plugin {
+            write!(f, "[{}] ", plugin)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_count_characters_from_one() {
+        let source = "let a = 1;\nconst é = \"./x\";\n";
+        let start = source.find("\"./x\"").unwrap();
+        assert_eq!(Span::from_offsets(source, start, start + 5), Span::new(2, 11, 2, 16));
+    }
+
+    #[test]
+    fn renders_a_code_frame() {
+        let diagnostic = Diagnostic::error("resolve/not-found", "Unable to resolve import './x'")
+            .with_file("src/a.js")
+            .with_span(Span::new(1, 15, 1, 20))
+            .with_fix("did you mean './y'?");
+
+        let rendered = diagnostic.render(Some("import a from './x';"), false);
+        assert_eq!(
+            rendered,
+            "error[resolve/not-found]: Unable to resolve import './x'\n \
+             --> src/a.js:1:15\n  \
+             |\n\
+             1 | import a from './x';\n  \
+             |               ^^^^^\n  \
+             = help: did you mean './y'?\n"
+        );
+    }
+}
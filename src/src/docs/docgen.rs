@@ -1,55 +1,67 @@
 use regex::Regex;
 use serde::Deserialize;
-use std::env;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+
+/// Default name of the documentation configuration file.
+pub const CONFIG_FILE: &str = "docgen.config.json";
 
 #[derive(Deserialize)]
-struct Config {
-    output_format: String,
-    include_index: bool,
+pub struct Config {
+    /// Options: "markdown", "html", "both".
+    pub output_format: String,
+    pub include_index: bool,
 }
 
-fn main() -> std::io::Result<()> {
-    // Load configuration
-    let config: Config = load_config("docgen.config.json")?;
-
-    // Get command-line arguments
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: docgen <directory>");
-        std::process::exit(1);
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_format: "markdown".to_string(),
+            include_index: true,
+        }
     }
+}
 
-    let dir = &args[1];
+/// Counts reported after generating documentation.
+pub struct Summary {
+    pub file_count: u32,
+    pub error_count: u32,
+}
+
+/// Generates documentation for every JS/TS file under `dir` and writes it to `out_dir`.
+///
+/// # Arguments
+/// * `dir` - The directory to process.
+/// * `out_dir` - The directory the documentation files are written to.
+/// * `config` - Output format and index options.
+///
+/// # Returns
+/// * `Ok(Summary)` - The number of processed files and errors.
+/// * `Err(e)` - If the directory cannot be read or an output file cannot be written.
+pub fn generate(dir: &Path, out_dir: &Path, config: &Config) -> io::Result<Summary> {
     let mut markdown = String::new();
     let mut html = String::new();
-    let mut index = String::new();
     let mut file_count = 0;
     let mut error_count = 0;
     let mut file_paths = Vec::new();
 
     // Process the specified directory
-    if let Err(e) = process_directory(dir, &mut markdown, &mut html, &mut index, &mut file_count, &mut error_count, &mut file_paths) {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    }
+    process_directory(&dir.to_string_lossy(), &mut markdown, &mut html, &mut file_count, &mut error_count, &mut file_paths)?;
 
     // Write the generated documentation to files
+    fs::create_dir_all(out_dir)?;
     if config.output_format == "markdown" || config.output_format == "both" {
-        fs::write("DOCUMENTATION.md", markdown)?;
+        fs::write(out_dir.join("DOCUMENTATION.md"), markdown)?;
     }
     if config.output_format == "html" || config.output_format == "both" {
-        fs::write("DOCUMENTATION.html", html)?;
+        fs::write(out_dir.join("DOCUMENTATION.html"), html)?;
     }
     if config.include_index {
-        fs::write("INDEX.md", index)?;
+        fs::write(out_dir.join("INDEX.md"), generate_index(&file_paths))?;
     }
 
-    println!("Processed {} files with {} errors.", file_count, error_count);
-
-    Ok(())
+    Ok(Summary { file_count, error_count })
 }
 
 /// Loads the configuration from a JSON file.
@@ -58,10 +70,23 @@ fn main() -> std::io::Result<()> {
 /// * `filename` - The name of the configuration file.
 /// 
 /// # Returns
-/// * `Result<Config, serde_json::Error>` - The loaded configuration.
-fn load_config(filename: &str) -> Result<Config, serde_json::Error> {
-    let config_str = fs::read_to_string(filename)?;
-    serde_json::from_str(&config_str)
+/// * `Result<Config, String>` - The loaded configuration.
+pub fn load_config(filename: &str) -> Result<Config, String> {
+    let config_str = fs::read_to_string(filename)
+        .map_err(|err| format!("Failed to read {}: {}", filename, err))?;
+    serde_json::from_str(&config_str).map_err(|err| format!("Failed to parse {}: {}", filename, err))
+}
+
+/// Builds a Markdown index linking every processed file.
+fn generate_index(file_paths: &[PathBuf]) -> String {
+    let mut index = String::new();
+    if !file_paths.is_empty() {
+        index.push_str("# File Index\n\n");
+        for path in file_paths {
+            index.push_str(&format!("- [{}]({})\n", path.display(), path.display()));
+        }
+    }
+    index
 }
 
 /// Recursively processes files in the specified directory.
@@ -70,7 +95,6 @@ fn load_config(filename: &str) -> Result<Config, serde_json::Error> {
 /// * `dir` - The directory to process.
 /// * `markdown` - A mutable reference to a string to accumulate Markdown content.
 /// * `html` - A mutable reference to a string to accumulate HTML content.
-/// * `index` - A mutable reference to a string to accumulate file index.
 /// * `file_count` - A mutable reference to count processed files.
 /// * `error_count` - A mutable reference to count errors encountered.
 /// * `file_paths` - A mutable reference to store paths of processed files.
@@ -82,7 +106,6 @@ fn process_directory(
     dir: &str,
     markdown: &mut String,
     html: &mut String,
-    index: &mut String,
     file_count: &mut u32,
     error_count: &mut u32,
     file_paths: &mut Vec<PathBuf>,
@@ -94,7 +117,7 @@ fn process_directory(
 
         if path.is_dir() {
             // Recursively process subdirectories
-            process_directory(&path.to_string_lossy(), markdown, html, index, file_count, error_count, file_paths)?;
+            process_directory(&path.to_string_lossy(), markdown, html, file_count, error_count, file_paths)?;
         } else if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
             if ext == "js" || ext == "ts" {
                 // Process JavaScript and TypeScript files
@@ -123,14 +146,6 @@ fn process_directory(
         }
     }
 
-    // Generate file index
-    if !file_paths.is_empty() {
-        index.push_str("# File Index\n\n");
-        for path in file_paths {
-            index.push_str(&format!("- [{}]({})\n", path.display(), path.display().to_string()));
-        }
-    }
-
     Ok(())
 }
 
@@ -142,7 +157,7 @@ fn process_directory(
 /// # Returns
 /// * `Ok((String, String))` - Markdown and HTML content of the file documentation.
 /// * `Err(e)` - If an error occurs while reading the file.
-fn process_file(path: &PathBuf) -> std::io::Result<(String, String)> {
+fn process_file(path: &Path) -> std::io::Result<(String, String)> {
     let content = fs::read_to_string(path)?;
     let comments = extract_jsdoc_comments(&content);
 
@@ -173,7 +188,7 @@ fn extract_jsdoc_comments(content: &str) -> Vec<String> {
                 // Start of a JSDoc comment
                 in_comment = true;
                 current_comment.clear();
-                current_comment.push_str(&line.trim_start_matches("/**").trim().to_string());
+                current_comment.push_str(line.trim_start_matches("/**").trim());
             } else if line.trim().starts_with("*/") {
                 // End of a JSDoc comment
                 if in_comment {
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Span};
+
+/// The dotenv files read for `mode`, lowest precedence first.
+pub fn env_files(mode: &str) -> [String; 4] {
+    [
+        ".env".to_string(),
+        ".env.local".to_string(),
+        format!(".env.{}", mode),
+        format!(".env.{}.local", mode),
+    ]
+}
+
+/// Loads the variables the bundle may see: the dotenv files in `env.dir` for the current mode,
+/// overridden by the process environment, keeping only names that start with one of
+/// `env.prefixes`.
+pub fn load(config: &Config) -> Result<BTreeMap<String, String>, Diagnostic> {
+    let mut vars = BTreeMap::new();
+    for file_name in env_files(config.mode.as_str()) {
+        let path = config.env.dir.join(file_name);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(Diagnostic::error("env/read", format!("Unable to read env file: {}", err)).with_file(&path))
+            }
+        };
+        vars.extend(parse(&contents).map_err(|diagnostic| diagnostic.with_file(&path))?);
+    }
+    vars.extend(env::vars());
+
+    vars.retain(|name, _| config.env.prefixes.iter().any(|prefix| name.starts_with(prefix.as_str())));
+    Ok(vars)
+}
+
+/// Parses dotenv syntax: `KEY=value` lines, optionally prefixed with `export`, with `#`
+/// comments and single- or double-quoted values. Double-quoted values understand `\n`, `\t`,
+/// `\"` and `\\`.
+pub fn parse(contents: &str) -> Result<BTreeMap<String, String>, Diagnostic> {
+    let mut vars = BTreeMap::new();
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| {
+            let start = line.len() - line.trim_start().len();
+            Diagnostic::error("env/parse", message).with_span(Span::on_line(index + 1, line, start, line.trim_end().len()))
+        };
+
+        let assignment = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+        let Some((name, value)) = assignment.split_once('=') else {
+            return Err(error("Expected `NAME=value`"));
+        };
+        let name = name.trim();
+        let valid_name = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(error(&format!("'{}' is not a valid variable name", name)));
+        }
+
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut escaped = false;
+                let closing = value[1..].find(|c: char| {
+                    let found = c == quote && !escaped;
+                    escaped = quote == '"' && c == '\\' && !escaped;
+                    found
+                });
+                let Some(end) = closing else {
+                    return Err(error("Unterminated quoted value"));
+                };
+                let inner = &value[1..end + 1];
+                if quote == '"' {
+                    unescape(inner)
+                } else {
+                    inner.to_string()
+                }
+            }
+            // An unquoted value ends at a ` #` comment.
+            _ => value.split(" #").next().unwrap_or_default().trim_end().to_string(),
+        };
+        vars.insert(name.to_string(), value);
+    }
+    Ok(vars)
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dotenv_syntax() {
+        let vars = parse(
+            "# API settings\nHYPERPACK_API_URL=https://api.example.com # prod\nexport HYPERPACK_NAME='My App'\nHYPERPACK_GREETING=\"a\\n\\\"b\\\"\"\n",
+        )
+        .unwrap();
+        assert_eq!(vars["HYPERPACK_API_URL"], "https://api.example.com");
+        assert_eq!(vars["HYPERPACK_NAME"], "My App");
+        assert_eq!(vars["HYPERPACK_GREETING"], "a\n\"b\"");
+
+        let err = parse("OK=1\n  NOT VALID\n").unwrap_err();
+        assert_eq!(err.span, Some(Span::new(2, 3, 2, 12)));
+    }
+}
@@ -1,5 +1,6 @@
 use std::fs;
-use std::io::prelude::*;
+use std::io;
+use std::path::Path;
 use regex::Regex;
 
 /// Formats TypeScript and JavaScript code.
@@ -13,17 +14,15 @@ fn format_js_ts(content: &str) -> String {
     let re_multi_line_comment = Regex::new(r"/\*[\s\S]*?\*/").unwrap();
 
     // Remove single-line and multi-line comments
-    let no_comments = re_multi_line_comment.replace_all(
-        &re_single_line_comment.replace_all(content, ""),
-        ""
-    );
+    let no_single_line_comments = re_single_line_comment.replace_all(content, "");
+    let no_comments = re_multi_line_comment.replace_all(&no_single_line_comments, "");
 
     // Normalize spaces
     let normalized_spaces = re_whitespace.replace_all(&no_comments, " ").to_string();
 
     let mut in_string = false;
     let mut in_template = false;
-    let mut indent_level = 0;
+    let mut indent_level: usize = 0;
 
     for c in normalized_spaces.chars() {
         match c {
@@ -42,7 +41,7 @@ fn format_js_ts(content: &str) -> String {
                 result.push_str(&"  ".repeat(indent_level));
             },
             '}' if !in_string && !in_template => {
-                indent_level -= 1;
+                indent_level = indent_level.saturating_sub(1);
                 result.push('\n');
                 result.push_str(&"  ".repeat(indent_level));
                 result.push(c);
@@ -70,7 +69,7 @@ fn format_html(content: &str) -> String {
     // Normalize multiple spaces
     let normalized_spaces = re_whitespace.replace_all(content, " ").to_string();
 
-    let mut indent_level = 0;
+    let mut indent_level: usize = 0;
     let mut in_tag = false;
     let mut is_closing_tag = false;
 
@@ -90,7 +89,7 @@ fn format_html(content: &str) -> String {
                 result.push(c);
                 in_tag = false;
                 if is_closing_tag {
-                    indent_level -= 1;
+                    indent_level = indent_level.saturating_sub(1);
                 } else {
                     indent_level += 1;
                 }
@@ -122,7 +121,7 @@ fn format_css(content: &str) -> String {
     // Normalize spaces
     let normalized_spaces = re_whitespace.replace_all(&no_comments, " ").to_string();
     let mut in_block = false;
-    let mut indent_level = 0;
+    let mut indent_level: usize = 0;
 
     for c in normalized_spaces.chars() {
         match c {
@@ -137,7 +136,7 @@ fn format_css(content: &str) -> String {
             },
             '}' => {
                 if in_block {
-                    indent_level -= 1;
+                    indent_level = indent_level.saturating_sub(1);
                     result.push('\n');
                     result.push_str(&"  ".repeat(indent_level));
                     result.push(c);
@@ -156,32 +155,30 @@ fn format_css(content: &str) -> String {
     result
 }
 
-/// Main function to format files.
+/// Formats `content` according to the file extension.
 ///
-/// Reads content from specified files, formats it based on the file type, and writes it back.
-/// Supported file types: TypeScript, JavaScript, HTML, and CSS.
-fn main() {
-    let file_paths = vec![
-        "example.ts",
-        "example.js",
-        "example.html",
-        "example.css"
-    ];
-
-    for path in file_paths {
-        match fs::read_to_string(path) {
-            Ok(content) => {
-                let formatted_content = match path.split('.').last().unwrap() {
-                    "ts" | "js" => format_js_ts(&content),
-                    "html" => format_html(&content),
-                    "css" => format_css(&content),
-                    _ => content,
-                };
-
-                let mut file = fs::File::create(path).expect("Unable to create file");
-                file.write_all(formatted_content.as_bytes()).expect("Unable to write data");
-            }
-            Err(e) => eprintln!("Error reading {}: {}", path, e),
-        }
+/// Supported extensions: TypeScript, JavaScript, HTML, and CSS. Anything else is returned unchanged.
+pub fn format_source(extension: &str, content: &str) -> String {
+    match extension {
+        "ts" | "js" => format_js_ts(content),
+        "html" => format_html(content),
+        "css" => format_css(content),
+        _ => content.to_string(),
+    }
+}
+
+/// Formats a file in place.
+///
+/// Reads the file, formats it based on its extension and, when `write` is set, writes it back.
+/// Returns whether the formatted content differs from what is on disk.
+pub fn format_file(path: &Path, write: bool) -> io::Result<bool> {
+    let content = fs::read_to_string(path)?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let formatted_content = format_source(extension, &content);
+
+    let changed = formatted_content != content;
+    if changed && write {
+        fs::write(path, formatted_content)?;
     }
-}
\ No newline at end of file
+    Ok(changed)
+}
@@ -1,56 +1,42 @@
-use regex::Regex;
-use std::env;
+use fancy_regex::Regex;
 use std::fs;
-use std::process;
-
-/// Main entry point of the linter.
-fn main() {
-    // Collect command-line arguments.
-    let args: Vec<String> = env::args().collect();
-
-    // Check if the correct number of arguments are provided.
-    if args.len() != 3 {
-        eprintln!("Usage: {} <file> <type>", args[0]);
-        eprintln!("Types: html, css, js");
-        process::exit(1);
-    }
+use std::path::Path;
+
+use crate::diagnostic::{Diagnostic, Span};
+
+/// Lints a single file, inferring the file type from its extension when `file_type` is `None`.
+pub fn lint_file(path: &Path, file_type: Option<&str>) -> Result<Vec<Diagnostic>, Diagnostic> {
+    let file_type = file_type
+        .or_else(|| path.extension().and_then(|ext| ext.to_str()))
+        .unwrap_or_default();
 
-    // Extract filename and file type from arguments.
-    let filename = &args[1];
-    let file_type = &args[2];
-    
     // Read the file content.
-    let content = match fs::read_to_string(filename) {
-        Ok(content) => content,
-        Err(err) => {
-            eprintln!("Error reading file: {}", err);
-            process::exit(1);
-        }
-    };
+    let content = fs::read_to_string(path).map_err(|err| {
+        Diagnostic::error("lint/read", format!("Error reading file: {}", err)).with_file(path)
+    })?;
 
     // Run the appropriate check based on the file type.
-    let issues = match file_type.as_str() {
-        "html" => check_html(&content),
+    let issues = match file_type {
+        "html" | "htm" => check_html(&content),
         "css" => check_css(&content),
-        "js" => check_js(&content),
+        "js" | "mjs" | "cjs" => check_js(&content),
         _ => {
-            eprintln!("Unsupported file type: {}", file_type);
-            process::exit(1);
+            return Err(Diagnostic::error("lint/unsupported", format!("Unsupported file type: {}", file_type))
+                .with_file(path)
+                .with_note("supported types: html, css, js"))
         }
     };
+    Ok(issues.into_iter().map(|issue| issue.with_file(path)).collect())
+}
 
-    // Output the issues found or a message indicating no issues.
-    if issues.is_empty() {
-        println!("No issues found.");
-    } else {
-        for issue in issues {
-            println!("{}", issue);
-        }
-    }
+/// A warning for the bytes `start..end` of the zero-based line `line_number`.
+fn issue(line_number: usize, line: &str, start: usize, end: usize, code: &str, message: &str) -> Diagnostic {
+    Diagnostic::warning(&format!("lint/{}", code), message)
+        .with_span(Span::on_line(line_number + 1, line, start, end))
 }
 
 /// Check HTML for common issues.
-fn check_html(content: &str) -> Vec<String> {
+fn check_html(content: &str) -> Vec<Diagnostic> {
     let mut issues = Vec::new();
 
     // Regex to detect unclosed tags.
@@ -80,36 +66,36 @@ fn check_html(content: &str) -> Vec<String> {
     // Iterate through each line of the HTML content.
     for (line_number, line) in content.lines().enumerate() {
         // Check for missing doctype.
-        if !missing_doctype_re.is_match(line) {
-            issues.push(format!("Line {}: Missing doctype declaration", line_number + 1));
+        if !missing_doctype_re.is_match(line).unwrap_or(false) {
+            issues.push(issue(line_number, line, 0, line.len(), "missing-doctype", "Missing doctype declaration"));
         }
         // Check for unclosed tags.
-        if unclosed_tag_re.is_match(line) {
-            issues.push(format!("Line {}: Unclosed tag detected", line_number + 1));
+        if let Ok(Some(found)) = unclosed_tag_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "unclosed-tag", "Unclosed tag detected"));
         }
         // Check for missing alt attributes in <img> tags.
-        if missing_alt_re.is_match(line) {
-            issues.push(format!("Line {}: Missing alt attribute in <img> tag", line_number + 1));
+        if let Ok(Some(found)) = missing_alt_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "missing-alt", "Missing alt attribute in <img> tag"));
         }
         // Check for multiple spaces between attributes.
-        if multiple_spaces_re.is_match(line) {
-            issues.push(format!("Line {}: Multiple spaces between attributes", line_number + 1));
+        if let Ok(Some(found)) = multiple_spaces_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "multiple-spaces", "Multiple spaces between attributes"));
         }
         // Check for inline styles.
-        if inline_styles_re.is_match(line) {
-            issues.push(format!("Line {}: Inline styles detected", line_number + 1));
+        if let Ok(Some(found)) = inline_styles_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "inline-style", "Inline styles detected"));
         }
         // Check for missing closing tags (basic check).
-        if missing_closing_tag_re.is_match(line) {
-            issues.push(format!("Line {}: Potential missing closing tag", line_number + 1));
+        if let Ok(Some(found)) = missing_closing_tag_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "missing-closing-tag", "Potential missing closing tag"));
         }
         // Check for empty tags.
-        if empty_tag_re.is_match(line) {
-            issues.push(format!("Line {}: Empty tag detected", line_number + 1));
+        if let Ok(Some(found)) = empty_tag_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "empty-tag", "Empty tag detected"));
         }
         // Check for deprecated tags.
-        if deprecated_tags_re.is_match(line) {
-            issues.push(format!("Line {}: Deprecated tag detected", line_number + 1));
+        if let Ok(Some(found)) = deprecated_tags_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "deprecated-tag", "Deprecated tag detected"));
         }
     }
 
@@ -117,7 +103,7 @@ fn check_html(content: &str) -> Vec<String> {
 }
 
 /// Check CSS for common issues.
-fn check_css(content: &str) -> Vec<String> {
+fn check_css(content: &str) -> Vec<Diagnostic> {
     let mut issues = Vec::new();
 
     // Regex to detect missing semicolons before closing braces.
@@ -144,32 +130,32 @@ fn check_css(content: &str) -> Vec<String> {
     // Iterate through each line of the CSS content.
     for (line_number, line) in content.lines().enumerate() {
         // Check for missing semicolons before closing braces.
-        if missing_semicolon_re.is_match(line) {
-            issues.push(format!("Line {}: Missing semicolon before closing brace", line_number + 1));
+        if let Ok(Some(found)) = missing_semicolon_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "missing-semicolon", "Missing semicolon before closing brace"));
         }
         // Check for duplicate CSS properties.
-        if duplicate_properties_re.is_match(line) {
-            issues.push(format!("Line {}: Duplicate CSS properties detected", line_number + 1));
+        if let Ok(Some(found)) = duplicate_properties_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "duplicate-properties", "Duplicate CSS properties detected"));
         }
         // Check for empty CSS rules.
-        if empty_rule_re.is_match(line) {
-            issues.push(format!("Line {}: Empty CSS rule detected", line_number + 1));
+        if let Ok(Some(found)) = empty_rule_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "empty-rule", "Empty CSS rule detected"));
         }
         // Check for invalid property names.
-        if invalid_property_re.is_match(line) {
-            issues.push(format!("Line {}: Invalid property detected", line_number + 1));
+        if let Ok(Some(found)) = invalid_property_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "invalid-property", "Invalid property detected"));
         }
         // Check for invalid hex color codes.
-        if invalid_hex_color_re.is_match(line) {
-            issues.push(format!("Line {}: Invalid hex color code detected", line_number + 1));
+        if let Ok(Some(found)) = invalid_hex_color_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "invalid-hex-color", "Invalid hex color code detected"));
         }
         // Check for non-standard CSS properties (vendor prefixes).
-        if non_standard_properties_re.is_match(line) {
-            issues.push(format!("Line {}: Non-standard CSS property detected", line_number + 1));
+        if let Ok(Some(found)) = non_standard_properties_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "vendor-prefix", "Non-standard CSS property detected"));
         }
         // Check for CSS hacks.
-        if css_hacks_re.is_match(line) {
-            issues.push(format!("Line {}: CSS hack detected", line_number + 1));
+        if let Ok(Some(found)) = css_hacks_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "css-hack", "CSS hack detected"));
         }
     }
 
@@ -177,7 +163,7 @@ fn check_css(content: &str) -> Vec<String> {
 }
 
 /// Check JavaScript for common issues.
-fn check_js(content: &str) -> Vec<String> {
+fn check_js(content: &str) -> Vec<Diagnostic> {
     let mut issues = Vec::new();
 
     // Regex to detect missing semicolons.
@@ -204,32 +190,32 @@ fn check_js(content: &str) -> Vec<String> {
     // Iterate through each line of the JavaScript content.
     for (line_number, line) in content.lines().enumerate() {
         // Check for missing semicolons.
-        if missing_semicolon_re.is_match(line) {
-            issues.push(format!("Line {}: Missing semicolon", line_number + 1));
+        if let Ok(Some(found)) = missing_semicolon_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "missing-semicolon", "Missing semicolon"));
         }
         // Check for console.log statements.
-        if console_log_re.is_match(line) {
-            issues.push(format!("Line {}: console.log() detected", line_number + 1));
+        if let Ok(Some(found)) = console_log_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "console-log", "console.log() detected"));
         }
         // Check for unused variables.
-        if unused_variable_re.is_match(line) {
-            issues.push(format!("Line {}: Potential unused variable", line_number + 1));
+        if let Ok(Some(found)) = unused_variable_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "unused-variable", "Potential unused variable"));
         }
         // Check for eval usage.
-        if eval_re.is_match(line) {
-            issues.push(format!("Line {}: Use of eval() detected", line_number + 1));
+        if let Ok(Some(found)) = eval_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "eval", "Use of eval() detected"));
         }
         // Check for var usage instead of let/const.
-        if var_usage_re.is_match(line) {
-            issues.push(format!("Line {}: Usage of 'var' instead of 'let' or 'const'", line_number + 1));
+        if let Ok(Some(found)) = var_usage_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "no-var", "Usage of 'var' instead of 'let' or 'const'"));
         }
         // Check for anonymous functions.
-        if anonymous_function_re.is_match(line) {
-            issues.push(format!("Line {}: Anonymous function detected", line_number + 1));
+        if let Ok(Some(found)) = anonymous_function_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "anonymous-function", "Anonymous function detected"));
         }
         // Check for uninitialized const variables.
-        if uninitialized_const_re.is_match(line) {
-            issues.push(format!("Line {}: Const variable declared but not initialized", line_number + 1));
+        if let Ok(Some(found)) = uninitialized_const_re.find(line) {
+            issues.push(issue(line_number, line, found.start(), found.end(), "uninitialized-const", "Const variable declared but not initialized"));
         }
     }
 
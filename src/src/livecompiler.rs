@@ -1,17 +1,23 @@
 use warp::Filter;
 use serde::{Deserialize, Serialize};
-use swc_common::{FileName, SourceMap, Globals};
-use swc_ecmascript::parser::{Syntax, TsConfig, Parser};
-use swc_ecmascript::transforms::{resolver::Resolver, typescript::TsTransform, react::React};
-use swc_ecmascript::visit::VisitMut;
-use swc_ecmascript::codegen::{Emitter, CodeGenerator};
+use swc_common::collections::AHashMap;
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, Globals, Mark, SourceMap, Spanned, GLOBALS};
+use swc_ecmascript::ast::{EsVersion, Expr, Program};
+use swc_ecmascript::codegen::text_writer::JsWriter;
+use swc_ecmascript::codegen::{self, Emitter};
+use swc_ecmascript::parser::{parse_file_as_expr, parse_file_as_program, EsSyntax, Syntax, TsSyntax};
+use swc_ecmascript::transforms::optimization::inline_globals;
+use swc_ecmascript::transforms::{fixer, react, resolver, typescript};
+use swc_ecmascript::visit::FoldWith;
 use thiserror::Error;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::io::Write;
-use std::fmt;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::net::SocketAddr;
+use log::info;
+
+use crate::diagnostic::{Diagnostic, Span};
 
 #[derive(Deserialize)]
 struct CompileRequest {
@@ -30,41 +36,43 @@ struct CompileRequest {
 #[derive(Serialize)]
 struct CompileResponse {
     code: String,
-    errors: Vec<String>,
+    errors: Vec<Diagnostic>,
     source_map: Option<String>,
 }
 
 #[derive(Error, Debug)]
 enum CompileError {
     #[error("Parsing error: {0}")]
-    ParseError(String),
+    Parse(String, Option<Span>),
     #[error("Code generation error: {0}")]
-    CodegenError(String),
+    Codegen(String),
     #[error("Custom error: {0}")]
-    CustomError(String),
+    Custom(String),
     #[error("Source map generation error: {0}")]
-    SourceMapError(String),
+    SourceMap(String),
     #[error("Global configuration error: {0}")]
-    GlobalError(String),
+    Global(String),
 }
 
-impl fmt::Display for CompileError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
+async fn compile(req: CompileRequest) -> Result<impl warp::Reply, warp::Rejection> {
+    let response = match compile_request(req) {
+        Ok((code, source_map)) => CompileResponse { code, errors: vec![], source_map },
+        Err(e) => CompileResponse { code: "".into(), errors: vec![e.into()], source_map: None },
+    };
+    Ok(warp::reply::json(&response))
 }
 
-async fn compile(req: CompileRequest) -> Result<impl warp::Reply, warp::Rejection> {
-    let cm = SourceMap::new();
-    let globals = Arc::new(Mutex::new(Globals::default()));
-    
+fn compile_request(req: CompileRequest) -> Result<(String, Option<String>), CompileError> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let comments = SingleThreadedComments::default();
+
     // Combine code from files or direct code input
     let code = if let Some(code) = req.code {
         HashMap::from([("input.js".to_string(), code)])
     } else if let Some(files) = req.files {
         files
     } else {
-        return Ok(warp::reply::json(&CompileResponse { code: "".into(), errors: vec!["No code or files provided".to_string()], source_map: None }));
+        return Err(CompileError::Custom("No code or files provided".to_string()));
     };
 
     // Concatenate all code if bundling is enabled
@@ -75,121 +83,175 @@ async fn compile(req: CompileRequest) -> Result<impl warp::Reply, warp::Rejectio
     };
 
     // Determine syntax based on request
+    let react_enabled = req.react.unwrap_or(false);
     let syntax = match req.syntax.as_deref() {
-        Some("ts") => Syntax::Typescript(TsConfig::default()),
-        _ => Syntax::Es(Default::default()),
-    };
-
-    // Parse the module
-    let fm = cm.new_source_file(FileName::Custom("input.js".into()), concatenated_code);
-    let parser = Parser::new(syntax, TsConfig::default(), fm);
-    let module = match parser.parse_module() {
-        Ok(module) => module,
-        Err(e) => return Ok(warp::reply::json(&CompileResponse { code: "".into(), errors: vec![format!("Parse error: {:?}", e)], source_map: None })),
+        Some("ts") => Syntax::Typescript(TsSyntax { tsx: react_enabled, ..Default::default() }),
+        _ => Syntax::Es(EsSyntax { jsx: react_enabled, ..Default::default() }),
     };
 
-    let mut module = module;
-    let mut resolver = Resolver::default();
-    resolver.visit_mut_module(&mut module);
+    // Parse the program
+    let fm = cm.new_source_file(Lrc::new(FileName::Custom("input.js".into())), concatenated_code);
+    let program = parse_file_as_program(&fm, syntax, EsVersion::latest(), Some(&comments), &mut vec![])
+        .map_err(|e| {
+            let lo = cm.lookup_char_pos(e.span().lo);
+            let hi = cm.lookup_char_pos(e.span().hi);
+            let span = Span::new(lo.line, lo.col.0 + 1, hi.line, hi.col.0 + 1);
+            CompileError::Parse(e.kind().msg().to_string(), Some(span))
+        })?;
 
-    // Apply React and TypeScript transformations if requested
-    if req.react.unwrap_or(false) {
-        let react_transform = React::default();
-        react_transform.visit_mut_module(&mut module);
+    // Apply custom transformations based on extra options
+    if let Some(extra_options) = &req.extra_options {
+        apply_custom_transformations(extra_options, &program)?;
     }
 
-    if req.ts.unwrap_or(false) {
-        let ts_transform = TsTransform::default();
-        ts_transform.visit_mut_module(&mut module);
-    }
+    let program = GLOBALS.set(&Globals::new(), || -> Result<Program, CompileError> {
+        let unresolved_mark = Mark::new();
+        let top_level_mark = Mark::new();
+        let is_ts = req.ts.unwrap_or(false) || req.syntax.as_deref() == Some("ts");
 
-    // Apply custom transformations based on extra options
-    if let Some(extra_options) = req.extra_options {
-        apply_custom_transformations(&extra_options, &mut module)?;
-    }
+        let mut program = program.fold_with(&mut resolver(unresolved_mark, top_level_mark, is_ts));
 
-    // Handle global configurations if provided
-    if let Some(globals_config) = req.globals {
-        apply_globals(&globals_config, &globals)?;
-    }
+        // Apply React and TypeScript transformations if requested
+        if is_ts {
+            program = program.fold_with(&mut typescript::strip(unresolved_mark, top_level_mark));
+        }
+
+        if react_enabled {
+            program = program.fold_with(&mut react::react(
+                cm.clone(),
+                Some(&comments),
+                Default::default(),
+                top_level_mark,
+                unresolved_mark,
+            ));
+        }
+
+        // Handle global configurations if provided
+        if let Some(globals_config) = &req.globals {
+            program = apply_globals(globals_config, program)?;
+        }
+
+        Ok(program.fold_with(&mut fixer(Some(&comments))))
+    })?;
 
     // Configure the emitter for code generation
     let minify = req.minify.unwrap_or(false);
-    let mut emitter = Emitter {
-        cfg: swc_ecmascript::codegen::Config {
-            minify,
-            ..Default::default()
-        },
-        cm: cm.clone(),
-        comments: None,
-    };
-
-    // Generate the compiled code
     let mut buf = Vec::new();
-    let code = match emitter.emit_module(&module, &mut buf) {
-        Ok(_) => String::from_utf8(buf).unwrap_or_default(),
-        Err(e) => return Ok(warp::reply::json(&CompileResponse { code: "".into(), errors: vec![format!("Code generation error: {:?}", e)], source_map: None })),
-    };
+    let mut mappings = Vec::new();
+    {
+        let mut emitter = Emitter {
+            cfg: codegen::Config::default().with_minify(minify),
+            cm: cm.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut mappings)),
+        };
+
+        // Generate the compiled code
+        emitter
+            .emit_program(&program)
+            .map_err(|e| CompileError::Codegen(e.to_string()))?;
+    }
+    let code = String::from_utf8(buf).map_err(|e| CompileError::Codegen(e.to_string()))?;
 
     // Generate source maps if requested
     let source_map = if req.source_map.unwrap_or(false) {
-        match generate_source_map(&concatenated_code) {
-            Ok(map) => Some(map),
-            Err(e) => return Ok(warp::reply::json(&CompileResponse { code, errors: vec![format!("Source map generation error: {:?}", e)], source_map: None })),
-        }
+        Some(generate_source_map(&cm, &mappings)?)
     } else {
         None
     };
 
-    Ok(warp::reply::json(&CompileResponse { code, errors: vec![], source_map }))
+    Ok((code, source_map))
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(err: CompileError) -> Self {
+        let code = match &err {
+            CompileError::Parse(..) => "compile/parse",
+            CompileError::Codegen(_) => "compile/codegen",
+            CompileError::Custom(_) => "compile/options",
+            CompileError::SourceMap(_) => "compile/sourcemap",
+            CompileError::Global(_) => "compile/globals",
+        };
+        let diagnostic = Diagnostic::error(code, err.to_string()).with_file("input.js");
+        match err {
+            CompileError::Parse(_, Some(span)) => diagnostic.with_span(span),
+            _ => diagnostic,
+        }
+    }
 }
 
-fn apply_custom_transformations(extra_options: &HashMap<String, Value>, module: &mut swc_ecmascript::ast::Module) -> Result<(), CompileError> {
+fn apply_custom_transformations(extra_options: &HashMap<String, Value>, _program: &Program) -> Result<(), CompileError> {
     for (key, value) in extra_options {
         match key.as_str() {
             "minify" => {
                 if let Value::Bool(enable) = value {
                     // Configure minification if necessary
-                    println!("Custom minify option: {}", enable);
+                    info!("Custom minify option: {}", enable);
                 }
             },
             "custom_plugin" => {
                 if let Value::String(plugin) = value {
                     // Apply custom plugin logic
-                    println!("Applying custom plugin: {}", plugin);
+                    info!("Applying custom plugin: {}", plugin);
                 }
             },
-            _ => return Err(CompileError::CustomError(format!("Unknown custom option: {}", key))),
+            _ => return Err(CompileError::Custom(format!("Unknown custom option: {}", key))),
         }
     }
 
     Ok(())
 }
 
-fn apply_globals(globals_config: &HashMap<String, String>, globals: &Arc<Mutex<Globals>>) -> Result<(), CompileError> {
-    let mut globals = globals.lock().await;
+/// Replaces every reference to a configured global identifier with its value,
+/// which is parsed as a JavaScript expression (e.g. `"\"1.0.0\""` or `"false"`).
+fn apply_globals(globals_config: &HashMap<String, String>, program: Program) -> Result<Program, CompileError> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let mut globals: AHashMap<_, Expr> = AHashMap::default();
+
     for (key, value) in globals_config {
-        // Apply global configurations here
-        println!("Setting global {} to {}", key, value);
-        globals.set(key.clone(), value.clone());
+        info!("Setting global {} to {}", key, value);
+        let fm = cm.new_source_file(Lrc::new(FileName::Custom(key.clone())), value.clone());
+        let expr = parse_file_as_expr(&fm, Syntax::default(), EsVersion::latest(), None, &mut vec![])
+            .map_err(|e| CompileError::Global(format!("{}: {:?}", key, e.kind())))?;
+        globals.insert(key.as_str().into(), *expr);
     }
 
-    Ok(())
+    Ok(program.fold_with(&mut inline_globals(
+        Default::default(),
+        Lrc::new(globals),
+        Default::default(),
+    )))
 }
 
-fn generate_source_map(code: &str) -> Result<String, CompileError> {
-    // Generate source map for the provided code
-    // Placeholder logic for demonstration purposes
-    let source_map = format!("Source map for code: {}", code);
-    Ok(source_map)
+fn generate_source_map(
+    cm: &Lrc<SourceMap>,
+    mappings: &[(swc_common::BytePos, swc_common::source_map::LineCol)],
+) -> Result<String, CompileError> {
+    let mut source_map = Vec::new();
+    cm.build_source_map(mappings)
+        .to_writer(&mut source_map)
+        .map_err(|e| CompileError::SourceMap(e.to_string()))?;
+    String::from_utf8(source_map).map_err(|e| CompileError::SourceMap(e.to_string()))
 }
 
-#[tokio::main]
-async fn main() {
-    let compile = warp::post()
-        .and(warp::path("compile"))
-        .and(warp::body::json())
-        .and_then(compile);
+/// Serves `POST /compile` on `host:port` until the process is stopped.
+pub fn serve(host: &str, port: u16) -> Result<(), String> {
+    let addr: SocketAddr = format!("{}:{}", host, port)
+        .parse()
+        .map_err(|e| format!("Invalid address {}:{}: {}", host, port, e))?;
+
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to start async runtime: {}", e))?;
 
-    warp::serve(compile).run(([127, 0, 0, 1], 3030)).await;
-}
\ No newline at end of file
+    runtime.block_on(async {
+        let compile = warp::post()
+            .and(warp::path("compile"))
+            .and(warp::body::json())
+            .and_then(compile);
+
+        info!("Live compiler listening on http://{}/compile", addr);
+        warp::serve(compile).run(addr).await;
+    });
+
+    Ok(())
+}
@@ -0,0 +1,291 @@
+//! Turns imported files that are not JavaScript, such as `logo.svg` or `data.yaml`, into
+//! JavaScript modules. The loader is picked by the file's extension; files without one are
+//! bundled as they are.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Span};
+
+/// The built-in loaders: `js` bundles a file as it is, `text`, `base64` and `dataurl` export its
+/// contents as a string, `file` copies it next to the bundle and exports its URL, and `json`,
+/// `yaml` and `toml` export the parsed value.
+pub const BUILTIN_LOADERS: [&str; 8] = ["js", "text", "base64", "dataurl", "file", "json", "yaml", "toml"];
+
+/// Loaders used for these extensions unless a plugin or the config picks another.
+pub const DEFAULT_LOADERS: [(&str, &str); 19] = [
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("txt", "text"),
+    ("md", "text"),
+    ("graphql", "text"),
+    ("gql", "text"),
+    ("svg", "file"),
+    ("png", "file"),
+    ("jpg", "file"),
+    ("jpeg", "file"),
+    ("gif", "file"),
+    ("webp", "file"),
+    ("avif", "file"),
+    ("ico", "file"),
+    ("woff", "file"),
+    ("woff2", "file"),
+    ("ttf", "file"),
+];
+
+/// A module produced by a loader.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Loaded {
+    pub code: String,
+    /// Where the file is copied to, for the `file` loader.
+    pub asset: Option<PathBuf>,
+}
+
+impl Loaded {
+    fn code(code: String) -> Self {
+        Self { code, asset: None }
+    }
+}
+
+/// Turns a file's contents into a JavaScript module. Plugins add their own through
+/// [`crate::Plugin::loaders`].
+pub trait Loader: Send + Sync {
+    /// How `loaders` in the config refers to it.
+    fn name(&self) -> &str;
+
+    /// Extensions, without the dot, it loads unless the config picks another loader.
+    fn extensions(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn load(&self, file_path: &str, contents: &[u8]) -> Result<Loaded, Diagnostic>;
+}
+
+/// One of [`BUILTIN_LOADERS`].
+pub struct BuiltinLoader {
+    name: &'static str,
+    /// Where the `file` loader copies files to.
+    output_dir: PathBuf,
+    extensions: Vec<String>,
+}
+
+impl BuiltinLoader {
+    pub fn new(name: &'static str, output_dir: &Path) -> Self {
+        Self { name, output_dir: output_dir.to_path_buf(), extensions: vec![] }
+    }
+
+    /// Also claims `extensions`, for plugins that add the loader to more files.
+    pub fn with_extensions(mut self, extensions: &[String]) -> Self {
+        self.extensions = extensions.to_vec();
+        self
+    }
+}
+
+impl Loader for BuiltinLoader {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn extensions(&self) -> Vec<String> {
+        self.extensions.clone()
+    }
+
+    fn load(&self, file_path: &str, contents: &[u8]) -> Result<Loaded, Diagnostic> {
+        let text = || {
+            std::str::from_utf8(contents).map_err(|err| {
+                Diagnostic::error("loader/utf8", format!("The `{}` loader needs UTF-8 text: {}", self.name, err))
+            })
+        };
+        match self.name {
+            "js" => Ok(Loaded::code(text()?.to_string())),
+            "text" => Ok(Loaded::code(default_export(&Value::from(text()?)))),
+            "base64" => Ok(Loaded::code(default_export(&Value::from(STANDARD.encode(contents))))),
+            "dataurl" => {
+                let url = format!("data:{};base64,{}", mime_type(file_path), STANDARD.encode(contents));
+                Ok(Loaded::code(default_export(&Value::from(url))))
+            }
+            "file" => {
+                let name = hashed_name(Path::new(file_path), contents);
+                Ok(Loaded {
+                    code: default_export(&Value::from(format!("./{}", name))),
+                    asset: Some(self.output_dir.join(name)),
+                })
+            }
+            "json" => json_module(text()?).map(Loaded::code),
+            "yaml" => {
+                let value: Value = serde_yaml::from_str(text()?).map_err(|err| {
+                    let diagnostic = Diagnostic::error("loader/yaml", format!("Invalid YAML: {}", err));
+                    match err.location() {
+                        Some(location) => diagnostic.with_span(Span::new(
+                            location.line(),
+                            location.column(),
+                            location.line(),
+                            location.column() + 1,
+                        )),
+                        None => diagnostic,
+                    }
+                })?;
+                Ok(Loaded::code(default_export(&value)))
+            }
+            "toml" => {
+                let source = text()?;
+                let value: toml::Value = toml::from_str(source).map_err(|err| {
+                    let diagnostic = Diagnostic::error("loader/toml", format!("Invalid TOML: {}", err.message()));
+                    match err.span() {
+                        Some(span) => diagnostic.with_span(Span::from_offsets(source, span.start, span.end)),
+                        None => diagnostic,
+                    }
+                })?;
+                Ok(Loaded::code(default_export(&toml_to_json(value))))
+            }
+            name => Err(Diagnostic::error("loader/unknown", format!("Unknown loader `{}`", name))),
+        }
+    }
+}
+
+/// Parses JSON into a module whose default export is the value, pointing errors at their line.
+pub fn json_module(source: &str) -> Result<String, Diagnostic> {
+    let value: Value = serde_json::from_str(source).map_err(|err| {
+        let (line, column) = (err.line(), err.column().max(1));
+        Diagnostic::error("loader/json", format!("Invalid JSON: {}", err)).with_span(Span::new(line, column, line, column + 1))
+    })?;
+    Ok(default_export(&value))
+}
+
+/// A module whose default export is `value`.
+pub fn default_export(value: &Value) -> String {
+    format!("export default {};\n", value)
+}
+
+/// `logo.svg` as `logo-<hash>.svg`, so a changed file gets a new URL.
+fn hashed_name(path: &Path, contents: &[u8]) -> String {
+    let hash: String = Sha256::digest(contents).iter().take(4).map(|byte| format!("{:02x}", byte)).collect();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, hash, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, hash),
+    }
+}
+
+fn mime_type(file_path: &str) -> &'static str {
+    match extension(file_path).as_str() {
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "txt" | "md" | "graphql" | "gql" => "text/plain",
+        "json" => "application/json",
+        "css" => "text/css",
+        "html" => "text/html",
+        _ => "application/octet-stream",
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(string) => Value::from(string),
+        toml::Value::Integer(integer) => Value::from(integer),
+        toml::Value::Float(float) => Value::from(float),
+        toml::Value::Boolean(boolean) => Value::from(boolean),
+        toml::Value::Datetime(datetime) => Value::from(datetime.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()),
+    }
+}
+
+/// A file's extension, lowercased and without the dot.
+fn extension(file_path: &str) -> String {
+    Path::new(file_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Which loader handles each extension.
+pub struct LoaderRegistry {
+    loaders: HashMap<String, Arc<dyn Loader>>,
+    /// Loader names by extension.
+    extensions: HashMap<String, String>,
+}
+
+impl LoaderRegistry {
+    /// The built-in loaders and `plugin_loaders`, with extensions picked from, in increasing
+    /// precedence, [`DEFAULT_LOADERS`], the plugins and `config.loaders`.
+    pub fn new(config: &Config, plugin_loaders: Vec<Arc<dyn Loader>>) -> Self {
+        let output_dir = Path::new(config.output_pattern()).parent().unwrap_or(Path::new(""));
+        let mut loaders: HashMap<String, Arc<dyn Loader>> = HashMap::new();
+        for name in BUILTIN_LOADERS {
+            loaders.insert(name.to_string(), Arc::new(BuiltinLoader::new(name, output_dir)));
+        }
+        let mut extensions: HashMap<String, String> =
+            DEFAULT_LOADERS.iter().map(|(extension, name)| (extension.to_string(), name.to_string())).collect();
+        for loader in plugin_loaders {
+            for extension in loader.extensions() {
+                extensions.insert(extension.trim_start_matches('.').to_lowercase(), loader.name().to_string());
+            }
+            loaders.insert(loader.name().to_string(), loader);
+        }
+        for (extension, name) in &config.loaders {
+            extensions.insert(extension.trim_start_matches('.').to_lowercase(), name.clone());
+        }
+        Self { loaders, extensions }
+    }
+
+    /// The loader for `file_path`, or `None` if it is bundled as it is.
+    pub fn for_path(&self, file_path: &str) -> Result<Option<&dyn Loader>, Diagnostic> {
+        let Some(name) = self.extensions.get(&extension(file_path)) else {
+            return Ok(None);
+        };
+        if name == "js" {
+            return Ok(None);
+        }
+        match self.loaders.get(name) {
+            Some(loader) => Ok(Some(loader.as_ref())),
+            None => Err(Diagnostic::error("loader/unknown", format!("Unknown loader `{}`", name))
+                .with_file(file_path)
+                .with_note(format!("loaders are {} or one added by a plugin", BUILTIN_LOADERS.join(", ")))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_files_into_modules_by_extension() {
+        let mut config = Config::default();
+        config.loaders.insert(".svg".to_string(), "dataurl".to_string());
+        let registry = LoaderRegistry::new(&config, vec![]);
+        let load = |path: &str, contents: &str| registry.for_path(path).unwrap().unwrap().load(path, contents.as_bytes());
+
+        assert!(registry.for_path("src/a.js").unwrap().is_none());
+        assert_eq!(load("a.txt", "hi \"there\"\n").unwrap().code, "export default \"hi \\\"there\\\"\\n\";\n");
+        assert_eq!(load("a.svg", "<svg/>").unwrap().code, "export default \"data:image/svg+xml;base64,PHN2Zy8+\";\n");
+        assert_eq!(load("a.yaml", "n: 1\nlist: [a]\n").unwrap().code, "export default {\"n\":1,\"list\":[\"a\"]};\n");
+        assert_eq!(load("a.toml", "[server]\nport = 80\n").unwrap().code, "export default {\"server\":{\"port\":80}};\n");
+
+        let logo = load("img/logo.png", "png").unwrap();
+        assert_eq!(logo.asset, Some(PathBuf::from("dist/logo-8f8cbb7d.png")));
+        assert_eq!(logo.code, "export default \"./logo-8f8cbb7d.png\";\n");
+
+        let err = load("a.toml", "a = 1\nb = \n").unwrap_err();
+        assert_eq!(err.span.map(|span| span.line), Some(2));
+    }
+}
@@ -1,46 +1,646 @@
-pub trait Plugin {
-    fn on_resolve(&self, file_path: &str) -> Option<String>;
-    fn on_load(&self, file_path: &str, content: &str) -> Option<String>;
-    fn on_transform(&self, file_path: &str, content: &str) -> Option<String>;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostic::Diagnostic;
+use crate::loader::Loader;
+use crate::resolver::Resolver;
+use crate::sourcemap;
+
+/// The namespace of modules read from disk.
+pub const FILE_NAMESPACE: &str = "file";
+
+/// Every file a build is about to write, keyed by path. `generate_bundle` hooks may add,
+/// change or remove entries.
+pub type OutputBundle = BTreeMap<PathBuf, Vec<u8>>;
+
+/// A bundle being rendered for one entry.
+#[derive(Debug, Clone)]
+pub struct RenderedChunk {
+    pub entry: String,
+    pub path: PathBuf,
+    /// The modules concatenated into the chunk, in order.
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Update,
+    Delete,
+}
+
+/// When a plugin runs relative to the others, like Vite's `enforce`. Plugins with the same
+/// ordering run in the order they were registered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Enforce {
+    Pre,
+    #[default]
+    Normal,
+    Post,
+}
+
+/// A module's code after a transform, with a source map back to the transform's input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransformResult {
+    pub code: String,
+    /// The map as JSON. `None` means every line stayed where it was.
+    pub map: Option<String>,
+}
+
+/// How a module refers to what it resolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolveKind {
+    /// `import ... from "x"`
+    Import,
+    /// `require("x")`
+    Require,
+    /// `import("x")`
+    DynamicImport,
+    /// `url(x)` in CSS
+    CssUrl,
+}
+
+/// What a resolve hook is asked to resolve.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolveArgs<'a> {
+    /// The specifier as written, e.g. `./math` or `react`.
+    pub path: &'a str,
+    /// The module the specifier appears in.
+    pub importer: &'a str,
+    /// The importer's namespace, [`FILE_NAMESPACE`] for modules on disk.
+    pub namespace: &'a str,
+    pub kind: ResolveKind,
+    /// Resolves specifiers on disk as the build does, e.g. to what a plugin rewrote them to.
+    pub resolver: &'a Resolver,
+}
+
+/// Where a resolve hook sends an import.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ResolveResult {
+    pub path: String,
+    /// `None` keeps the module in [`FILE_NAMESPACE`]; other namespaces are not read from disk
+    /// and prefix the module's id, e.g. `mock:api`.
+    pub namespace: Option<String>,
+}
+
+/// Whether `id` names a module only plugins can load rather than a file: ids starting with
+/// `\0`, like Rollup's, and ids in a namespace other than [`FILE_NAMESPACE`], such as
+/// `virtual:routes`.
+pub fn is_virtual(id: &str) -> bool {
+    id.starts_with('\0') || split_module_id(id).0 != FILE_NAMESPACE
+}
+
+/// `id` as shown in messages and output comments, with a leading `\0` spelled out.
+pub fn display_id(id: &str) -> Cow<'_, str> {
+    match id.strip_prefix('\0') {
+        Some(rest) => Cow::Owned(format!("\\0{}", rest)),
+        None => Cow::Borrowed(id),
+    }
+}
+
+/// Limits which imports a plugin's resolve hook sees, like esbuild's `{ filter, namespace }`:
+/// the specifier must match `filter` and the importer must be in `namespace`, if given.
+#[derive(Debug, Clone)]
+pub struct PluginFilter {
+    pub filter: Regex,
+    pub namespace: Option<String>,
+}
+
+impl PluginFilter {
+    pub fn new(filter: &str) -> Result<Self, regex::Error> {
+        Ok(Self { filter: Regex::new(filter)?, namespace: None })
+    }
+
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    pub fn matches(&self, path: &str, namespace: &str) -> bool {
+        self.namespace.as_deref().is_none_or(|expected| expected == namespace) && self.filter.is_match(path)
+    }
+}
+
+/// The id of the module `path` in `namespace`; modules on disk are identified by their path.
+pub fn module_id(namespace: &str, path: &str) -> String {
+    if namespace == FILE_NAMESPACE {
+        path.to_string()
+    } else {
+        format!("{}:{}", namespace, path)
+    }
+}
+
+/// Splits a module id into its namespace and path.
+pub fn split_module_id(id: &str) -> (&str, &str) {
+    match id.split_once(':') {
+        // A single letter is a Windows drive, not a namespace.
+        Some((namespace, path))
+            if namespace.len() > 1 && namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            (namespace, path)
+        }
+        _ => (FILE_NAMESPACE, id),
+    }
+}
+
+/// What a resolve, load, transform or render hook returns: `Ok(None)` leaves the work to the
+/// next plugin and `Err` fails the module or chunk. The manager adds the plugin's name to the
+/// error, and the module to errors without a file.
+pub type HookResult<T> = Result<Option<T>, Diagnostic>;
+
+/// Handed to every hook, and collects what plugins ask of the build.
+#[derive(Debug, Default)]
+pub struct PluginContext {
+    plugin: String,
+    /// The module the current hook is working on, which warnings without a file point to.
+    module: Option<String>,
+    emitted: OutputBundle,
+    watch_files: Vec<PathBuf>,
+    warnings: Vec<Diagnostic>,
+    errors: Vec<Diagnostic>,
+}
+
+impl PluginContext {
+    /// Writes `contents` to `path`, relative to the project root, alongside the bundles.
+    pub fn emit_file(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.emitted.insert(path.into(), contents.into());
+    }
+
+    /// Rebuilds in watch mode whenever `path` changes, even though no module imports it.
+    ///
+    /// From [`Plugin::load_module`] and [`Plugin::transform_module`], `path` becomes a dependency
+    /// of the module, such as a config file the plugin read: its cached result is only reused
+    /// while the file is unchanged, and a change rebuilds just the modules depending on it.
+    pub fn add_watch_file(&mut self, path: impl Into<PathBuf>) {
+        self.watch_files.push(path.into());
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.add_warning(Diagnostic::warning("plugin/warning", message));
+    }
+
+    /// Reports a warning with its own code, and a location if it has one.
+    pub fn add_warning(&mut self, warning: Diagnostic) {
+        let warning = self.attribute(warning);
+        self.warnings.push(warning);
+    }
+
+    pub fn take_emitted(&mut self) -> OutputBundle {
+        mem::take(&mut self.emitted)
+    }
+
+    pub fn take_watch_files(&mut self) -> Vec<PathBuf> {
+        mem::take(&mut self.watch_files)
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Errors returned by lifecycle hooks, which do not fail any one module.
+    pub fn take_errors(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.errors)
+    }
+
+    fn enter(&mut self, plugin: &dyn Plugin) -> &mut Self {
+        self.plugin = plugin.name().to_string();
+        self.module = None;
+        self
+    }
+
+    fn enter_module(&mut self, plugin: &dyn Plugin, module: &str) -> &mut Self {
+        self.plugin = plugin.name().to_string();
+        self.module = Some(module.to_string());
+        self
+    }
+
+    /// Names the current plugin in `diagnostic`, and points it at the current module if it
+    /// has no file.
+    fn attribute(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        diagnostic.plugin.get_or_insert_with(|| self.plugin.clone());
+        if let (None, Some(module)) = (&diagnostic.file, &self.module) {
+            diagnostic.file = Some(PathBuf::from(display_id(module).as_ref()));
+        }
+        diagnostic
+    }
+
+    fn fail(&mut self, error: Diagnostic) {
+        let error = self.attribute(error);
+        self.errors.push(error);
+    }
 }
 
+pub trait Plugin: Send + Sync {
+    /// Identifies the plugin in cache fingerprints and messages.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Changes whenever the plugin could load or transform the same module differently, e.g.
+    /// its version and options, so cached modules are processed again. `None` means only the
+    /// plugin's name matters.
+    fn cache_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Resolves a bare specifier. Prefer [`Plugin::resolve_import`], which also sees the importer.
+    fn on_resolve(&self, _file_path: &str) -> Option<String> {
+        None
+    }
+
+    /// Limits the imports [`Plugin::resolve_import`] is called for; `None` means every import.
+    /// Queried once, when the plugin is registered.
+    fn resolve_filter(&self) -> Option<PluginFilter> {
+        None
+    }
+
+    /// Resolves an import. Defaults to [`Plugin::on_resolve`] with the specifier.
+    fn resolve_import(&self, _ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+        Ok(self.on_resolve(args.path).map(|path| ResolveResult { path, namespace: None }))
+    }
+
+    /// Loaders the plugin adds for files that are not JavaScript, picked by extension like the
+    /// built-in ones. Queried once, when the bundler is created.
+    fn loaders(&self) -> Vec<Arc<dyn Loader>> {
+        vec![]
+    }
+
+    /// Runs this plugin's hooks before or after the others.
+    fn enforce(&self) -> Enforce {
+        Enforce::Normal
+    }
+
+    /// Loads a module. Prefer [`Plugin::load_module`] to also report failures.
+    fn on_load(&self, _file_path: &str, _content: &str) -> Option<String> {
+        None
+    }
+
+    /// Loads a module. `content` is the file on disk, or empty for a virtual module (see
+    /// [`is_virtual`]), which fails to load unless a plugin returns its code. Defaults to
+    /// [`Plugin::on_load`].
+    fn load_module(&self, _ctx: &mut PluginContext, file_path: &str, content: &str) -> HookResult<String> {
+        Ok(self.on_load(file_path, content))
+    }
+
+    /// Rewrites a module's code. Prefer [`Plugin::transform_module`] to also return a source map.
+    fn on_transform(&self, _file_path: &str, _content: &str) -> Option<String> {
+        None
+    }
+
+    /// Rewrites a module's code, given the previous plugin's output. Defaults to
+    /// [`Plugin::on_transform`] without a source map.
+    fn transform_module(&self, _ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+        Ok(self.on_transform(file_path, code).map(|code| TransformResult { code, map: None }))
+    }
+
+    /// Called before any module is processed. An error fails the build.
+    fn build_start(&self, _ctx: &mut PluginContext) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+
+    /// Called once the build has finished or failed with `errors`.
+    fn build_end(&self, _ctx: &mut PluginContext, _errors: &[Diagnostic]) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+
+    /// Rewrites the code of a chunk before it is minified; `None` keeps it as is.
+    fn render_chunk(&self, _ctx: &mut PluginContext, _chunk: &RenderedChunk, _code: &str) -> HookResult<String> {
+        Ok(None)
+    }
+
+    /// Called with every file about to be written, which the plugin may add to or remove from.
+    fn generate_bundle(&self, _ctx: &mut PluginContext, _bundle: &mut OutputBundle) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+
+    /// Called in watch mode for each changed file, before the rebuild it triggers.
+    fn watch_change(&self, _ctx: &mut PluginContext, _path: &Path, _kind: ChangeKind) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
 pub struct PluginManager {
     plugins: Vec<Box<dyn Plugin>>,
+    /// Each plugin's resolve filter, by index.
+    resolve_filters: Vec<Option<PluginFilter>>,
 }
 
 impl PluginManager {
     pub fn new() -> Self {
-        Self { plugins: vec![] }
+        Self::default()
     }
 
+    /// Adds a plugin after every plugin that runs before or with it.
     pub fn register(&mut self, plugin: Box<dyn Plugin>) {
-        self.plugins.push(plugin);
+        let index = self.plugins.partition_point(|registered| registered.enforce() <= plugin.enforce());
+        self.resolve_filters.insert(index, plugin.resolve_filter());
+        self.plugins.insert(index, plugin);
+    }
+
+    /// The registered plugins and their cache keys, in order; part of the transform cache key.
+    pub fn fingerprint(&self) -> String {
+        self.plugins
+            .iter()
+            .map(|plugin| match plugin.cache_key() {
+                Some(key) => format!("{}:{}", plugin.name(), key),
+                None => plugin.name().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Every plugin's loaders, in order.
+    pub fn loaders(&self) -> Vec<Arc<dyn Loader>> {
+        self.plugins.iter().flat_map(|plugin| plugin.loaders()).collect()
+    }
+
+    /// Asks each plugin whose filter matches, in order, until one resolves the import. The
+    /// first error stops the search.
+    pub fn resolve(&self, ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+        for (plugin, filter) in self.plugins.iter().zip(&self.resolve_filters) {
+            if filter.as_ref().is_some_and(|filter| !filter.matches(args.path, args.namespace)) {
+                continue;
+            }
+            let ctx = ctx.enter_module(plugin.as_ref(), args.importer);
+            match plugin.resolve_import(ctx, args) {
+                Ok(None) => {}
+                Ok(Some(result)) => return Ok(Some(result)),
+                Err(error) => return Err(ctx.attribute(error)),
+            }
+        }
+        Ok(None)
     }
 
-    pub fn resolve(&self, file_path: &str) -> Option<String> {
+    /// Asks each plugin in turn until one loads the module.
+    pub fn load(&self, ctx: &mut PluginContext, file_path: &str, content: &str) -> HookResult<String> {
         for plugin in &self.plugins {
-            if let Some(new_path) = plugin.on_resolve(file_path) {
-                return Some(new_path);
+            let ctx = ctx.enter_module(plugin.as_ref(), file_path);
+            match plugin.load_module(ctx, file_path, content) {
+                Ok(None) => {}
+                Ok(Some(new_content)) => return Ok(Some(new_content)),
+                Err(error) => return Err(ctx.attribute(error)),
             }
         }
-        None
+        Ok(None)
     }
 
-    pub fn load(&self, file_path: &str, content: &str) -> Option<String> {
+    /// Passes the module's code through every plugin in turn, composing their source maps into
+    /// one that maps the final code back to `content`. A plugin that changes the code without a
+    /// map drops the maps before it, which no longer match the code, with a warning.
+    pub fn transform(&self, ctx: &mut PluginContext, file_path: &str, content: String) -> Result<TransformResult, Diagnostic> {
+        let mut result = TransformResult { code: content, map: None };
         for plugin in &self.plugins {
-            if let Some(new_content) = plugin.on_load(file_path, content) {
-                return Some(new_content);
+            let ctx = ctx.enter_module(plugin.as_ref(), file_path);
+            let Some(step) = plugin.transform_module(ctx, file_path, &result.code).map_err(|error| ctx.attribute(error))? else {
+                continue;
+            };
+            match step.map {
+                Some(map) => {
+                    result.map = Some(sourcemap::compose(&map, result.map.as_deref()).map_err(|err| {
+                        ctx.attribute(Diagnostic::error("plugin/sourcemap", format!("Invalid source map: {}", err)))
+                    })?);
+                }
+                None if result.map.is_some() && step.code != result.code => {
+                    result.map = None;
+                    ctx.add_warning(
+                        Diagnostic::warning("plugin/sourcemap", "Changed the code without a source map")
+                            .with_note("the module's source map is dropped; return one from `transform_module`"),
+                    );
+                }
+                None => {}
             }
+            result.code = step.code;
         }
-        None
+        Ok(result)
     }
 
-    pub fn transform(&self, file_path: &str, content: &str) -> Option<String> {
+    /// Errors from this and the other lifecycle hooks are collected in `ctx`.
+    pub fn build_start(&self, ctx: &mut PluginContext) {
         for plugin in &self.plugins {
-            if let Some(new_content) = plugin.on_transform(file_path, content) {
-                return Some(new_content);
+            if let Err(error) = plugin.build_start(ctx.enter(plugin.as_ref())) {
+                ctx.fail(error);
             }
         }
-        None
     }
-}
\ No newline at end of file
+
+    pub fn build_end(&self, ctx: &mut PluginContext, errors: &[Diagnostic]) {
+        for plugin in &self.plugins {
+            if let Err(error) = plugin.build_end(ctx.enter(plugin.as_ref()), errors) {
+                ctx.fail(error);
+            }
+        }
+    }
+
+    /// Passes the chunk's code through every plugin in turn. A plugin that fails leaves the
+    /// code as it was.
+    pub fn render_chunk(&self, ctx: &mut PluginContext, chunk: &RenderedChunk, mut code: String) -> String {
+        for plugin in &self.plugins {
+            match plugin.render_chunk(ctx.enter(plugin.as_ref()), chunk, &code) {
+                Ok(None) => {}
+                Ok(Some(new_code)) => code = new_code,
+                Err(error) => ctx.fail(error),
+            }
+        }
+        code
+    }
+
+    pub fn generate_bundle(&self, ctx: &mut PluginContext, bundle: &mut OutputBundle) {
+        for plugin in &self.plugins {
+            if let Err(error) = plugin.generate_bundle(ctx.enter(plugin.as_ref()), bundle) {
+                ctx.fail(error);
+            }
+        }
+    }
+
+    pub fn watch_change(&self, ctx: &mut PluginContext, path: &Path, kind: ChangeKind) {
+        for plugin in &self.plugins {
+            if let Err(error) = plugin.watch_change(ctx.enter(plugin.as_ref()), path, kind) {
+                ctx.fail(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ResolveConfig;
+    use crate::diagnostic::Span;
+
+    struct Stamp;
+
+    impl Plugin for Stamp {
+        fn name(&self) -> &str {
+            "stamp"
+        }
+        fn render_chunk(&self, ctx: &mut PluginContext, chunk: &RenderedChunk, code: &str) -> HookResult<String> {
+            ctx.emit_file("dist/entries.txt", chunk.entry.clone());
+            ctx.warn("stamped");
+            Ok(Some(format!("/* {} */\n{}", chunk.entry, code)))
+        }
+    }
+
+    struct Mocks;
+
+    impl Plugin for Mocks {
+        fn resolve_filter(&self) -> Option<PluginFilter> {
+            PluginFilter::new("^@mocks/").ok()
+        }
+        fn resolve_import(&self, _ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+            assert!(args.path.starts_with("@mocks/"), "filtered out: {}", args.path);
+            Ok(Some(ResolveResult { path: format!("{} from {}", args.path, args.importer), namespace: Some("mock".into()) }))
+        }
+    }
+
+    /// Prepends a line, mapping the rest back to where it was.
+    struct Prepend(&'static str, Enforce);
+
+    impl Plugin for Prepend {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn enforce(&self) -> Enforce {
+            self.1
+        }
+        fn transform_module(&self, _ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+            let mut map = ::sourcemap::SourceMapBuilder::new(None);
+            for line in 0..code.lines().count() as u32 {
+                map.add(line + 1, 0, line, 0, Some(file_path), None, false);
+            }
+            Ok(Some(TransformResult {
+                code: format!("// {}\n{}", self.0, code),
+                map: Some(sourcemap::to_json(&map.into_sourcemap())),
+            }))
+        }
+    }
+
+    /// Drops comment lines, without a source map.
+    struct Strip;
+
+    impl Plugin for Strip {
+        fn name(&self) -> &str {
+            "strip"
+        }
+        fn on_transform(&self, _file_path: &str, content: &str) -> Option<String> {
+            Some(content.lines().filter(|line| !line.starts_with("//")).map(|line| format!("{}\n", line)).collect())
+        }
+    }
+
+    /// Warns about `TODO`s and rejects `eval`, at their location.
+    struct Strict;
+
+    impl Plugin for Strict {
+        fn name(&self) -> &str {
+            "strict"
+        }
+        fn cache_key(&self) -> Option<String> {
+            Some("v1".to_string())
+        }
+        fn transform_module(&self, ctx: &mut PluginContext, _file_path: &str, code: &str) -> HookResult<TransformResult> {
+            if let Some(index) = code.find("TODO") {
+                ctx.add_warning(
+                    Diagnostic::warning("strict/todo", "Unfinished code")
+                        .with_span(Span::from_offsets(code, index, index + 4)),
+                );
+            }
+            match code.find("eval(") {
+                Some(index) => Err(Diagnostic::error("strict/eval", "`eval` is not allowed")
+                    .with_span(Span::from_offsets(code, index, index + 4))),
+                None => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn transforms_chain_in_enforce_order_with_composed_maps() {
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Prepend("post", Enforce::Post)));
+        plugins.register(Box::new(Prepend("normal", Enforce::Normal)));
+        plugins.register(Box::new(Prepend("pre", Enforce::Pre)));
+        assert_eq!(plugins.fingerprint(), "pre,normal,post");
+
+        let result = plugins.transform(&mut PluginContext::default(), "src/a.js", "let a;\nlet b;\n".to_string()).unwrap();
+        assert_eq!(result.code, "// post\n// normal\n// pre\nlet a;\nlet b;\n");
+        let map = sourcemap::parse(&result.map.unwrap()).unwrap();
+        let token = map.lookup_token(4, 0).unwrap();
+        assert_eq!((token.get_source(), token.get_src_line()), (Some("src/a.js"), 1));
+    }
+
+    #[test]
+    fn a_transform_without_a_map_drops_the_maps_before_it() {
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Prepend("pre", Enforce::Pre)));
+        plugins.register(Box::new(Strip));
+        let mut ctx = PluginContext::default();
+
+        let result = plugins.transform(&mut ctx, "src/a.js", "let a;\n".to_string()).unwrap();
+        assert_eq!((result.code.as_str(), result.map), ("let a;\n", None));
+        let warning = &ctx.take_warnings()[0];
+        assert_eq!((warning.code.as_str(), warning.plugin.as_deref()), ("plugin/sourcemap", Some("strip")));
+    }
+
+    #[test]
+    fn resolve_hooks_only_see_matching_imports() {
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Mocks));
+        let resolver = Resolver::new(&ResolveConfig::default());
+        let args = |path| ResolveArgs {
+            path,
+            importer: "src/a.js",
+            namespace: FILE_NAMESPACE,
+            kind: ResolveKind::Import,
+            resolver: &resolver,
+        };
+
+        let mut ctx = PluginContext::default();
+        assert_eq!(plugins.resolve(&mut ctx, &args("./math")).unwrap(), None);
+        let resolved = plugins.resolve(&mut ctx, &args("@mocks/api")).unwrap().unwrap();
+        assert_eq!(module_id(resolved.namespace.as_deref().unwrap(), &resolved.path), "mock:@mocks/api from src/a.js");
+        assert_eq!(split_module_id("mock:api"), ("mock", "api"));
+        assert_eq!(split_module_id("C:/src/a.js"), (FILE_NAMESPACE, "C:/src/a.js"));
+        assert!(is_virtual("virtual:routes") && is_virtual("\0build-info") && !is_virtual("src/a.js"));
+        assert_eq!(display_id("\0build-info"), "\\0build-info");
+    }
+
+    #[test]
+    fn hooks_default_to_no_ops_and_report_through_the_context() {
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Stamp));
+        let mut ctx = PluginContext::default();
+        plugins.build_start(&mut ctx);
+
+        let chunk = RenderedChunk { entry: "main".to_string(), path: PathBuf::from("dist/main.js"), modules: vec![] };
+        assert_eq!(plugins.render_chunk(&mut ctx, &chunk, "code".to_string()), "/* main */\ncode");
+
+        let mut bundle = ctx.take_emitted();
+        plugins.generate_bundle(&mut ctx, &mut bundle);
+        assert_eq!(bundle[Path::new("dist/entries.txt")], b"main");
+        let warnings = ctx.take_warnings();
+        assert_eq!(warnings[0].plugin.as_deref(), Some("stamp"));
+    }
+
+    #[test]
+    fn hook_errors_and_warnings_name_the_plugin_and_module() {
+        let mut plugins = PluginManager::new();
+        plugins.register(Box::new(Strict));
+        assert_eq!(plugins.fingerprint(), "strict:v1");
+        let mut ctx = PluginContext::default();
+
+        let result = plugins.transform(&mut ctx, "src/a.js", "// TODO\nlet a;\n".to_string()).unwrap();
+        assert_eq!(result.code, "// TODO\nlet a;\n");
+        let warning = &ctx.take_warnings()[0];
+        assert_eq!(warning.to_string(), "src/a.js:1:4: [strict] Unfinished code");
+
+        let error = plugins.transform(&mut ctx, "src/b.js", "let a;\neval(a);\n".to_string()).unwrap_err();
+        assert_eq!((error.code.as_str(), error.plugin.as_deref()), ("strict/eval", Some("strict")));
+        assert_eq!(error.to_string(), "src/b.js:2:1: [strict] `eval` is not allowed");
+    }
+}
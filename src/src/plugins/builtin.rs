@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use fancy_regex::Regex;
+use walkdir::WalkDir;
+
+use crate::diagnostic::Diagnostic;
+use crate::loader::{BuiltinLoader, Loader};
+use crate::plugin::{
+    Enforce, HookResult, OutputBundle, Plugin, PluginContext, PluginFilter, RenderedChunk, ResolveArgs, ResolveResult,
+    TransformResult,
+};
+use crate::sourcemap;
+
+/// Adds `banner` before and `footer` after every chunk the build renders, once it is minified,
+/// and shifts its source map to match. Other files in the bundle, such as copied scripts, are
+/// left alone.
+pub struct BannerPlugin {
+    banner: String,
+    footer: String,
+    /// The chunks rendered since the last `generate_bundle`.
+    chunks: Mutex<HashSet<PathBuf>>,
+}
+
+impl BannerPlugin {
+    pub fn new(banner: &str, footer: &str) -> Self {
+        Self { banner: banner.to_string(), footer: footer.to_string(), chunks: Mutex::default() }
+    }
+}
+
+const INLINE_MAP_PREFIX: &str = "//# sourceMappingURL=data:application/json;base64,";
+
+impl Plugin for BannerPlugin {
+    fn name(&self) -> &str {
+        "banner"
+    }
+
+    fn enforce(&self) -> Enforce {
+        Enforce::Post
+    }
+
+    fn render_chunk(&self, _ctx: &mut PluginContext, chunk: &RenderedChunk, _code: &str) -> HookResult<String> {
+        // The banner goes in once the chunk is minified, which is after this hook.
+        self.chunks.lock().unwrap().insert(chunk.path.clone());
+        Ok(None)
+    }
+
+    fn generate_bundle(&self, ctx: &mut PluginContext, bundle: &mut OutputBundle) -> Result<(), Diagnostic> {
+        // Exactly what is prepended, which is what the source maps shift by.
+        let banner = match self.banner.is_empty() {
+            true => String::new(),
+            false => format!("{}\n", self.banner.trim_end_matches('\n')),
+        };
+        let banner_lines = banner.matches('\n').count();
+        let chunks: Vec<PathBuf> = self.chunks.lock().unwrap().drain().filter(|path| bundle.contains_key(path)).collect();
+
+        for path in chunks {
+            let code = String::from_utf8_lossy(&bundle[&path]).into_owned();
+            // The source map comment stays last, after the footer.
+            let (body, map_comment) = match code.rfind("\n//# sourceMappingURL=") {
+                Some(index) => code.split_at(index + 1),
+                None => (code.as_str(), ""),
+            };
+            let mut map_comment = map_comment.to_string();
+
+            if banner_lines > 0 {
+                let shifted = match map_comment.strip_prefix(INLINE_MAP_PREFIX) {
+                    Some(encoded) => shift_inline_map(encoded.trim_end(), banner_lines)
+                        .map(|encoded| Some(format!("{}{}\n", INLINE_MAP_PREFIX, encoded))),
+                    None => shift_map_file(bundle, &path, &map_comment, banner_lines).map(|_| None),
+                };
+                match shifted {
+                    Ok(Some(comment)) => map_comment = comment,
+                    Ok(None) => {}
+                    Err(err) => ctx.warn(format!("Unable to shift the source map of {}: {}", path.display(), err)),
+                }
+            }
+
+            let mut output = String::with_capacity(code.len() + self.banner.len() + self.footer.len() + 2);
+            output.push_str(&banner);
+            output.push_str(body);
+            if !self.footer.is_empty() {
+                if !output.ends_with('\n') {
+                    output.push('\n');
+                }
+                output.push_str(self.footer.trim_end_matches('\n'));
+                output.push('\n');
+            }
+            output.push_str(&map_comment);
+            bundle.insert(path, output.into_bytes());
+        }
+        Ok(())
+    }
+}
+
+fn shift_inline_map(encoded: &str, lines: usize) -> Result<String, String> {
+    let map = STANDARD.decode(encoded).map_err(|err| err.to_string())?;
+    let map = sourcemap::prepend_lines(&String::from_utf8_lossy(&map), lines)?;
+    Ok(STANDARD.encode(map))
+}
+
+/// Shifts the map `comment` points to, or else a hidden `<path>.map`, if it is in the bundle.
+fn shift_map_file(bundle: &mut OutputBundle, path: &Path, comment: &str, lines: usize) -> Result<(), String> {
+    let map_path = match comment.trim().strip_prefix("//# sourceMappingURL=") {
+        Some(url) => path.with_file_name(url),
+        None => PathBuf::from(format!("{}.map", path.display())),
+    };
+    let Some(map) = bundle.get(&map_path) else {
+        return Ok(());
+    };
+    let shifted = sourcemap::prepend_lines(&String::from_utf8_lossy(map), lines)?;
+    bundle.insert(map_path, shifted.into_bytes());
+    Ok(())
+}
+
+/// Redirects imports of each key, or of paths under it, to its value, like
+/// `"@components": "./src/components"` or `"react": "preact/compat"`. Targets are packages or
+/// paths relative to the project root.
+pub struct AliasPlugin {
+    /// Longest key first, so the most specific alias wins.
+    entries: Vec<(String, String)>,
+}
+
+impl AliasPlugin {
+    pub fn new(entries: &BTreeMap<String, String>) -> Self {
+        let mut entries: Vec<_> = entries.iter().map(|(key, target)| (key.clone(), target.clone())).collect();
+        entries.sort_by_key(|(key, _)| std::cmp::Reverse(key.len()));
+        Self { entries }
+    }
+
+    fn rewrite(&self, path: &str) -> Option<String> {
+        self.entries.iter().find_map(|(key, target)| match path.strip_prefix(key.as_str()) {
+            Some("") => Some(target.clone()),
+            Some(rest) if rest.starts_with('/') => Some(format!("{}{}", target.trim_end_matches('/'), rest)),
+            _ => None,
+        })
+    }
+}
+
+impl Plugin for AliasPlugin {
+    fn name(&self) -> &str {
+        "alias"
+    }
+
+    fn enforce(&self) -> Enforce {
+        Enforce::Pre
+    }
+
+    fn resolve_filter(&self) -> Option<PluginFilter> {
+        let keys: Vec<String> = self.entries.iter().map(|(key, _)| regex::escape(key)).collect();
+        PluginFilter::new(&format!("^(?:{})(?:/|$)", keys.join("|"))).ok()
+    }
+
+    fn resolve_import(&self, _ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+        let Some(target) = self.rewrite(args.path) else {
+            return Ok(None);
+        };
+        // Resolved from the project root rather than the importer.
+        let path = args
+            .resolver
+            .resolve("", &target, args.kind)
+            .map(|resolved| resolved.id)
+            .map_err(|err| Diagnostic::from(err).with_note(format!("'{}' is an alias of '{}'", args.path, target)))?;
+        Ok(Some(ResolveResult { path, namespace: None }))
+    }
+}
+
+/// Replaces each key with its value wherever it appears as a whole word, so `DEBUG` is replaced
+/// in `if (DEBUG)` but not in `DEBUG_LEVEL` or `config.DEBUG`.
+pub struct ReplacePlugin {
+    values: BTreeMap<String, String>,
+    pattern: Option<Regex>,
+}
+
+impl ReplacePlugin {
+    pub fn new(values: &BTreeMap<String, String>) -> Self {
+        let mut keys: Vec<&String> = values.keys().filter(|key| !key.is_empty()).collect();
+        keys.sort_by_key(|key| std::cmp::Reverse(key.len()));
+        let alternatives: Vec<String> = keys.iter().map(|key| fancy_regex::escape(key).into_owned()).collect();
+        let pattern = (!alternatives.is_empty())
+            .then(|| Regex::new(&format!(r"(?<![\w$.])(?:{})(?![\w$])", alternatives.join("|"))).ok())
+            .flatten();
+        Self { values: values.clone(), pattern }
+    }
+}
+
+impl Plugin for ReplacePlugin {
+    fn name(&self) -> &str {
+        "replace"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        serde_json::to_string(&self.values).ok()
+    }
+
+    fn transform_module(&self, _ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+        let Some(pattern) = &self.pattern else {
+            return Ok(None);
+        };
+        if !self.values.keys().any(|key| code.contains(key.as_str())) {
+            return Ok(None);
+        }
+        let mut output = String::with_capacity(code.len());
+        let mut map = ::sourcemap::SourceMapBuilder::new(None);
+        // Positions in the output and in `code`, as zero-based lines and columns.
+        let (mut out, mut src) = ((0, 0), (0, 0));
+        let mut multiline = false;
+        let mut last = 0;
+        for found in pattern.find_iter(code) {
+            let found = found.map_err(|err| Diagnostic::error("replace/match", err.to_string()))?;
+            let unchanged = &code[last..found.start()];
+            map_unchanged(&mut map, file_path, out, src, unchanged);
+            (out, src) = (advance(out, unchanged), advance(src, unchanged));
+            let value = &self.values[found.as_str()];
+            map.add(out.0, out.1, src.0, src.1, Some(file_path), None, false);
+            multiline |= value.contains('\n');
+            (out, src) = (advance(out, value), advance(src, found.as_str()));
+            output.push_str(unchanged);
+            output.push_str(value);
+            last = found.end();
+        }
+        map_unchanged(&mut map, file_path, out, src, &code[last..]);
+        output.push_str(&code[last..]);
+        if output == code {
+            return Ok(None);
+        }
+        // Single-line replacements only move columns, so lines still map to themselves.
+        let map = multiline.then(|| sourcemap::to_json(&map.into_sourcemap()));
+        Ok(Some(TransformResult { code: output, map }))
+    }
+}
+
+/// Where `text` ends if it starts at `start`, counting lines and columns from zero.
+fn advance((mut line, mut column): (u32, u32), text: &str) -> (u32, u32) {
+    for c in text.chars() {
+        if c == '\n' {
+            (line, column) = (line + 1, 0);
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// Maps `text`, copied as it is from `src` to `out`, at its start and at each of its lines.
+fn map_unchanged(map: &mut ::sourcemap::SourceMapBuilder, file_path: &str, out: (u32, u32), src: (u32, u32), text: &str) {
+    map.add(out.0, out.1, src.0, src.1, Some(file_path), None, false);
+    for line in 1..=text.matches('\n').count() as u32 {
+        map.add(out.0 + line, 0, src.0 + line, 0, Some(file_path), None, false);
+    }
+}
+
+/// Copies every file under `from` into `to`, keeping its path relative to `from`. Files the
+/// build already writes take precedence.
+pub struct CopyPlugin {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+impl CopyPlugin {
+    pub fn new(from: &Path, to: &Path) -> Self {
+        Self { from: from.to_path_buf(), to: to.to_path_buf() }
+    }
+
+    fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        WalkDir::new(&self.from)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+    }
+}
+
+impl Plugin for CopyPlugin {
+    fn name(&self) -> &str {
+        "copy"
+    }
+
+    fn build_start(&self, ctx: &mut PluginContext) -> Result<(), Diagnostic> {
+        for file in self.files() {
+            ctx.add_watch_file(file);
+        }
+        Ok(())
+    }
+
+    fn generate_bundle(&self, _ctx: &mut PluginContext, bundle: &mut OutputBundle) -> Result<(), Diagnostic> {
+        for file in self.files() {
+            let Ok(relative) = file.strip_prefix(&self.from) else {
+                continue;
+            };
+            let target = self.to.join(relative);
+            if bundle.contains_key(&target) {
+                continue;
+            }
+            let contents = fs::read(&file).map_err(|err| {
+                Diagnostic::error("copy/read", format!("Unable to copy file: {}", err)).with_file(&file)
+            })?;
+            bundle.insert(target, contents);
+        }
+        Ok(())
+    }
+}
+
+/// Loads `.json` files with the `json` loader, whatever `loaders` in the config says.
+pub struct JsonPlugin;
+
+impl Plugin for JsonPlugin {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn loaders(&self) -> Vec<Arc<dyn Loader>> {
+        vec![Arc::new(BuiltinLoader::new("json", Path::new("")).with_extensions(&["json".to_string()]))]
+    }
+}
+
+/// Loads files with one of `extensions` with the `text` loader, as modules whose default export
+/// is their contents.
+pub struct TextPlugin {
+    extensions: Vec<String>,
+}
+
+impl TextPlugin {
+    pub fn new(extensions: &[String]) -> Self {
+        let extensions = extensions.iter().map(|ext| ext.trim_start_matches('.').to_string()).collect();
+        Self { extensions }
+    }
+}
+
+impl Plugin for TextPlugin {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(self.extensions.join(" "))
+    }
+
+    fn loaders(&self) -> Vec<Arc<dyn Loader>> {
+        vec![Arc::new(BuiltinLoader::new("text", Path::new("")).with_extensions(&self.extensions))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_whole_words_only() {
+        let plugin = ReplacePlugin::new(&BTreeMap::from([
+            ("DEBUG".to_string(), "false".to_string()),
+            ("__VERSION__".to_string(), "\"1.2.0\"".to_string()),
+        ]));
+        let code = "if (DEBUG) log(__VERSION__, DEBUG_LEVEL, config.DEBUG, $DEBUG);\n";
+        assert_eq!(
+            plugin.transform_module(&mut PluginContext::default(), "src/a.js", code).unwrap().unwrap().code,
+            "if (false) log(\"1.2.0\", DEBUG_LEVEL, config.DEBUG, $DEBUG);\n"
+        );
+        assert_eq!(plugin.transform_module(&mut PluginContext::default(), "src/b.js", "DEBUG_LEVEL").unwrap(), None);
+
+        let plugin = ReplacePlugin::new(&BTreeMap::from([("BANNER".to_string(), "`a\nb`".to_string())]));
+        let result = plugin.transform_module(&mut PluginContext::default(), "src/c.js", "log(BANNER);\nlet c;\n").unwrap().unwrap();
+        assert_eq!(result.code, "log(`a\nb`);\nlet c;\n");
+        let map = sourcemap::parse(&result.map.unwrap()).unwrap();
+        assert_eq!(map.lookup_token(2, 0).unwrap().get_src_line(), 1);
+    }
+
+    #[test]
+    fn aliases_match_whole_segments() {
+        let plugin = AliasPlugin::new(&BTreeMap::from([
+            ("@".to_string(), "./src".to_string()),
+            ("@lib".to_string(), "./src/lib/".to_string()),
+        ]));
+        assert_eq!(plugin.rewrite("@/greet").as_deref(), Some("./src/greet"));
+        assert_eq!(plugin.rewrite("@lib/math").as_deref(), Some("./src/lib/math"));
+        assert_eq!(plugin.rewrite("@scope/pkg"), None);
+        let filter = plugin.resolve_filter().unwrap();
+        assert!(filter.matches("@lib", "file") && !filter.matches("@scope/pkg", "file"));
+    }
+
+    #[test]
+    fn banner_shifts_the_source_map() {
+        let plugin = BannerPlugin::new("/*! License */\n\n", "// end");
+        let map = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#;
+        let mut bundle = OutputBundle::from([
+            (PathBuf::from("dist/a.js"), b"let a;\n//# sourceMappingURL=a.js.map\n".to_vec()),
+            (PathBuf::from("dist/a.js.map"), map.as_bytes().to_vec()),
+            (PathBuf::from("dist/vendor.js"), b"let v;\n".to_vec()),
+        ]);
+        let chunk = RenderedChunk { entry: "a".to_string(), path: PathBuf::from("dist/a.js"), modules: vec![] };
+        plugin.render_chunk(&mut PluginContext::default(), &chunk, "").unwrap();
+        plugin.generate_bundle(&mut PluginContext::default(), &mut bundle).unwrap();
+
+        assert_eq!(bundle[Path::new("dist/a.js")], b"/*! License */\nlet a;\n// end\n//# sourceMappingURL=a.js.map\n");
+        assert_eq!(bundle[Path::new("dist/vendor.js")], b"let v;\n");
+        let map = sourcemap::parse(&String::from_utf8_lossy(&bundle[Path::new("dist/a.js.map")])).unwrap();
+        assert!(map.lookup_token(0, 0).is_none());
+        assert_eq!(map.lookup_token(1, 0).unwrap().get_src_line(), 0);
+    }
+}
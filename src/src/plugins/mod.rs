@@ -0,0 +1,127 @@
+pub mod builtin;
+pub mod process;
+pub mod wasm;
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{Config, PluginConfig};
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::plugin::{HookResult, Plugin, PluginContext, ResolveArgs, ResolveKind};
+
+/// Instantiates the plugins listed in the config file, in order.
+pub fn from_config(config: &Config) -> Result<Vec<Box<dyn Plugin>>, Diagnostic> {
+    let output_dir = Path::new(config.output_pattern()).parent().unwrap_or(Path::new(""));
+    config
+        .plugins
+        .iter()
+        .map(|config| -> Result<Box<dyn Plugin>, Diagnostic> {
+            match config {
+                PluginConfig::Wasm { path, name } => Ok(Box::new(wasm::WasmPlugin::load(path, name.as_deref())?)),
+                PluginConfig::Process { command, args, name, timeout_ms } => Ok(Box::new(process::ProcessPlugin::spawn(
+                    command,
+                    args,
+                    name.as_deref(),
+                    Duration::from_millis(*timeout_ms),
+                )?)),
+                PluginConfig::Banner { banner, footer } => Ok(Box::new(builtin::BannerPlugin::new(banner, footer))),
+                PluginConfig::Alias { entries } => Ok(Box::new(builtin::AliasPlugin::new(entries))),
+                PluginConfig::Replace { values } => Ok(Box::new(builtin::ReplacePlugin::new(values))),
+                PluginConfig::Copy { from, to } => {
+                    Ok(Box::new(builtin::CopyPlugin::new(from, to.as_deref().unwrap_or(output_dir))))
+                }
+                PluginConfig::Json => Ok(Box::new(builtin::JsonPlugin)),
+                PluginConfig::Text { extensions } => Ok(Box::new(builtin::TextPlugin::new(extensions))),
+            }
+        })
+        .collect()
+}
+
+/// The input of a `resolve` hook, as plugins outside the binary see it.
+#[derive(Serialize)]
+pub(crate) struct ResolveInput<'a> {
+    path: &'a str,
+    importer: &'a str,
+    namespace: &'a str,
+    kind: ResolveKind,
+}
+
+impl<'a> From<&ResolveArgs<'a>> for ResolveInput<'a> {
+    fn from(args: &ResolveArgs<'a>) -> Self {
+        Self { path: args.path, importer: args.importer, namespace: args.namespace, kind: args.kind }
+    }
+}
+
+/// The input of a `load` or `transform` hook.
+#[derive(Serialize)]
+pub(crate) struct CodeInput<'a> {
+    pub path: &'a str,
+    pub code: &'a str,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LoadOutput {
+    pub code: String,
+}
+
+/// An error or warning from a plugin outside the binary: `{"message", "code"?, "file"?,
+/// "line"?, "column"?}`, with 1-based lines and columns. Without a `file` it points at the
+/// module.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Message {
+    pub message: String,
+    code: Option<String>,
+    file: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl Message {
+    pub fn into_diagnostic(self, severity: Severity, code: &str) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(severity, self.code.as_deref().unwrap_or(code), self.message);
+        if let Some(file) = self.file {
+            diagnostic = diagnostic.with_file(file);
+        }
+        if let Some(line) = self.line {
+            let column = self.column.unwrap_or(1);
+            diagnostic = diagnostic.with_span(Span::new(line, column, line, column + 1));
+        }
+        diagnostic
+    }
+}
+
+/// Reads the output of a hook outside the binary. Its `warnings` are reported through `ctx`, its
+/// `watchFiles` are added as with [`PluginContext::add_watch_file`], and an `error` fails the
+/// hook; the rest is the result, where `null` or nothing leaves the module to the next plugin.
+/// `code` identifies malformed output.
+pub(crate) fn read_output<T: DeserializeOwned>(ctx: &mut PluginContext, mut output: Value, code: &str) -> HookResult<T> {
+    let invalid = |err: serde_json::Error| Diagnostic::error(code, format!("Invalid plugin output: {}", err));
+    if let Some(fields) = output.as_object_mut() {
+        if let Some(warnings) = fields.remove("warnings") {
+            for warning in serde_json::from_value::<Vec<Message>>(warnings).map_err(invalid)? {
+                ctx.add_warning(warning.into_diagnostic(Severity::Warning, "plugin/warning"));
+            }
+        }
+        if let Some(watch_files) = fields.remove("watchFiles") {
+            for path in serde_json::from_value::<Vec<String>>(watch_files).map_err(invalid)? {
+                ctx.add_watch_file(path);
+            }
+        }
+        if let Some(error) = fields.remove("error") {
+            let error: Message = serde_json::from_value(error).map_err(invalid)?;
+            return Err(error.into_diagnostic(Severity::Error, "plugin/error"));
+        }
+        if fields.is_empty() {
+            return Ok(None);
+        }
+    }
+    if output.is_null() {
+        return Ok(None);
+    }
+    serde_json::from_value(output).map(Some).map_err(invalid)
+}
@@ -0,0 +1,391 @@
+//! Runs a plugin as a child process speaking JSON-RPC 2.0 over its stdin and stdout, one
+//! message per line. Its stderr is passed through.
+//!
+//! # Protocol
+//!
+//! The host first calls `initialize` with no params; the plugin answers with the hooks it
+//! implements, e.g. `{"hooks": ["resolve", "transform"]}`, and optionally a `cacheKey`, such as
+//! its version, that changes whenever it could transform a module differently. The host then
+//! calls those hooks with the same params and results as the WebAssembly ABI in
+//! [`crate::plugins::wasm`]:
+//!
+//! | method      | params                                      | result                   |
+//! |-------------|---------------------------------------------|--------------------------|
+//! | `resolve`   | `{"path", "importer", "namespace", "kind"}` | `{"path", "namespace"?}` |
+//! | `load`      | `{"path", "code"}`                          | `{"code"}`               |
+//! | `transform` | `{"path", "code"}`                          | `{"code", "map"?}`       |
+//!
+//! A `null` result leaves the module to the next plugin. Results may list `warnings` and
+//! `watchFiles` or be an `error`, as in the WebAssembly ABI; a JSON-RPC error fails the module
+//! too, and its `data` may locate it with `{"file"?, "line"?, "column"?}`. Calls made while the
+//! previous message is being written are sent together as a batch (a JSON array), and may be
+//! answered in any order, either as a batch or one response per line. Each call fails if its
+//! response takes longer than the configured timeout, and the host then kills the process and
+//! starts it again, with `initialize`, on the next call. The host closes stdin when the build
+//! is done.
+//!
+//! `examples/plugins/rpc_plugin.py` is a minimal plugin.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, error};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{read_output, CodeInput, LoadOutput, Message, ResolveInput};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::plugin::{HookResult, Plugin, PluginContext, ResolveArgs, ResolveResult, TransformResult};
+
+/// Calls waiting for a response, by request id.
+type Pending = Arc<Mutex<HashMap<u64, Sender<Response>>>>;
+
+/// A plugin running as a child process. A call that times out stops the process, since it may
+/// still be busy with the call, and the next call starts it again.
+pub struct ProcessPlugin {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    hooks: Vec<String>,
+    cache_key: Option<String>,
+    timeout: Duration,
+    process: Mutex<Arc<Process>>,
+}
+
+/// One run of the plugin's command.
+struct Process {
+    child: Mutex<Child>,
+    /// Requests for the writer thread; dropped to close the child's stdin.
+    requests: Option<Sender<Value>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    /// Set once a call timed out and the process was killed.
+    killed: AtomicBool,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: u64,
+    #[serde(default)]
+    result: Value,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Value,
+}
+
+impl ResponseError {
+    fn into_diagnostic(self) -> Diagnostic {
+        let mut message: Message = serde_json::from_value(self.data).unwrap_or_default();
+        message.message = format!("{} (code {})", self.message, self.code);
+        message.into_diagnostic(Severity::Error, "plugin/process")
+    }
+}
+
+#[derive(Deserialize)]
+struct InitializeResult {
+    #[serde(default)]
+    hooks: Vec<String>,
+    #[serde(default, rename = "cacheKey")]
+    cache_key: Option<String>,
+}
+
+impl ProcessPlugin {
+    /// Starts `command` with `args` and asks it which hooks it implements.
+    pub fn spawn(command: &str, args: &[String], name: Option<&str>, timeout: Duration) -> Result<Self, Diagnostic> {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => Path::new(command).file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        };
+        let (process, initialized) = Self::start(command, args, &name, timeout)?;
+        Ok(Self {
+            name,
+            command: command.to_string(),
+            args: args.to_vec(),
+            hooks: initialized.hooks,
+            cache_key: initialized.cache_key,
+            timeout,
+            process: Mutex::new(Arc::new(process)),
+        })
+    }
+
+    /// Runs the command and sends it `initialize`.
+    fn start(command: &str, args: &[String], name: &str, timeout: Duration) -> Result<(Process, InitializeResult), Diagnostic> {
+        let failed = |message: String| {
+            Diagnostic::error("plugin/process", format!("Unable to start plugin `{}`: {}", name, message))
+                .with_note(format!("command: {} {}", command, args.join(" ")))
+        };
+        let process = Process::spawn(command, args).map_err(failed)?;
+        let initialized = process
+            .call("initialize", Value::Null, timeout)
+            .and_then(|result| {
+                serde_json::from_value(result)
+                    .map_err(|err| Diagnostic::error("plugin/process", format!("invalid result: {}", err)))
+            })
+            .map_err(|diagnostic| failed(diagnostic.message))?;
+        Ok((process, initialized))
+    }
+
+    /// Sends one request and waits for its result, first starting the process again if the
+    /// last call timed out.
+    fn call(&self, method: &str, params: impl Serialize) -> Result<Value, Diagnostic> {
+        let process = {
+            let mut current = self.process.lock().unwrap();
+            if current.killed.load(Ordering::Relaxed) {
+                debug!("Restarting plugin process `{}`", self.name);
+                let (process, _) = Self::start(&self.command, &self.args, &self.name, self.timeout)?;
+                *current = Arc::new(process);
+            }
+            Arc::clone(&current)
+        };
+        process.call(method, params, self.timeout)
+    }
+
+    /// Calls `hook` if the plugin implements it.
+    fn call_hook<O: DeserializeOwned>(&self, ctx: &mut PluginContext, hook: &str, params: impl Serialize) -> HookResult<O> {
+        if !self.hooks.iter().any(|implemented| implemented == hook) {
+            return Ok(None);
+        }
+        read_output(ctx, self.call(hook, params)?, "plugin/process")
+    }
+}
+
+impl Process {
+    fn spawn(command: &str, args: &[String]) -> Result<Self, String> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err("stdio is not piped".to_string());
+        };
+
+        let pending = Pending::default();
+        let (requests, queue) = channel();
+        thread::spawn(move || write_requests(stdin, queue));
+        let responses = Arc::clone(&pending);
+        thread::spawn(move || read_responses(stdout, responses));
+        Ok(Self {
+            child: Mutex::new(child),
+            requests: Some(requests),
+            pending,
+            next_id: AtomicU64::new(1),
+            killed: AtomicBool::new(false),
+        })
+    }
+
+    /// Sends one request and waits up to `timeout` for its result.
+    fn call(&self, method: &str, params: impl Serialize, timeout: Duration) -> Result<Value, Diagnostic> {
+        let failed = |message: String| Diagnostic::error("plugin/process", format!("`{}` failed: {}", method, message));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let response = match self.send(id, request, timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(failed(format!("no response within {}ms", timeout.as_millis()))
+                    .with_note("the plugin process was stopped, and starts again on the next call"));
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(failed("the plugin process has exited".to_string())),
+        };
+        match response.error {
+            Some(err) => Err(err.into_diagnostic()),
+            None => Ok(response.result),
+        }
+    }
+
+    /// Sends a request and waits up to `timeout` for its response, killing the process if it
+    /// does not answer in time. `Disconnected` means the process has exited.
+    fn send(&self, id: u64, request: Value, timeout: Duration) -> Result<Response, RecvTimeoutError> {
+        let (tx, rx) = channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let sent = self.requests.as_ref().is_some_and(|requests| requests.send(request).is_ok());
+        let response = if sent { rx.recv_timeout(timeout) } else { Err(RecvTimeoutError::Disconnected) };
+        if response.is_err() {
+            self.pending.lock().unwrap().remove(&id);
+        }
+        if let Err(RecvTimeoutError::Timeout) = response {
+            self.kill();
+        }
+        response
+    }
+
+    /// Stops the process at once, since it may still be busy with a call. Calls still waiting
+    /// on it fail as it exits.
+    fn kill(&self) {
+        self.killed.store(true, Ordering::Relaxed);
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Writes queued requests to the child, batching the ones that queued up behind a write.
+fn write_requests(mut stdin: ChildStdin, queue: Receiver<Value>) {
+    while let Ok(first) = queue.recv() {
+        let mut batch = vec![first];
+        batch.extend(queue.try_iter());
+        let message = if batch.len() == 1 { batch.remove(0) } else { Value::Array(batch) };
+        let written = serde_json::to_writer(&mut stdin, &message)
+            .map_err(std::io::Error::from)
+            .and_then(|_| stdin.write_all(b"\n"))
+            .and_then(|_| stdin.flush());
+        if let Err(err) = written {
+            debug!("Unable to write to plugin process: {}", err);
+            break;
+        }
+    }
+}
+
+/// Hands each response to the call waiting for it. Once the child closes stdout, every waiting
+/// call fails.
+fn read_responses(stdout: ChildStdout, pending: Pending) {
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let responses = match serde_json::from_str::<Value>(&line) {
+            Ok(Value::Array(batch)) => batch,
+            Ok(response) => vec![response],
+            Err(err) => {
+                error!("Plugin process wrote invalid JSON: {}", err);
+                continue;
+            }
+        };
+        for response in responses {
+            match serde_json::from_value::<Response>(response) {
+                Ok(response) => {
+                    if let Some(tx) = pending.lock().unwrap().remove(&response.id) {
+                        let _ = tx.send(response);
+                    }
+                }
+                Err(err) => error!("Plugin process wrote an invalid response: {}", err),
+            }
+        }
+    }
+    pending.lock().unwrap().clear();
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // Closing stdin asks the plugin to exit; one that does not is killed.
+        self.requests = None;
+        let mut child = self.child.lock().unwrap();
+        for _ in 0..10 {
+            if !matches!(child.try_wait(), Ok(None)) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+impl Plugin for ProcessPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        self.cache_key.clone()
+    }
+
+    fn resolve_import(&self, ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+        self.call_hook(ctx, "resolve", ResolveInput::from(args))
+    }
+
+    fn load_module(&self, ctx: &mut PluginContext, file_path: &str, content: &str) -> HookResult<String> {
+        let output: Option<LoadOutput> = self.call_hook(ctx, "load", CodeInput { path: file_path, code: content })?;
+        Ok(output.map(|output| output.code))
+    }
+
+    fn transform_module(&self, ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+        self.call_hook(ctx, "transform", CodeInput { path: file_path, code })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ResolveConfig;
+    use crate::resolver::Resolver;
+    use crate::plugin::{ResolveKind, FILE_NAMESPACE};
+
+    /// Starts the example plugin. The tests need `python3` on the `PATH`.
+    fn spawn(timeout: Duration) -> ProcessPlugin {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/plugins/rpc_plugin.py");
+        ProcessPlugin::spawn("python3", &[script.to_string()], Some("rpc"), timeout)
+            .unwrap_or_else(|err| panic!("python3 is needed to run the example plugin: {}", err))
+    }
+
+    #[test]
+    fn calls_hooks_over_json_rpc() {
+        let plugin = spawn(Duration::from_secs(5));
+        let resolver = Resolver::new(&ResolveConfig::default());
+        let args = |path| ResolveArgs {
+            path,
+            importer: "src/index.js",
+            namespace: FILE_NAMESPACE,
+            kind: ResolveKind::Import,
+            resolver: &resolver,
+        };
+        let mut ctx = PluginContext::default();
+        assert_eq!(plugin.resolve_import(&mut ctx, &args("./a")).unwrap(), None);
+        assert_eq!(plugin.resolve_import(&mut ctx, &args("@rpc/info")).unwrap().unwrap().path, "virtual:rpc/info");
+        assert_eq!(
+            plugin.load_module(&mut ctx, "virtual:rpc/info", "").unwrap().as_deref(),
+            Some("export const name = \"info\";\n")
+        );
+
+        // Concurrent calls are batched and every caller still gets its own result.
+        thread::scope(|scope| {
+            for index in 0..8 {
+                let plugin = &plugin;
+                scope.spawn(move || {
+                    let code = format!("export const n = {}, by = __RPC__;", index);
+                    let result = plugin.transform_module(&mut PluginContext::default(), "src/a.js", &code).unwrap().unwrap();
+                    assert_eq!(result.code, format!("export const n = {}, by = \"rpc\";", index));
+                });
+            }
+        });
+
+        let result = plugin.transform_module(&mut ctx, "src/b.js", "let a;\n// TODO\n").unwrap();
+        assert_eq!(result, None);
+        assert_eq!(ctx.take_warnings()[0].span.map(|span| (span.line, span.column)), Some((2, 4)));
+        let err = plugin.transform_module(&mut ctx, "src/b.js", "let a = __RPC_FAIL__;").unwrap_err();
+        assert_eq!((err.message.as_str(), err.span.unwrap().column), ("asked to fail", 9));
+    }
+
+    #[test]
+    fn gives_up_on_slow_calls() {
+        let plugin = spawn(Duration::from_secs(1));
+        let start = std::time::Instant::now();
+        let err = plugin.transform_module(&mut PluginContext::default(), "src/slow.js", "__RPC__").unwrap_err();
+        assert!(err.message.contains("no response within 1000ms"), "{}", err.message);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // The slow call was killed with its process; queued behind it, the next call would
+        // time out too.
+        let result = plugin.transform_module(&mut PluginContext::default(), "src/a.js", "__RPC__").unwrap();
+        assert_eq!(result.unwrap().code, "\"rpc\"");
+    }
+}
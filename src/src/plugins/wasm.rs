@@ -0,0 +1,287 @@
+//! Runs plugins compiled to WebAssembly in a sandboxed interpreter.
+//!
+//! # ABI
+//!
+//! A plugin is a core WebAssembly module. It is given no WASI imports, so it cannot reach the
+//! filesystem, network or clock; its only possible import is `hyperpack.log(ptr: i32, len: i32)`,
+//! which logs a UTF-8 message at debug level.
+//!
+//! It must export `memory` and `alloc(len: i32) -> i32`, which returns a buffer of `len` bytes
+//! for the host to write a hook's input into. If it exports `dealloc(ptr: i32, len: i32)`, the
+//! host calls it for each input once the hook returns, and for each output once it is read.
+//!
+//! Hooks are optional exports of type `(ptr: i32, len: i32) -> i64`, taking and returning UTF-8
+//! JSON:
+//!
+//! | export      | input                                                  | output                      |
+//! |-------------|--------------------------------------------------------|-----------------------------|
+//! | `resolve`   | `{"path", "importer", "namespace", "kind"}`            | `{"path", "namespace"?}`    |
+//! | `load`      | `{"path", "code"}`, `code` empty for virtual modules   | `{"code"}`                  |
+//! | `transform` | `{"path", "code"}`                                     | `{"code", "map"?}`          |
+//!
+//! `kind` is one of `import`, `require`, `dynamic-import` or `css-url`, and `map` is a source map
+//! as a JSON string. A hook returns where its output is as `ptr << 32 | len`, or `0` to leave the
+//! module to the next plugin.
+//!
+//! Any output may also list `warnings`, or be an `error` that fails the module instead, each
+//! shaped `{"message", "code"?, "file"?, "line"?, "column"?}` and pointing at the module unless
+//! it names a `file`. A hook that traps fails the module too. `load` and `transform` outputs may
+//! list `watchFiles`, paths the plugin read to produce them, such as its own config: the cached
+//! module is processed again whenever one changes.
+//!
+//! Each call may execute at most [`FUEL_PER_CALL`] instructions by default, and memory may grow
+//! to at most [`MEMORY_LIMIT`] bytes; a plugin exceeding either traps.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use wasmi::{Caller, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use super::{read_output, CodeInput, LoadOutput, ResolveInput};
+use crate::diagnostic::Diagnostic;
+use crate::plugin::{HookResult, Plugin, PluginContext, ResolveArgs, ResolveResult, TransformResult};
+
+/// Instructions one hook call may execute.
+pub const FUEL_PER_CALL: u64 = 1_000_000_000;
+
+/// Bytes of linear memory a plugin may grow to.
+pub const MEMORY_LIMIT: usize = 256 << 20;
+
+type Hook = TypedFunc<(i32, i32), i64>;
+
+/// A plugin loaded from a `.wasm` file.
+pub struct WasmPlugin {
+    name: String,
+    /// Hash of the module, so rebuilding the plugin invalidates what it transformed.
+    hash: String,
+    fuel_per_call: u64,
+    instance: Mutex<Instance>,
+}
+
+struct Instance {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: Option<TypedFunc<(i32, i32), ()>>,
+    resolve: Option<Hook>,
+    load: Option<Hook>,
+    transform: Option<Hook>,
+}
+
+impl WasmPlugin {
+    /// Reads and instantiates `path`, named `name` or else after the file.
+    pub fn load(path: &Path, name: Option<&str>) -> Result<Self, Diagnostic> {
+        let wasm = fs::read(path).map_err(|err| {
+            Diagnostic::error("plugin/wasm", format!("Unable to read WebAssembly plugin: {}", err)).with_file(path)
+        })?;
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        };
+        Self::new(&name, &wasm).map_err(|diagnostic| diagnostic.with_file(path))
+    }
+
+    /// Instantiates a plugin from the bytes of a WebAssembly module, or its text format.
+    pub fn new(name: &str, wasm: &[u8]) -> Result<Self, Diagnostic> {
+        let invalid = |err: String| {
+            Diagnostic::error("plugin/wasm", format!("Invalid WebAssembly plugin `{}`: {}", name, err))
+        };
+
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(|err| invalid(err.to_string()))?;
+
+        let mut store = Store::new(&engine, StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build());
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(|err| invalid(err.to_string()))?;
+
+        let mut linker = Linker::new(&engine);
+        let plugin_name = name.to_string();
+        linker
+            .func_wrap("hyperpack", "log", move |caller: Caller<'_, StoreLimits>, ptr: i32, len: i32| {
+                if let Some(Extern::Memory(memory)) = caller.get_export("memory") {
+                    let message = memory
+                        .data(&caller)
+                        .get(ptr as u32 as usize..)
+                        .and_then(|data| data.get(..len as u32 as usize))
+                        .unwrap_or_default();
+                    debug!("[{}] {}", plugin_name, String::from_utf8_lossy(message));
+                }
+            })
+            .map_err(|err| invalid(err.to_string()))?;
+        let instance = linker
+            .instantiate_and_start(&mut store, &module)
+            .map_err(|err| invalid(err.to_string()))?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| invalid("missing `memory` export".to_string()))?;
+        let alloc = instance
+            .get_typed_func(&store, "alloc")
+            .map_err(|err| invalid(format!("`alloc`: {}", err)))?;
+        let optional = |export: &str| -> Result<Option<Hook>, Diagnostic> {
+            match instance.get_export(&store, export) {
+                Some(_) => instance
+                    .get_typed_func(&store, export)
+                    .map(Some)
+                    .map_err(|err| invalid(format!("`{}`: {}", export, err))),
+                None => Ok(None),
+            }
+        };
+        let (resolve, load, transform) = (optional("resolve")?, optional("load")?, optional("transform")?);
+        let dealloc = match instance.get_export(&store, "dealloc") {
+            Some(_) => Some(
+                instance
+                    .get_typed_func(&store, "dealloc")
+                    .map_err(|err| invalid(format!("`dealloc`: {}", err)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            hash: Sha256::digest(wasm).iter().map(|byte| format!("{:02x}", byte)).collect(),
+            fuel_per_call: FUEL_PER_CALL,
+            instance: Mutex::new(Instance { store, memory, alloc, dealloc, resolve, load, transform }),
+        })
+    }
+
+    /// Limits each hook call to `fuel` instructions instead of [`FUEL_PER_CALL`].
+    pub fn with_fuel_per_call(mut self, fuel: u64) -> Self {
+        self.fuel_per_call = fuel;
+        self
+    }
+
+    /// Calls the hook `select` picks, if the plugin exports it.
+    fn call<O: DeserializeOwned>(
+        &self,
+        ctx: &mut PluginContext,
+        hook_name: &str,
+        select: fn(&Instance) -> Option<Hook>,
+        input: &impl Serialize,
+    ) -> HookResult<O> {
+        let output = {
+            let mut instance = self.instance.lock().unwrap();
+            let Some(hook) = select(&instance) else {
+                return Ok(None);
+            };
+            instance.call(hook, input, self.fuel_per_call).map_err(|err| {
+                Diagnostic::error("plugin/wasm", format!("`{}` failed: {}", hook_name, err))
+            })?
+        };
+        read_output(ctx, output, "plugin/wasm")
+    }
+}
+
+impl Instance {
+    /// Calls `hook` with `input`, returning its output, or `null` if it returned none.
+    fn call(&mut self, hook: Hook, input: &impl Serialize, fuel: u64) -> Result<Value, String> {
+        let input = serde_json::to_vec(input).map_err(|err| err.to_string())?;
+        let len = i32::try_from(input.len()).map_err(|_| "input is too large".to_string())?;
+        self.store.set_fuel(fuel).map_err(|err| err.to_string())?;
+
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|err| err.to_string())?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, &input)
+            .map_err(|err| format!("`alloc` returned an invalid buffer: {}", err))?;
+        let packed = hook.call(&mut self.store, (ptr, len)).map_err(|err| err.to_string())?;
+        self.dealloc(ptr, len)?;
+        if packed == 0 {
+            return Ok(Value::Null);
+        }
+
+        let (out_ptr, out_len) = ((packed as u64 >> 32) as u32, packed as u64 as u32);
+        let mut output = vec![0; out_len as usize];
+        self.memory
+            .read(&self.store, out_ptr as usize, &mut output)
+            .map_err(|err| format!("returned an invalid buffer: {}", err))?;
+        self.dealloc(out_ptr as i32, out_len as i32)?;
+        serde_json::from_slice(&output).map_err(|err| format!("invalid output: {}", err))
+    }
+
+    fn dealloc(&mut self, ptr: i32, len: i32) -> Result<(), String> {
+        match self.dealloc {
+            Some(dealloc) => dealloc.call(&mut self.store, (ptr, len)).map_err(|err| err.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Plugin for WasmPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(self.hash.clone())
+    }
+
+    fn resolve_import(&self, ctx: &mut PluginContext, args: &ResolveArgs) -> HookResult<ResolveResult> {
+        self.call(ctx, "resolve", |instance| instance.resolve, &ResolveInput::from(args))
+    }
+
+    fn load_module(&self, ctx: &mut PluginContext, file_path: &str, content: &str) -> HookResult<String> {
+        let input = CodeInput { path: file_path, code: content };
+        let output: Option<LoadOutput> = self.call(ctx, "load", |instance| instance.load, &input)?;
+        Ok(output.map(|output| output.code))
+    }
+
+    fn transform_module(&self, ctx: &mut PluginContext, file_path: &str, code: &str) -> HookResult<TransformResult> {
+        let input = CodeInput { path: file_path, code };
+        self.call(ctx, "transform", |instance| instance.transform, &input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ResolveConfig;
+    use crate::resolver::Resolver;
+    use crate::plugin::{ResolveKind, FILE_NAMESPACE};
+
+    /// Loads `virtual:answer`, resolves nothing and spins forever when asked to transform.
+    const PLUGIN: &str = r#"
+        (module
+          (import "hyperpack" "log" (func $log (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "{\"code\":\"export default 42;\"}")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "resolve") (param i32 i32) (result i64) (i64.const 0))
+          (func (export "load") (param $ptr i32) (param $len i32) (result i64)
+            (call $log (local.get $ptr) (local.get $len))
+            (i64.or (i64.shl (i64.const 0) (i64.const 32)) (i64.const 29)))
+          (func (export "transform") (param i32 i32) (result i64)
+            (loop $spin (br $spin))
+            (i64.const 0)))
+    "#;
+
+    #[test]
+    fn calls_hooks_through_the_abi() {
+        let plugin = WasmPlugin::new("answer", PLUGIN.as_bytes()).unwrap().with_fuel_per_call(10_000);
+        let mut ctx = PluginContext::default();
+        assert_eq!(plugin.load_module(&mut ctx, "virtual:answer", "").unwrap().as_deref(), Some("export default 42;"));
+
+        let resolver = Resolver::new(&ResolveConfig::default());
+        let args = ResolveArgs {
+            path: "./a",
+            importer: "src/index.js",
+            namespace: FILE_NAMESPACE,
+            kind: ResolveKind::Import,
+            resolver: &resolver,
+        };
+        assert_eq!(plugin.resolve_import(&mut ctx, &args).unwrap(), None);
+        // Runs out of fuel instead of hanging the build.
+        let err = plugin.transform_module(&mut ctx, "src/a.js", "let a;").unwrap_err();
+        assert!(err.message.starts_with("`transform` failed"), "{}", err.message);
+
+        let err = WasmPlugin::new("empty", b"(module)").err().unwrap();
+        assert!(err.message.contains("missing `memory` export"), "{}", err.message);
+    }
+}
@@ -1,130 +1,1239 @@
-use std::path::{Path, PathBuf};
-use std::fs;
-use std::env;
-use regex::Regex;
-use log::{info, error, debug};
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use log::{info, debug, warn};
+use serde_json::{Map, Value};
 
-pub struct PluginManager;
+use crate::config::ResolveConfig;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::plugin::{self, PluginContext, PluginManager, ResolveArgs, ResolveKind};
+
+/// Resolves `import_path` as imported from the module `base`, through plugins and then on disk.
+/// Plugins report warnings through `ctx`.
+pub fn resolve_path(
+    base: &str,
+    import_path: &str,
+    kind: ResolveKind,
+    plugins: &PluginManager,
+    ctx: &mut PluginContext,
+    resolver: &Resolver,
+) -> Result<Resolved, ResolveError> {
+    debug!("Import path: {}", import_path);
+
+    let (namespace, _) = plugin::split_module_id(base);
+    let args = ResolveArgs {
+        path: import_path,
+        importer: base,
+        namespace,
+        kind,
+        resolver,
+    };
+    let resolved = plugins.resolve(ctx, &args).map_err(|cause| ResolveError {
+        kind: ResolveErrorKind::Plugin,
+        specifier: import_path.to_string(),
+        importer: base.to_string(),
+        tried: vec![],
+        suggestion: None,
+        cause,
+    })?;
+    if let Some(resolved) = resolved {
+        let id = plugin::module_id(resolved.namespace.as_deref().unwrap_or(plugin::FILE_NAMESPACE), &resolved.path);
+        info!("Resolved path via plugins: {}", id);
+        // Plugins only decide where a file is; whether it may be read is up to the roots.
+        if !plugin::is_virtual(&id) {
+            resolver.check_roots(Path::new(&id), import_path).map_err(|cause| ResolveError {
+                kind: ResolveErrorKind::of(&cause),
+                specifier: import_path.to_string(),
+                importer: base.to_string(),
+                tried: vec![],
+                suggestion: None,
+                cause: cause.with_note("a plugin resolved it there"),
+            })?;
+        }
+        return Ok(Resolved { id });
+    }
 
-impl PluginManager {
-    pub fn resolve(&self, path: &str) -> Option<String> {
-        // Placeholder for actual plugin resolution logic
-        None
+    // Left for the plugins' load hooks, which fail the build if none of them loads it.
+    if import_path.starts_with("virtual:") || import_path.starts_with('\0') {
+        return Ok(Resolved { id: import_path.to_string() });
     }
+
+    resolver.resolve(base, import_path, kind)
+}
+
+/// Where an import resolved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    /// The imported module's id: a path relative to the project root, or a virtual module.
+    pub id: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Config {
-    paths: HashMap<String, String>,
-    extensions: Vec<String>,
+/// An import that could not be resolved.
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub kind: ResolveErrorKind,
+    /// The specifier as written.
+    pub specifier: String,
+    /// The module importing it.
+    pub importer: String,
+    /// Every path looked at, in order.
+    pub tried: Vec<PathBuf>,
+    /// A similarly named file or package that exists, written as the specifier would be.
+    pub suggestion: Option<String>,
+    /// What went wrong, as the resolver or a plugin reported it.
+    pub cause: Diagnostic,
 }
 
-fn load_config(config_path: &str) -> Result<Config, String> {
-    let config_file = fs::read_to_string(config_path)
-        .map_err(|err| format!("Failed to read config file: {}", err))?;
-    serde_json::from_str(&config_file).map_err(|err| format!("Failed to parse config file: {}", err))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveErrorKind {
+    /// Nothing exists where the import points.
+    NotFound,
+    /// The package's `exports`, `imports` or `browser` field does not expose it.
+    NotExported,
+    /// A `package.json` or tsconfig is invalid.
+    InvalidConfig,
+    /// It resolves to a file outside the project, its `node_modules` and `resolve.allowed_dirs`.
+    NotAllowed,
+    /// A plugin's resolve hook failed.
+    Plugin,
 }
 
-pub fn resolve_path(base: &str, import_path: &str, plugins: &PluginManager, config_path: &str) -> String {
-    let re = Regex::new(r"(?P<path>[./\w-]+)(?:#(?P<fragment>[\w-]+))?").unwrap();
-    let config = match load_config(config_path) {
-        Ok(cfg) => cfg,
-        Err(err) => {
-            error!("{}", err);
-            return "".to_string();
+impl ResolveErrorKind {
+    fn of(cause: &Diagnostic) -> Self {
+        match cause.code.as_str() {
+            _ if cause.plugin.is_some() => Self::Plugin,
+            "resolve/not-exported" | "resolve/not-imported" | "resolve/browser-disabled" => Self::NotExported,
+            "resolve/package-json" | "resolve/tsconfig" | "resolve/invalid-exports" => Self::InvalidConfig,
+            "resolve/not-allowed" => Self::NotAllowed,
+            _ => Self::NotFound,
         }
-    };
+    }
+}
 
-    let mut resolved_path: PathBuf;
-    let base_path = Path::new(base);
-    let base_dir = base_path.parent().unwrap_or_else(|| Path::new(""));
+/// How many tried paths an error lists.
+const TRIED_SHOWN: usize = 8;
 
-    debug!("Base directory: {:?}", base_dir);
-    debug!("Import path: {}", import_path);
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.importer, self.cause.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+impl From<ResolveError> for Diagnostic {
+    fn from(err: ResolveError) -> Self {
+        let mut diagnostic = err.cause;
+        match &diagnostic.file {
+            None => diagnostic = diagnostic.with_file(&err.importer),
+            Some(file) if *file != Path::new(&err.importer) => {
+                diagnostic = diagnostic.with_note(format!("imported by {}", err.importer));
+            }
+            Some(_) => {}
+        }
+        if !err.tried.is_empty() {
+            let mut tried: Vec<String> = err.tried.iter().take(TRIED_SHOWN).map(|path| path.display().to_string()).collect();
+            if err.tried.len() > TRIED_SHOWN {
+                tried.push(format!("and {} more", err.tried.len() - TRIED_SHOWN));
+            }
+            diagnostic = diagnostic.with_note(format!("tried {}", tried.join(", ")));
+        }
+        match err.suggestion {
+            Some(suggestion) => diagnostic.with_fix(format!("did you mean '{}'?", suggestion)),
+            None => diagnostic,
+        }
+    }
+}
+
+/// Where a bare specifier resolved, if anywhere, and every path looked at on the way.
+type Lookup = (Option<PathBuf>, Vec<PathBuf>);
+
+/// Resolves imports to files the way Node does: paths relative to the importer, bare
+/// specifiers such as `react` or `@scope/pkg/sub` from the `node_modules` directories above it,
+/// through their package's `exports`, and `#internal` specifiers through the importer's package
+/// `imports`. Bare specifiers are first mapped through the `paths` and `baseUrl` of the project's
+/// `tsconfig.json` or `jsconfig.json`, and else through `resolve.paths`. What it reads from disk
+/// is memoized per directory until [`Resolver::clear`].
+///
+/// Imports may only resolve to files in the project directory, the `node_modules` directories
+/// Node would look in, and `resolve.allowed_dirs`, with symlinks followed before checking.
+#[derive(Debug)]
+pub struct Resolver {
+    config: ResolveConfig,
+    /// Matched in `exports` and `imports` besides `default` and `import` or `require`.
+    conditions: Vec<String>,
+    /// `package.json` by directory, `None` where there is none.
+    packages: Mutex<HashMap<PathBuf, Option<Arc<Package>>>>,
+    /// Bare specifiers by the directory they are imported from, with the paths looked at.
+    modules: Mutex<HashMap<(PathBuf, String, ResolveKind), Lookup>>,
+    /// The project's tsconfig once read, `None` inside if there is none or it is invalid.
+    tsconfig: Mutex<Option<Option<Arc<TsConfig>>>>,
+    /// The directories imports may resolve into, canonicalized, once found.
+    roots: Mutex<Option<Arc<Vec<PathBuf>>>>,
+}
+
+/// What a `tsconfig.json` and the files it extends say about resolving imports.
+#[derive(Debug, Default)]
+struct TsConfig {
+    /// `compilerOptions.baseUrl`, relative to the project root.
+    base_url: Option<PathBuf>,
+    /// `compilerOptions.paths`, and the directory of the file setting it, which its targets are
+    /// relative to without a `baseUrl`.
+    paths: Option<(Map<String, Value>, PathBuf)>,
+}
+
+/// A parsed `package.json`.
+#[derive(Debug)]
+struct Package {
+    dir: PathBuf,
+    manifest: Value,
+}
+
+/// What a target in `exports` or `imports` maps an import to.
+#[derive(Debug, PartialEq)]
+enum Target {
+    Path(String),
+    /// Excluded with `null`, which ends the search.
+    Excluded,
+    /// None of its conditions apply, so the search goes on.
+    Unmatched,
+}
+
+impl Target {
+    /// The first of `targets` that is not [`Target::Unmatched`].
+    fn first(mut targets: impl Iterator<Item = Target>) -> Self {
+        targets.find(|target| *target != Target::Unmatched).unwrap_or(Target::Unmatched)
+    }
+}
+
+impl Resolver {
+    pub fn new(config: &ResolveConfig) -> Self {
+        Self {
+            config: config.clone(),
+            conditions: config.conditions.clone(),
+            packages: Mutex::default(),
+            modules: Mutex::default(),
+            tsconfig: Mutex::default(),
+            roots: Mutex::default(),
+        }
+    }
+
+    /// Also matches `condition` in `exports` and `imports`, e.g. the mode.
+    pub fn with_condition(mut self, condition: &str) -> Self {
+        self.conditions.push(condition.to_string());
+        self
+    }
+
+    pub fn config(&self) -> &ResolveConfig {
+        &self.config
+    }
+
+    /// Forgets what was read from disk, so the next build sees packages installed since.
+    pub fn clear(&self) {
+        self.packages.lock().unwrap().clear();
+        self.modules.lock().unwrap().clear();
+        *self.tsconfig.lock().unwrap() = None;
+        *self.roots.lock().unwrap() = None;
+    }
+
+    /// Reads `resolve.tsconfig`, or else the `tsconfig.json` or `jsconfig.json` in the project
+    /// root, with the files it extends. Resolving ignores an invalid one, so builds report it
+    /// by calling this first.
+    pub fn load_tsconfig(&self) -> Result<(), Diagnostic> {
+        let path = match &self.config.tsconfig {
+            Some(path) => Some(path.clone()),
+            None => ["tsconfig.json", "jsconfig.json"].iter().map(PathBuf::from).find(|path| path.is_file()),
+        };
+        let (tsconfig, result) = match path.map(|path| read_tsconfig(&path, &mut vec![])).transpose() {
+            Ok(tsconfig) => (tsconfig.map(Arc::new), Ok(())),
+            Err(diagnostic) => (None, Err(diagnostic)),
+        };
+        *self.tsconfig.lock().unwrap() = Some(tsconfig);
+        result
+    }
+
+    fn tsconfig(&self) -> Option<Arc<TsConfig>> {
+        if self.tsconfig.lock().unwrap().is_none() {
+            if let Err(diagnostic) = self.load_tsconfig() {
+                warn!("Ignoring {}", diagnostic);
+            }
+        }
+        self.tsconfig.lock().unwrap().clone().flatten()
+    }
 
-    if let Some(new_path) = plugins.resolve(import_path) {
-        info!("Resolved path via plugins: {}", new_path);
-        return new_path;
+    /// Maps a bare specifier through the tsconfig's `paths`, trying each target of the matching
+    /// pattern in order, and then relative to its `baseUrl`.
+    fn resolve_tsconfig_paths(&self, specifier: &str, tried: &mut Vec<PathBuf>) -> Result<Option<PathBuf>, Diagnostic> {
+        let Some(tsconfig) = self.tsconfig() else {
+            return Ok(None);
+        };
+        if let Some((paths, paths_dir)) = &tsconfig.paths {
+            if let Some((targets, matched)) = match_subpath(paths, specifier) {
+                let root = tsconfig.base_url.as_deref().unwrap_or(paths_dir);
+                for target in targets.as_array().into_iter().flatten().filter_map(Value::as_str) {
+                    let path = normalize_path(&root.join(target.replace('*', &matched)));
+                    if let Some(resolved) = self.resolve_file_or_dir(&path, tried)? {
+                        info!("Resolved '{}' via tsconfig paths: {:?}", specifier, resolved);
+                        return Ok(Some(resolved));
+                    }
+                }
+            }
+        }
+        match &tsconfig.base_url {
+            Some(base_url) => self.resolve_file_or_dir(&normalize_path(&base_url.join(specifier)), tried),
+            None => Ok(None),
+        }
     }
 
-    if let Some(caps) = re.captures(import_path) {
-        let path_match = caps.name("path").map_or("", |m| m.as_str());
-        let fragment_match = caps.name("fragment").map_or("", |m| m.as_str());
+    /// Resolves `import_path` as imported from the module `base` to a file, without asking plugins.
+    pub fn resolve(&self, base: &str, import_path: &str, kind: ResolveKind) -> Result<Resolved, ResolveError> {
+        let mut tried = vec![];
+        match self.lookup(base, import_path, kind, &mut tried) {
+            Ok(path) => Ok(Resolved { id: path.to_string_lossy().to_string() }),
+            Err(cause) => {
+                let kind = ResolveErrorKind::of(&cause);
+                let suggestion = match kind {
+                    ResolveErrorKind::NotFound => self.suggest(import_path, &tried),
+                    _ => None,
+                };
+                Err(ResolveError {
+                    kind,
+                    specifier: import_path.to_string(),
+                    importer: base.to_string(),
+                    tried,
+                    suggestion,
+                    cause,
+                })
+            }
+        }
+    }
 
-        resolved_path = if path_match.starts_with("./") || path_match.starts_with("../") {
-            base_dir.join(path_match)
+    fn lookup(&self, base: &str, import_path: &str, kind: ResolveKind, tried: &mut Vec<PathBuf>) -> Result<PathBuf, Diagnostic> {
+        let specifier = import_path;
+        // Relative imports in virtual modules resolve against the project root.
+        let base_dir = if plugin::is_virtual(base) {
+            Path::new("")
         } else {
-            PathBuf::from(path_match)
+            Path::new(base).parent().unwrap_or_else(|| Path::new(""))
         };
+        debug!("Base directory: {:?}", base_dir);
 
-        if !fragment_match.is_empty() {
-            let mut new_path = resolved_path.clone();
-            new_path.set_extension(format!("{}.ext", fragment_match));
-            resolved_path = new_path;
+        let mapped = match is_path(import_path) || import_path.starts_with('#') {
+            true => None,
+            false => self.resolve_tsconfig_paths(import_path, tried)?,
+        };
+        let aliased_path = apply_path_aliases(import_path, &self.config);
+        let aliased = aliased_path != import_path;
+        let import_path = aliased_path.as_str();
+
+        let with_importer = |diagnostic: Diagnostic| match diagnostic.file {
+            Some(_) => diagnostic,
+            None => diagnostic.with_file(base),
+        };
+        let package = !aliased && !is_path(import_path);
+        let not_found = || {
+            let diagnostic = Diagnostic::error("resolve/not-found", format!("Unable to resolve import '{}'", specifier))
+                .with_file(base);
+            if !package {
+                return diagnostic;
+            }
+            let from = match base_dir.to_string_lossy() {
+                dir if dir.is_empty() => "the project root".into(),
+                dir => dir,
+            };
+            diagnostic
+                .with_note(format!("looked for package '{}' in node_modules from {} up", package_name(import_path).0, from))
+                .with_fix(format!("install it with `npm install {}`", package_name(import_path).0))
+        };
+        let mut resolved_path = if let Some(mapped) = mapped {
+            mapped
+        } else if package && import_path.starts_with('#') {
+            self.resolve_imports(base_dir, import_path, kind, tried).map_err(with_importer)?
+        } else if package {
+            self.resolve_package(base_dir, import_path, kind, tried).map_err(with_importer)?.ok_or_else(not_found)?
+        } else {
+            self.resolve_relative(base_dir, import_path, tried)?
+        };
+        if self.targets_browser() {
+            if let Some(replacement) = self.browser_file(&resolved_path)? {
+                resolved_path = replacement;
+            }
         }
 
-        if resolved_path.to_str().unwrap_or("").contains("..") {
-            let normalized_path = resolved_path.canonicalize().unwrap_or_else(|_| resolved_path.clone());
-            resolved_path = normalized_path;
+        if !resolved_path.is_file() {
+            debug!("Path {} does not exist", resolved_path.display());
+            return Err(not_found());
         }
+        self.check_roots(&resolved_path, specifier).map_err(with_importer)?;
+
+        Ok(resolved_path)
+    }
 
-        if !resolved_path.exists() {
-            if let Some(alternative_path) = try_alternate_resolutions(&resolved_path, &config) {
-                resolved_path = alternative_path;
+    /// Resolves a path relative to `base_dir`, or to the project root once aliased. Returns
+    /// the path as written when nothing matches.
+    fn resolve_relative(&self, base_dir: &Path, import_path: &str, tried: &mut Vec<PathBuf>) -> Result<PathBuf, Diagnostic> {
+        let in_base = |path: &str| match is_path(path) {
+            true => normalize_path(&base_dir.join(path)),
+            false => normalize_path(Path::new(path)),
+        };
+        let resolved_path = in_base(import_path);
+        if let Some(resolved) = self.resolve_file_or_dir(&resolved_path, tried)? {
+            return Ok(resolved);
+        }
+        // A `?query` or `#hash` suffix, as in `./icon.svg?raw`, is not part of the file name
+        // unless a file is named that way.
+        match import_path.find(['?', '#']) {
+            Some(end) if end > 0 => {
+                let stripped = in_base(&import_path[..end]);
+                Ok(self.resolve_file_or_dir(&stripped, tried)?.unwrap_or(resolved_path))
             }
+            _ => Ok(resolved_path),
         }
-    } else {
-        resolved_path = base_dir.join(import_path);
     }
 
-    if let Some(env_path) = load_env_variable_path("RESOLVED_PATH") {
-        info!("Path resolved via environment variable: {:?}", env_path);
-        resolved_path = env_path;
+    /// Resolves a bare specifier from the `node_modules` directories in `base_dir` and above it,
+    /// nearest first, after the importing package's `browser` replacements.
+    fn resolve_package(
+        &self,
+        base_dir: &Path,
+        specifier: &str,
+        kind: ResolveKind,
+        tried: &mut Vec<PathBuf>,
+    ) -> Result<Option<PathBuf>, Diagnostic> {
+        let key = (base_dir.to_path_buf(), specifier.to_string(), kind);
+        if let Some((resolved, looked_at)) = self.modules.lock().unwrap().get(&key) {
+            tried.extend(looked_at.iter().cloned());
+            return Ok(resolved.clone());
+        }
+
+        let mut looked_at = vec![];
+        let replaced = match self.targets_browser() {
+            true => self.browser_replacement(base_dir, kind, &mut looked_at, |_, key| key == specifier)?,
+            false => None,
+        };
+        let resolved = match replaced {
+            Some(resolved) => Some(resolved),
+            None => self.find_in_node_modules(base_dir, specifier, kind, &mut looked_at)?,
+        };
+        tried.extend(looked_at.iter().cloned());
+        self.modules.lock().unwrap().insert(key, (resolved.clone(), looked_at));
+        Ok(resolved)
+    }
+
+    fn find_in_node_modules(
+        &self,
+        base_dir: &Path,
+        specifier: &str,
+        kind: ResolveKind,
+        tried: &mut Vec<PathBuf>,
+    ) -> Result<Option<PathBuf>, Diagnostic> {
+        let (name, subpath) = package_name(specifier);
+        for dir in base_dir.ancestors() {
+            if dir.file_name().is_some_and(|dir_name| dir_name == "node_modules") {
+                continue;
+            }
+            let package_dir = dir.join("node_modules").join(name);
+            if !package_dir.is_dir() {
+                tried.push(package_dir);
+                continue;
+            }
+            // A package with `exports` exposes nothing else.
+            if let Some(package) = self.package(&package_dir)?.filter(|package| package.manifest.get("exports").is_some()) {
+                return self.resolve_exports(&package, name, subpath, kind, tried).map(Some);
+            }
+            let resolved = match subpath {
+                Some(subpath) => self.resolve_file_or_dir(&normalize_path(&package_dir.join(subpath)), tried)?,
+                None => self.resolve_dir(&package_dir, tried)?,
+            };
+            if resolved.is_some() {
+                info!("Resolved '{}' in {}", specifier, package_dir.display());
+                return Ok(resolved);
+            }
+        }
+        Ok(None)
+    }
+
+    fn resolve_file_or_dir(&self, path: &Path, tried: &mut Vec<PathBuf>) -> Result<Option<PathBuf>, Diagnostic> {
+        match self.resolve_file(path, tried) {
+            Some(file) => Ok(Some(file)),
+            None => self.resolve_dir(path, tried),
+        }
+    }
+
+    /// `path` itself, or else `path` with the first of the configured extensions that exists.
+    fn resolve_file(&self, path: &Path, tried: &mut Vec<PathBuf>) -> Option<PathBuf> {
+        tried.push(path.to_path_buf());
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        self.config.extensions.iter().find_map(|ext| {
+            let mut candidate = path.as_os_str().to_owned();
+            candidate.push(format!(".{}", ext));
+            let candidate = PathBuf::from(candidate);
+            tried.push(candidate.clone());
+            candidate.is_file().then(|| {
+                info!("Resolved path with alternate extension: {:?}", candidate);
+                candidate
+            })
+        })
+    }
+
+    /// The entry named by the first of `main_fields` in the directory's `package.json`, or
+    /// else its `index` file.
+    fn resolve_dir(&self, dir: &Path, tried: &mut Vec<PathBuf>) -> Result<Option<PathBuf>, Diagnostic> {
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        if let Some(package) = self.package(dir)? {
+            for field in &self.config.main_fields {
+                let Some(Value::String(entry)) = package.manifest.get(field) else {
+                    continue;
+                };
+                let entry = normalize_path(&dir.join(entry));
+                if let Some(resolved) = self.resolve_file(&entry, tried).or_else(|| self.resolve_index(&entry, tried)) {
+                    return Ok(Some(resolved));
+                }
+            }
+        }
+        Ok(self.resolve_index(dir, tried))
+    }
+
+    fn resolve_index(&self, dir: &Path, tried: &mut Vec<PathBuf>) -> Option<PathBuf> {
+        self.config.extensions.iter().map(|ext| dir.join(format!("index.{}", ext))).find(|index| {
+            tried.push(index.clone());
+            index.is_file()
+        })
+    }
+
+    fn targets_browser(&self) -> bool {
+        self.config.main_fields.iter().any(|field| field == "browser")
+    }
+
+    /// What the `browser` field of the package `file` belongs to replaces it with, if anything.
+    fn browser_file(&self, file: &Path) -> Result<Option<PathBuf>, Diagnostic> {
+        let Some(dir) = file.parent() else {
+            return Ok(None);
+        };
+        self.browser_replacement(dir, ResolveKind::Import, &mut vec![], |package, key| {
+            let key_file = self.resolve_file(&normalize_path(&package.dir.join(key)), &mut vec![]);
+            key.starts_with('.') && key_file.as_deref() == Some(file)
+        })
     }
 
-    if !verify_path_security(&resolved_path) {
-        error!("Path {} is not secure or does not exist", resolved_path.display());
-        return "".to_string();
+    /// Follows the first entry `matches` picks in the object form of the `browser` field of
+    /// the nearest `package.json` in or above `dir`, e.g. `{"./server.js": "./client.js"}`.
+    fn browser_replacement(
+        &self,
+        dir: &Path,
+        kind: ResolveKind,
+        tried: &mut Vec<PathBuf>,
+        matches: impl Fn(&Package, &str) -> bool,
+    ) -> Result<Option<PathBuf>, Diagnostic> {
+        let Some(package) = self.nearest_package(dir)? else {
+            return Ok(None);
+        };
+        let Some(Value::Object(replacements)) = package.manifest.get("browser") else {
+            return Ok(None);
+        };
+        let Some((key, replacement)) = replacements.iter().find(|(key, _)| matches(&package, key)) else {
+            return Ok(None);
+        };
+        match replacement {
+            Value::String(replacement) if is_path(replacement) => {
+                self.resolve_file_or_dir(&normalize_path(&package.dir.join(replacement)), tried)
+            }
+            Value::String(replacement) => self.find_in_node_modules(&package.dir, replacement, kind, tried),
+            _ => Err(Diagnostic::error("resolve/browser-disabled", format!("'{}' is not available in the browser", key))
+                .with_file(package.dir.join("package.json"))
+                .with_note("its `browser` field maps it to false")),
+        }
     }
 
-    resolved_path.to_str().unwrap_or("").to_string()
+    /// Resolves `pkg/subpath` through the package's `exports`, e.g.
+    /// `{".": {"import": "./index.mjs", "require": "./index.cjs"}, "./utils/*": "./lib/*.js"}`.
+    fn resolve_exports(
+        &self,
+        package: &Package,
+        name: &str,
+        subpath: Option<&str>,
+        kind: ResolveKind,
+        tried: &mut Vec<PathBuf>,
+    ) -> Result<PathBuf, Diagnostic> {
+        let subpath = subpath.map_or_else(|| ".".to_string(), |subpath| format!("./{}", subpath));
+        let exports = &package.manifest["exports"];
+        // A string, array or conditions object is shorthand for the export of `.`.
+        let shorthand;
+        let exports = match exports {
+            Value::Object(exports) if exports.keys().all(|key| key.starts_with('.')) => exports,
+            _ => {
+                shorthand = Map::from_iter([(".".to_string(), exports.clone())]);
+                &shorthand
+            }
+        };
+        let manifest_path = package.dir.join("package.json");
+        let Some((target, matched)) = match_subpath(exports, &subpath) else {
+            let exported: Vec<&str> = exports.keys().map(String::as_str).collect();
+            return Err(Diagnostic::error(
+                "resolve/not-exported",
+                format!("Package subpath '{}' is not exported by '{}'", subpath, name),
+            )
+            .with_note(format!("{} exports {}", manifest_path.display(), exported.join(", ")))
+            .with_fix(match exported.first() {
+                Some(first) => format!("import one of its exports, e.g. '{}{}'", name, &first[1..]),
+                None => "the package exports nothing".to_string(),
+            }));
+        };
+        let target = match self.resolve_target(target, &matched, kind) {
+            Target::Path(path) => path,
+            // As in Node, a subpath mapped to `null` is not exported at all.
+            Target::Excluded => {
+                return Err(Diagnostic::error(
+                    "resolve/not-exported",
+                    format!("Package subpath '{}' is not exported by '{}'", subpath, name),
+                )
+                .with_note(format!("{} excludes it with null", manifest_path.display())));
+            }
+            Target::Unmatched => {
+                return Err(self.no_condition(target, kind, format!("'{}{}'", name, &subpath[1..]), &manifest_path));
+            }
+        };
+        if !target.starts_with("./") {
+            return Err(Diagnostic::error(
+                "resolve/invalid-exports",
+                format!("Invalid export target '{}' for '{}' in '{}'", target, subpath, name),
+            )
+            .with_file(manifest_path)
+            .with_note("export targets are paths starting with './'"));
+        }
+        self.exported_file(&package.dir, &target, &format!("{}{}", name, &subpath[1..]), tried)
+    }
+
+    /// Resolves `#internal` through the `imports` of the nearest `package.json` above `base_dir`.
+    fn resolve_imports(
+        &self,
+        base_dir: &Path,
+        specifier: &str,
+        kind: ResolveKind,
+        tried: &mut Vec<PathBuf>,
+    ) -> Result<PathBuf, Diagnostic> {
+        let package = self.nearest_package(base_dir)?;
+        let imports = package.as_ref().and_then(|package| package.manifest.get("imports")?.as_object().map(|imports| (package, imports)));
+        let Some((package, imports)) = imports else {
+            return Err(Diagnostic::error("resolve/not-imported", format!("Unable to resolve import '{}'", specifier))
+                .with_note("specifiers starting with '#' are mapped by the `imports` field of the nearest package.json, and there is none"));
+        };
+        let manifest_path = package.dir.join("package.json");
+        let Some((target, matched)) = match_subpath(imports, specifier) else {
+            let defined: Vec<&str> = imports.keys().map(String::as_str).collect();
+            return Err(Diagnostic::error(
+                "resolve/not-imported",
+                format!("'{}' is not defined in the `imports` of {}", specifier, manifest_path.display()),
+            )
+            .with_note(format!("it defines {}", defined.join(", "))));
+        };
+        let target = match self.resolve_target(target, &matched, kind) {
+            Target::Path(path) => path,
+            Target::Excluded => {
+                return Err(Diagnostic::error(
+                    "resolve/not-imported",
+                    format!("'{}' is not defined in the `imports` of {}", specifier, manifest_path.display()),
+                )
+                .with_note(format!("{} excludes it with null", manifest_path.display())));
+            }
+            Target::Unmatched => {
+                return Err(self.no_condition(target, kind, format!("'{}'", specifier), &manifest_path));
+            }
+        };
+        if target.starts_with("./") {
+            return self.exported_file(&package.dir, &target, specifier, tried);
+        }
+        self.find_in_node_modules(&package.dir, &target, kind, tried)?.ok_or_else(|| {
+            Diagnostic::error("resolve/not-found", format!("Unable to resolve import '{}'", specifier))
+                .with_note(format!("it is mapped to package '{}', which is not installed", target))
+        })
+    }
+
+    /// The file `target` of the package in `dir` names, which must exist as written.
+    fn exported_file(&self, dir: &Path, target: &str, specifier: &str, tried: &mut Vec<PathBuf>) -> Result<PathBuf, Diagnostic> {
+        let path = normalize_path(&dir.join(target));
+        tried.push(path.clone());
+        if !path.is_file() {
+            return Err(Diagnostic::error("resolve/not-found", format!("Unable to resolve import '{}'", specifier))
+                .with_note(format!("it is mapped to {}, which does not exist", path.display())));
+        }
+        Ok(path)
+    }
+
+    /// Picks what `target` maps to for an import of `kind`, with `matched` in place of `*`. The
+    /// first alternative or applying condition that is a path or `null` decides.
+    fn resolve_target(&self, target: &Value, matched: &str, kind: ResolveKind) -> Target {
+        match target {
+            Value::String(target) => Target::Path(target.replace('*', matched)),
+            Value::Null => Target::Excluded,
+            Value::Array(alternatives) => {
+                Target::first(alternatives.iter().map(|alternative| self.resolve_target(alternative, matched, kind)))
+            }
+            Value::Object(conditions) => Target::first(
+                conditions
+                    .iter()
+                    .filter(|(condition, _)| self.condition_applies(condition, kind))
+                    .map(|(_, target)| self.resolve_target(target, matched, kind)),
+            ),
+            _ => Target::Unmatched,
+        }
+    }
+
+    /// Whether `condition` in `exports` or `imports` applies to an import of `kind`.
+    fn condition_applies(&self, condition: &str, kind: ResolveKind) -> bool {
+        match condition {
+            "default" => true,
+            "import" => kind != ResolveKind::Require,
+            "require" => kind == ResolveKind::Require,
+            _ => self.conditions.iter().any(|active| active == condition),
+        }
+    }
+
+    fn no_condition(&self, target: &Value, kind: ResolveKind, what: String, manifest_path: &Path) -> Diagnostic {
+        let mut active = vec!["default", if kind == ResolveKind::Require { "require" } else { "import" }];
+        active.extend(self.conditions.iter().map(String::as_str));
+        let diagnostic = Diagnostic::error("resolve/not-exported", format!("No condition matches {}", what))
+            .with_note(format!("this build matches the conditions {}", active.join(", ")));
+        match target {
+            Value::Object(conditions) => diagnostic
+                .with_note(format!(
+                    "{} maps it for {}",
+                    manifest_path.display(),
+                    conditions.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+                ))
+                .with_fix("add one of them to `resolve.conditions`"),
+            _ => diagnostic.with_note(format!("{} excludes it", manifest_path.display())),
+        }
+    }
+
+    /// The project directory, every `node_modules` directory above it and `resolve.allowed_dirs`,
+    /// with symlinks resolved. Directories that do not exist are left out.
+    fn roots(&self) -> Arc<Vec<PathBuf>> {
+        self.roots
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                let project = fs::canonicalize(".").unwrap_or_default();
+                let node_modules = project.ancestors().map(|dir| dir.join("node_modules"));
+                let dirs = std::iter::once(project.clone()).chain(node_modules).chain(self.config.allowed_dirs.iter().cloned());
+                Arc::new(dirs.filter_map(|dir| fs::canonicalize(dir).ok()).collect())
+            })
+            .clone()
+    }
+
+    /// Fails unless the file `path` really is in one of the [`roots`](Self::roots), so neither a
+    /// `../` nor a symlink can pull in files from elsewhere on the machine.
+    fn check_roots(&self, path: &Path, specifier: &str) -> Result<(), Diagnostic> {
+        let real = real_path(path);
+        let roots = self.roots();
+        if roots.iter().any(|root| real.starts_with(root)) {
+            return Ok(());
+        }
+        let mut diagnostic = Diagnostic::error(
+            "resolve/not-allowed",
+            format!("Import '{}' resolves to {}, outside the project", specifier, real.display()),
+        );
+        if let Some(link) = std::path::absolute(path).ok().map(|path| normalize_path(&path)).filter(|path| *path != real) {
+            diagnostic = diagnostic.with_note(format!("{} is a symlink to it", link.display()));
+        }
+        let roots: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+        Err(diagnostic
+            .with_note(format!("imports may resolve into {}", roots.join(", ")))
+            .with_fix("if it is meant to be bundled, add its directory to `resolve.allowed_dirs`"))
+    }
+
+    /// The closest name to what `import_path` asked for next to where it was looked for:
+    /// another file in the same directory, or another installed package.
+    fn suggest(&self, import_path: &str, tried: &[PathBuf]) -> Option<String> {
+        if import_path.starts_with('#') {
+            return None;
+        }
+        if !is_path(import_path) {
+            let (name, _) = package_name(import_path);
+            let depth = name.split('/').count();
+            let installed: Vec<String> = tried
+                .iter()
+                .filter(|path| path.ends_with(name))
+                .filter_map(|path| path.ancestors().nth(depth))
+                .flat_map(|node_modules| {
+                    list_dir(node_modules).into_iter().flat_map(move |(entry, _)| match entry.starts_with('@') {
+                        true => list_dir(&node_modules.join(&entry))
+                            .into_iter()
+                            .map(|(scoped, _)| format!("{}/{}", entry, scoped))
+                            .collect(),
+                        false => vec![entry],
+                    })
+                })
+                .collect();
+            let closest = closest(name, installed.iter().map(|installed| (installed.as_str(), installed.as_str())))?;
+            return Some(import_path.replacen(name, closest, 1));
+        }
+
+        let wanted_path = tried.first()?;
+        let wanted = wanted_path.file_name()?.to_str()?;
+        let prefix = import_path.strip_suffix(wanted)?;
+        let wanted_extension = Path::new(wanted).extension().is_some();
+        let entries: Vec<(String, String)> = list_dir(wanted_path.parent()?)
+            .into_iter()
+            .map(|(entry, is_dir)| {
+                let path = Path::new(&entry);
+                let resolvable = path
+                    .extension()
+                    .is_some_and(|ext| self.config.extensions.iter().any(|known| ext == known.as_str()));
+                // Written the way it would be imported: without an extension the resolver adds.
+                let written = match is_dir || wanted_extension || !resolvable {
+                    true => entry.clone(),
+                    false => path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+                };
+                (written, entry)
+            })
+            .collect();
+        let wanted_stem = Path::new(wanted).file_stem()?.to_str()?;
+        let stems: Vec<(String, &str)> = entries
+            .iter()
+            .filter(|(written, _)| written != wanted)
+            .map(|(written, entry)| {
+                (Path::new(entry).file_stem().unwrap_or_default().to_string_lossy().to_string(), written.as_str())
+            })
+            .collect();
+        let closest = closest(wanted_stem, stems.iter().map(|(stem, written)| (stem.as_str(), *written)))?;
+        Some(format!("{}{}", prefix, closest))
+    }
+
+    fn nearest_package(&self, dir: &Path) -> Result<Option<Arc<Package>>, Diagnostic> {
+        for dir in dir.ancestors() {
+            if let Some(package) = self.package(dir)? {
+                return Ok(Some(package));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The `package.json` in `dir`, read once.
+    fn package(&self, dir: &Path) -> Result<Option<Arc<Package>>, Diagnostic> {
+        if let Some(package) = self.packages.lock().unwrap().get(dir) {
+            return Ok(package.clone());
+        }
+        let path = dir.join("package.json");
+        let package = match fs::read_to_string(&path) {
+            Ok(source) => {
+                let manifest = serde_json::from_str(&source).map_err(|err| {
+                    let (line, column) = (err.line(), err.column().max(1));
+                    Diagnostic::error("resolve/package-json", format!("Invalid package.json: {}", err))
+                        .with_file(&path)
+                        .with_span(Span::new(line, column, line, column + 1))
+                })?;
+                Some(Arc::new(Package { dir: dir.to_path_buf(), manifest }))
+            }
+            Err(_) => None,
+        };
+        self.packages.lock().unwrap().insert(dir.to_path_buf(), package.clone());
+        Ok(package)
+    }
 }
 
-fn try_alternate_resolutions(path: &PathBuf, config: &Config) -> Option<PathBuf> {
-    let extensions = config.extensions.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-    for ext in &extensions {
-        let mut new_path = path.clone();
-        new_path.set_extension(ext);
-        if new_path.exists() {
-            info!("Resolved path with alternate extension: {:?}", new_path);
-            return Some(new_path);
+/// `path` made absolute with symlinks resolved. Where it does not exist yet, the symlinks in the
+/// part that does are resolved and the rest is kept as written.
+fn real_path(path: &Path) -> PathBuf {
+    if let Ok(real) = fs::canonicalize(path) {
+        return real;
+    }
+    let absolute = normalize_path(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    for dir in absolute.ancestors() {
+        if let Ok(real) = fs::canonicalize(dir) {
+            return real.join(absolute.strip_prefix(dir).unwrap_or(Path::new("")));
         }
     }
-    None
+    absolute
+}
+
+/// The names in `dir` with whether each is a directory, sorted; empty if it cannot be read.
+fn list_dir(dir: &Path) -> Vec<(String, bool)> {
+    let mut entries: Vec<(String, bool)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path().is_dir()))
+        .collect();
+    entries.sort();
+    entries
 }
 
-fn load_env_variable_path(variable_name: &str) -> Option<PathBuf> {
-    env::var(variable_name).ok().map(PathBuf::from)
+/// The value of the candidate whose key is closest to `wanted`, if any is close enough to be a
+/// likely typo. Candidates are `(key, value)` pairs; the first wins a tie.
+fn closest<'a>(wanted: &str, candidates: impl Iterator<Item = (&'a str, &'a str)>) -> Option<&'a str> {
+    let threshold = (wanted.chars().count() / 3).max(1);
+    candidates
+        .map(|(key, value)| (strsim::damerau_levenshtein(wanted, key), value))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, value)| value)
 }
 
-fn verify_path_security(path: &Path) -> bool {
-    if let Ok(metadata) = fs::metadata(path) {
-        metadata.is_file() && !metadata.permissions().readonly()
+/// Reads the tsconfig at `path`, after the files it `extends`. `extending` are the files being
+/// read that extend it, to catch cycles.
+fn read_tsconfig(path: &Path, extending: &mut Vec<PathBuf>) -> Result<TsConfig, Diagnostic> {
+    let invalid = |message: String| Diagnostic::error("resolve/tsconfig", message).with_file(path);
+    if extending.iter().any(|extended| extended == path) {
+        return Err(invalid(format!("{} extends itself", path.display())));
+    }
+    let source = fs::read_to_string(path).map_err(|err| invalid(format!("Unable to read tsconfig: {}", err)))?;
+    let json: Value = serde_json::from_str(&strip_jsonc(&source)).map_err(|err| {
+        let (line, column) = (err.line(), err.column().max(1));
+        invalid(format!("Invalid tsconfig: {}", err)).with_span(Span::new(line, column, line, column + 1))
+    })?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut tsconfig = TsConfig::default();
+    let extends = match json.get("extends") {
+        Some(Value::String(extended)) => vec![extended.as_str()],
+        Some(Value::Array(extended)) => extended.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    extending.push(path.to_path_buf());
+    for extended in extends {
+        let extended_path = find_extended_tsconfig(dir, extended)
+            .ok_or_else(|| invalid(format!("Unable to find '{}', which {} extends", extended, path.display())))?;
+        let base = read_tsconfig(&extended_path, extending)?;
+        tsconfig.base_url = base.base_url.or(tsconfig.base_url);
+        tsconfig.paths = base.paths.or(tsconfig.paths);
+    }
+    extending.pop();
+
+    let options = json.get("compilerOptions");
+    if let Some(base_url) = options.and_then(|options| options.get("baseUrl")).and_then(Value::as_str) {
+        tsconfig.base_url = Some(normalize_path(&dir.join(base_url)));
+    }
+    if let Some(paths) = options.and_then(|options| options.get("paths")).and_then(Value::as_object) {
+        tsconfig.paths = Some((paths.clone(), dir.to_path_buf()));
+    }
+    Ok(tsconfig)
+}
+
+/// The file `extends` names from the tsconfig in `dir`: a path, or a file in a package such as
+/// `@tsconfig/node20/tsconfig.json`, with or without `.json`, or a package's own `tsconfig.json`.
+fn find_extended_tsconfig(dir: &Path, extended: &str) -> Option<PathBuf> {
+    let candidates: Vec<PathBuf> = if is_path(extended) {
+        vec![normalize_path(&dir.join(extended))]
     } else {
-        false
+        dir.ancestors().map(|dir| dir.join("node_modules").join(extended)).collect()
+    };
+    candidates.into_iter().find_map(|candidate| {
+        let with_extension = PathBuf::from(format!("{}.json", candidate.display()));
+        [candidate.clone(), with_extension, candidate.join("tsconfig.json")].into_iter().find(|path| path.is_file())
+    })
+}
+
+/// `source` with the comments and trailing commas tsconfig files allow blanked out, keeping
+/// every line and column where it was.
+fn strip_jsonc(source: &str) -> String {
+    let mut bytes = source.as_bytes().to_vec();
+    let mut in_string = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (in_string, bytes[i], bytes.get(i + 1)) {
+            (true, b'\\', _) => i += 1,
+            (_, b'"', _) => in_string = !in_string,
+            (false, b'/', Some(b'/')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    bytes[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            }
+            (false, b'/', Some(b'*')) => {
+                let end = source[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 2);
+                for byte in &mut bytes[i..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    // Commas are only known to trail once the comments after them are gone.
+    in_string = false;
+    i = 0;
+    while i < bytes.len() {
+        match (in_string, bytes[i]) {
+            (true, b'\\') => i += 1,
+            (_, b'"') => in_string = !in_string,
+            (false, b',') => {
+                if matches!(bytes[i + 1..].iter().find(|byte| !byte.is_ascii_whitespace()), Some(b'}' | b']')) {
+                    bytes[i] = b' ';
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Whether `specifier` is a path rather than a package name.
+fn is_path(specifier: &str) -> bool {
+    specifier == "."
+        || specifier == ".."
+        || specifier.starts_with("./")
+        || specifier.starts_with("../")
+        || Path::new(specifier).is_absolute()
+}
+
+/// Looks `subpath` up in an `exports` or `imports` map: an exact key, or else the pattern with
+/// the longest prefix, like `./features/*.js`. Returns its target and what `*` matched.
+fn match_subpath<'a>(map: &'a Map<String, Value>, subpath: &str) -> Option<(&'a Value, String)> {
+    if let Some(target) = map.get(subpath).filter(|_| !subpath.contains('*')) {
+        return Some((target, String::new()));
+    }
+    map.iter()
+        .filter_map(|(key, target)| {
+            let (prefix, suffix) = key.split_once('*')?;
+            let matched = subpath.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            (subpath.len() >= key.len()).then(|| (prefix.len(), key.len(), target, matched.to_string()))
+        })
+        .max_by_key(|(prefix_len, key_len, ..)| (*prefix_len, *key_len))
+        .map(|(.., target, matched)| (target, matched))
+}
+
+/// Splits `@scope/pkg/sub/path` into `@scope/pkg` and `sub/path`.
+fn package_name(specifier: &str) -> (&str, Option<&str>) {
+    let name_end = match specifier.strip_prefix('@') {
+        Some(scoped) => scoped.find('/').and_then(|scope_end| {
+            scoped[scope_end + 1..].find('/').map(|name_end| scope_end + name_end + 2)
+        }),
+        None => specifier.find('/'),
+    };
+    match name_end {
+        Some(end) => (&specifier[..end], Some(&specifier[end + 1..]).filter(|subpath| !subpath.is_empty())),
+        None => (specifier, None),
     }
 }
 
-fn main() {
-    env_logger::init();
-    let plugins = PluginManager;
-    let base = "../main.rs";
-    let import_path = "./utils#fragment";
-    let config_path = "config.json";
-    
-    let resolved_path = resolve_path(base, import_path, &plugins, config_path);
-    println!("Resolved Path: {}", resolved_path);
-}
\ No newline at end of file
+/// Rewrites the longest `paths` prefix matching `import_path`, e.g. `@app/` => `./src/`.
+/// Targets are relative to the project root rather than to the importing file.
+fn apply_path_aliases(import_path: &str, config: &ResolveConfig) -> String {
+    config
+        .paths
+        .iter()
+        .filter(|(prefix, _)| import_path.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(prefix, target)| {
+            format!("{}{}", target.trim_start_matches("./"), &import_path[prefix.len()..])
+        })
+        .unwrap_or_else(|| import_path.to_string())
+}
+
+/// Lexically removes `.` segments and folds `..` into their parent so the same
+/// file always resolves to the same path string, wherever it is imported from.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// Sends every import to a fixed file.
+    struct Redirect(String);
+
+    impl plugin::Plugin for Redirect {
+        fn name(&self) -> &str {
+            "redirect"
+        }
+        fn resolve_import(&self, _ctx: &mut PluginContext, _args: &ResolveArgs) -> plugin::HookResult<plugin::ResolveResult> {
+            Ok(Some(plugin::ResolveResult { path: self.0.clone(), namespace: None }))
+        }
+    }
+
+    #[test]
+    fn resolves_packages_from_node_modules() {
+        let root = TempDir::new("resolver");
+        let files = [
+            ("src/a.js", ""),
+            ("src/utils/index.ts", ""),
+            ("src/icon.svg", ""),
+            ("src/c#.js", ""),
+            ("node_modules/pkg/package.json", r#"{"main": "cjs.js", "module": "esm"}"#),
+            ("node_modules/pkg/esm.js", ""),
+            ("node_modules/@scope/lib/sub/index.js", ""),
+            ("node_modules/shim/package.json", r#"{"main": "server.js", "browser": {"./server.js": "./client.js"}}"#),
+            ("node_modules/shim/server.js", ""),
+            ("node_modules/shim/client.js", ""),
+        ];
+        root.write(&files);
+        // Read-only, as in a Nix store.
+        let mut permissions = fs::metadata(root.join("node_modules/pkg/esm.js")).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(root.join("node_modules/pkg/esm.js"), permissions).unwrap();
+        let resolver = Resolver::new(&ResolveConfig { allowed_dirs: vec![root.to_path_buf()], ..Default::default() });
+        let importer = root.join("src/a.js").to_string_lossy().to_string();
+        let resolve = |specifier| resolver.resolve(&importer, specifier, ResolveKind::Import).map(|resolved| resolved.id.replace(&*root.to_string_lossy(), ""));
+
+        assert_eq!(resolve("./utils").unwrap(), "/src/utils/index.ts");
+        assert_eq!(resolve("./icon.svg?raw").unwrap(), "/src/icon.svg");
+        assert_eq!(resolve("../src/utils#main").unwrap(), "/src/utils/index.ts");
+        assert_eq!(resolve("./c#").unwrap(), "/src/c#.js");
+        assert_eq!(resolve("pkg").unwrap(), "/node_modules/pkg/esm.js");
+        assert_eq!(resolve("@scope/lib/sub").unwrap(), "/node_modules/@scope/lib/sub/index.js");
+        assert_eq!(resolve("shim").unwrap(), "/node_modules/shim/client.js");
+        let err = resolve("missing/sub").unwrap_err();
+        assert_eq!(err.kind, ResolveErrorKind::NotFound);
+        assert!(err.tried.contains(&root.join("node_modules/missing")), "{:?}", err.tried);
+        assert_eq!(Diagnostic::from(err).fix.as_deref(), Some("install it with `npm install missing`"));
+        // Tests run in the crate's root, but a bare specifier only ever names a package.
+        let err = resolve("Cargo.toml").unwrap_err();
+        assert_eq!(err.kind, ResolveErrorKind::NotFound);
+        assert!(err.tried.iter().all(|path| path.to_string_lossy().contains("node_modules")), "{:?}", err.tried);
+        let err = resolve("./utlis").unwrap_err();
+        assert_eq!(err.tried.first(), Some(&root.join("src/utlis")));
+        assert_eq!(err.suggestion.as_deref(), Some("./utils"));
+        assert_eq!(resolve("@scope/lbi").unwrap_err().suggestion.as_deref(), Some("@scope/lib"));
+
+        let outside = TempDir::new("resolver-outside");
+        outside.write(&[("lib.js", "")]);
+        let outside_lib = outside.join("lib.js").to_string_lossy().to_string();
+        assert_eq!(resolve(&outside_lib).unwrap_err().kind, ResolveErrorKind::NotAllowed);
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.join("lib.js"), root.join("src/linked.js")).unwrap();
+            let err = resolve("./linked").unwrap_err();
+            assert_eq!(err.kind, ResolveErrorKind::NotAllowed);
+            assert!(err.cause.notes[0].ends_with("src/linked.js is a symlink to it"), "{:?}", err.cause.notes);
+            let resolver = Resolver::new(&ResolveConfig { allowed_dirs: vec![root.to_path_buf(), outside.to_path_buf()], ..Default::default() });
+            assert!(resolver.resolve(&importer, "./linked", ResolveKind::Import).is_ok());
+        }
+    }
+
+    #[test]
+    fn resolves_exports_and_imports_by_condition() {
+        let root = TempDir::new("exports");
+        let exports = r#"{"exports": {
+            ".": {"node": "./node.js", "import": "./index.mjs", "require": "./index.cjs"},
+            "./features/*.js": "./src/features/*.js",
+            "./features/private/*": null,
+            "./x": {"browser": null, "default": "./src/x.js"}
+        }}"#;
+        let files = [
+            ("package.json", r##"{"imports": {"#db": {"development": "./src/mock-db.js", "default": "./src/db.js"}}}"##),
+            ("src/a.js", ""),
+            ("src/db.js", ""),
+            ("src/mock-db.js", ""),
+            ("node_modules/pkg/package.json", exports),
+            ("node_modules/pkg/index.mjs", ""),
+            ("node_modules/pkg/index.cjs", ""),
+            ("node_modules/pkg/node.js", ""),
+            ("node_modules/pkg/src/features/a.js", ""),
+            ("node_modules/pkg/src/features/private/b.js", ""),
+            ("node_modules/pkg/src/x.js", ""),
+        ];
+        root.write(&files);
+        let importer = root.join("src/a.js").to_string_lossy().to_string();
+        let allowed_dirs = vec![root.to_path_buf()];
+        let resolve = |resolver: &Resolver, specifier, kind| {
+            resolver.resolve(&importer, specifier, kind).map(|resolved| resolved.id.replace(&*root.to_string_lossy(), ""))
+        };
+
+        let resolver = Resolver::new(&ResolveConfig { allowed_dirs: allowed_dirs.clone(), ..Default::default() });
+        assert_eq!(resolve(&resolver, "pkg", ResolveKind::Import).unwrap(), "/node_modules/pkg/index.mjs");
+        assert_eq!(resolve(&resolver, "pkg", ResolveKind::Require).unwrap(), "/node_modules/pkg/index.cjs");
+        assert_eq!(resolve(&resolver, "pkg/features/a.js", ResolveKind::Import).unwrap(), "/node_modules/pkg/src/features/a.js");
+        assert_eq!(resolve(&resolver, "#db", ResolveKind::Import).unwrap(), "/src/db.js");
+        let err = resolve(&resolver, "pkg/index.cjs", ResolveKind::Import).unwrap_err();
+        assert_eq!(err.kind, ResolveErrorKind::NotExported);
+        assert_eq!(err.cause.message, "Package subpath './index.cjs' is not exported by 'pkg'");
+        let err = resolve(&resolver, "pkg/features/private/b.js", ResolveKind::Import).unwrap_err();
+        assert_eq!(err.kind, ResolveErrorKind::NotExported);
+        assert_eq!(err.cause.message, "Package subpath './features/private/b.js' is not exported by 'pkg'");
+        // `browser` is a default condition, and its `null` ends the search before `default`.
+        let err = resolve(&resolver, "pkg/x", ResolveKind::Import).unwrap_err();
+        assert_eq!(err.kind, ResolveErrorKind::NotExported);
+        assert!(err.cause.notes[0].ends_with("excludes it with null"), "{:?}", err.cause.notes);
+
+        let mut config = ResolveConfig { allowed_dirs, ..Default::default() };
+        config.conditions.push("node".to_string());
+        let resolver = Resolver::new(&config).with_condition("development");
+        assert_eq!(resolve(&resolver, "pkg", ResolveKind::Require).unwrap(), "/node_modules/pkg/node.js");
+        assert_eq!(resolve(&resolver, "#db", ResolveKind::Import).unwrap(), "/src/mock-db.js");
+
+        let resolver = Resolver::new(&ResolveConfig { conditions: vec![], ..config });
+        assert_eq!(resolve(&resolver, "pkg/x", ResolveKind::Import).unwrap(), "/node_modules/pkg/src/x.js");
+    }
+
+    #[test]
+    fn maps_imports_through_tsconfig_paths() {
+        let root = TempDir::new("tsconfig");
+        let files = [
+            ("tsconfig.base.json", "{\n  // Shared by every package.\n  \"compilerOptions\": {\"baseUrl\": \"src\",},\n}"),
+            ("tsconfig.json", r#"{"extends": "./tsconfig.base", "compilerOptions": {"paths": {"@ui/*": ["missing/*", "components/*"]}}}"#),
+            ("src/a.ts", ""),
+            ("src/lib/math.ts", ""),
+            ("src/components/Button.tsx", ""),
+            ("legacy/old.js", ""),
+        ];
+        root.write(&files);
+        let mut config =
+            ResolveConfig { tsconfig: Some(root.join("tsconfig.json")), allowed_dirs: vec![root.to_path_buf()], ..Default::default() };
+        config.paths.insert("@legacy/".to_string(), root.join("legacy/").to_string_lossy().to_string());
+        let resolver = Resolver::new(&config);
+        resolver.load_tsconfig().unwrap();
+        let importer = root.join("src/a.ts").to_string_lossy().to_string();
+        let resolve = |specifier| {
+            resolver.resolve(&importer, specifier, ResolveKind::Import).map(|resolved| resolved.id.replace(&*root.to_string_lossy(), ""))
+        };
+
+        assert_eq!(resolve("@ui/Button").unwrap(), "/src/components/Button.tsx");
+        assert_eq!(resolve("lib/math").unwrap(), "/src/lib/math.ts");
+        assert_eq!(resolve("@legacy/old").unwrap(), "/legacy/old.js");
+
+        fs::write(root.join("tsconfig.json"), r#"{"extends": "./tsconfig.json"}"#).unwrap();
+        let err = resolver.load_tsconfig().unwrap_err();
+        assert!(err.message.ends_with("tsconfig.json extends itself"), "{}", err.message);
+    }
+
+    #[test]
+    fn plugins_cannot_resolve_outside_the_roots() {
+        let root = TempDir::new("plugin-roots");
+        let outside = TempDir::new("plugin-roots-outside");
+        root.write(&[("src/a.js", ""), ("src/b.js", "")]);
+        outside.write(&[("id_rsa", "")]);
+        let resolver = Resolver::new(&ResolveConfig { allowed_dirs: vec![root.to_path_buf()], ..Default::default() });
+        let importer = root.join("src/a.js").to_string_lossy().to_string();
+        let resolve = |target: &Path| {
+            let mut plugins = PluginManager::new();
+            plugins.register(Box::new(Redirect(target.to_string_lossy().to_string())));
+            resolve_path(&importer, "./b", ResolveKind::Import, &plugins, &mut PluginContext::default(), &resolver)
+        };
+
+        assert!(resolve(&root.join("src/b.js")).is_ok());
+        assert_eq!(resolve(&outside.join("id_rsa")).unwrap_err().kind, ResolveErrorKind::NotAllowed);
+        assert_eq!(resolve(&root.join("src/../../x.js")).unwrap_err().kind, ResolveErrorKind::NotAllowed);
+    }
+}
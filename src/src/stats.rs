@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+use crate::bundler::{ModuleInfo, OutputFile, Timings};
+use crate::diagnostic::Diagnostic;
+
+/// The contents of `stats.json`. Sizes are in bytes and timings in milliseconds.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub timings: BTreeMap<&'static str, f64>,
+    pub modules: Vec<ModuleStats>,
+    /// One chunk per entry.
+    pub chunks: Vec<ChunkStats>,
+    /// Files written alongside the chunks, such as source maps and files emitted by plugins.
+    pub assets: Vec<AssetStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleStats {
+    pub path: String,
+    /// Size on disk, before plugins and defines transformed it.
+    pub original_size: usize,
+    /// Size of what the module contributes to the bundle.
+    pub size: usize,
+    pub imports: Vec<String>,
+    pub importers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkStats {
+    pub entry: String,
+    pub path: String,
+    pub size: usize,
+    pub gzip_size: usize,
+    /// Modules in the chunk, in bundle order.
+    pub modules: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetStats {
+    pub path: String,
+    pub size: usize,
+    pub gzip_size: usize,
+}
+
+impl Stats {
+    /// Collects the stats of a finished build from its modules and the chunks and assets on
+    /// disk, reading them back for their gzip sizes. After a rebuild, `outputs` and `assets`
+    /// are everything written so far, not only what the rebuild rewrote.
+    pub fn collect(
+        modules: &BTreeMap<String, ModuleInfo>,
+        outputs: &[OutputFile],
+        assets: &[PathBuf],
+        timings: &Timings,
+    ) -> Result<Self, Diagnostic> {
+        let mut importers: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (path, module) in modules {
+            for import in &module.imports {
+                importers.entry(import.as_str()).or_default().push(path.clone());
+            }
+        }
+
+        let module_stats = modules
+            .iter()
+            .map(|(path, module)| ModuleStats {
+                path: path.clone(),
+                original_size: module.original_size,
+                size: module.size,
+                imports: module.imports.clone(),
+                importers: importers.remove(path.as_str()).unwrap_or_default(),
+            })
+            .collect();
+
+        let mut chunks = vec![];
+        let mut asset_stats = vec![];
+        for output in outputs {
+            let (size, gzip_size) = file_sizes(&output.path)?;
+            chunks.push(ChunkStats {
+                entry: output.entry.clone(),
+                path: output.path.display().to_string(),
+                size,
+                gzip_size,
+                modules: output.modules.clone(),
+            });
+            if let Some(sourcemap) = &output.sourcemap {
+                let (size, gzip_size) = file_sizes(sourcemap)?;
+                asset_stats.push(AssetStats { path: sourcemap.display().to_string(), size, gzip_size });
+            }
+        }
+        for asset in assets {
+            let (size, gzip_size) = file_sizes(asset)?;
+            asset_stats.push(AssetStats { path: asset.display().to_string(), size, gzip_size });
+        }
+
+        let ms = |duration: Duration| (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0;
+        let timings = BTreeMap::from([
+            ("modules", ms(timings.modules)),
+            ("transform", ms(timings.transform)),
+            ("resolve", ms(timings.resolve)),
+            ("emit", ms(timings.emit)),
+            ("total", ms(timings.total)),
+        ]);
+
+        Ok(Self { timings, modules: module_stats, chunks, assets: asset_stats })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Diagnostic> {
+        let write_error =
+            |err: String| Diagnostic::error("stats/write", format!("Unable to write stats file: {}", err)).with_file(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| write_error(err.to_string()))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|err| write_error(err.to_string()))?;
+        fs::write(path, json).map_err(|err| write_error(err.to_string()))
+    }
+}
+
+/// The size of `bytes` after gzip at the default compression level.
+pub fn gzip_size(bytes: &[u8]) -> usize {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail.
+    let _ = encoder.write_all(bytes);
+    encoder.finish().map_or(0, |compressed| compressed.len())
+}
+
+fn file_sizes(path: &Path) -> Result<(usize, usize), Diagnostic> {
+    let bytes = fs::read(path).map_err(|err| {
+        Diagnostic::error("stats/read", format!("Unable to read output file: {}", err)).with_file(path)
+    })?;
+    Ok((bytes.len(), gzip_size(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn gzip_shrinks_repetitive_output() {
+        let code = "export const a = 1;\n".repeat(100);
+        let size = gzip_size(code.as_bytes());
+        assert!(size > 0 && size < code.len() / 10);
+    }
+
+    #[test]
+    fn collects_modules_chunks_and_assets() {
+        let dir = TempDir::new("stats");
+        let chunk = dir.join("main.js");
+        let sourcemap = dir.join("main.js.map");
+        let asset = dir.join("logo.svg");
+        fs::write(&chunk, "const b = 1;\nconst a = b;\n").unwrap();
+        fs::write(&sourcemap, "{}").unwrap();
+        fs::write(&asset, "<svg/>").unwrap();
+
+        let module = |imports: &[&str], original_size, size| ModuleInfo {
+            imports: imports.iter().map(|import| import.to_string()).collect(),
+            original_size,
+            size,
+        };
+        let modules = BTreeMap::from([
+            ("src/a.js".to_string(), module(&["src/b.js"], 40, 13)),
+            ("src/b.js".to_string(), module(&[], 20, 13)),
+        ]);
+        let outputs = [OutputFile {
+            entry: "main".to_string(),
+            path: chunk,
+            size: 26,
+            sourcemap: Some(sourcemap.clone()),
+            modules: vec!["src/b.js".to_string(), "src/a.js".to_string()],
+        }];
+        let stats = Stats::collect(&modules, &outputs, std::slice::from_ref(&asset), &Timings::default()).unwrap();
+
+        let summary: Vec<_> = stats
+            .modules
+            .iter()
+            .map(|module| (module.path.as_str(), module.original_size, module.size, module.importers.clone()))
+            .collect();
+        assert_eq!(summary, [("src/a.js", 40, 13, vec![]), ("src/b.js", 20, 13, vec!["src/a.js".to_string()])]);
+        assert_eq!(stats.chunks.len(), 1);
+        assert_eq!((stats.chunks[0].entry.as_str(), stats.chunks[0].size), ("main", 26));
+        assert_eq!(stats.chunks[0].modules, ["src/b.js", "src/a.js"]);
+        let assets: Vec<_> = stats.assets.iter().map(|asset| (asset.path.clone(), asset.size)).collect();
+        assert_eq!(assets, [(sourcemap.display().to_string(), 2), (asset.display().to_string(), 6)]);
+    }
+}
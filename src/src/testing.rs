@@ -0,0 +1,45 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An empty directory of one test's own, removed when dropped. The name includes the process,
+/// the time and a counter, so tests running in parallel, in one binary or several, never share
+/// a directory.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos());
+        let unique = format!("{}-{}-{}", std::process::id(), nanos, COUNT.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(format!("hyperpack-{}-{}", name, unique));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Writes each `(path, contents)`, with paths relative to the directory.
+    pub fn write(&self, files: &[(&str, &str)]) {
+        for (path, contents) in files {
+            fs::create_dir_all(self.0.join(path).parent().unwrap()).unwrap();
+            fs::write(self.0.join(path), contents).unwrap();
+        }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,186 +1,89 @@
-use regex::Regex;
-use std::collections::HashMap;
+//! Strips TypeScript down to JavaScript: type annotations, interfaces, type aliases and other
+//! declarations that only exist for the type checker are removed, and enums and namespaces are
+//! compiled to plain objects.
 
-// Function to create the regex pattern for removing TypeScript type annotations
-fn create_type_annotation_regex() -> Regex {
-    Regex::new(r"(?s)
-        (?:\b(?:number|string|boolean|any|void|undefined|object|Array|Record|Tuple|Function|Promise|Set|Map|WeakMap|WeakSet|Symbol|Date|RegExp)\s*<[^>]*>)|
-        \b(?:number|string|boolean|any|void|undefined|object|Array|Record|Tuple|Function|Promise|Set|Map|WeakMap|WeakSet|Symbol|Date|RegExp)\s*[\w]+\s*:\s*[^;\n]*?|
-        \b(?:interface|type)\s+\w+\s*{[^}]*}|
-        \b(?:interface|type)\s+\w+\s*=\s*[^;]*|
-        \b(?:const|let|var)\s+[\w]+\s*:\s*[^;\n]*?;\s*|
-        \b(?:const|let|var)\s+[\w]+\s*:\s*[^;\n]*?\s*=\s*[^;\n]*?;\s*|
-        \b(?:const|let|var)\s+[\w]+\s*=\s*[^;\n]*?;\s*|
-        \b(?:function|const|let|var)\s+[\w]+\s*\([^)]*\)\s*:\s*[^;\n]*?|
-        \b(?:function|const|let|var)\s+[\w]+\s*=\s*\([^)]*\)\s*:\s*[^;\n]*?|
-        \b(?:constructor|new)\s*\([^)]*\)\s*:\s*[^;\n]*?|
-        \s*as\s+[^;\n]*|
-        <[^>]*>|
-        /\*\*[\s\S]*?\*/|
-        \b(?:is)\s+[^;\n]*|
-        \benum\s+\w+\s*{[^}]*}|
-        \bnamespace\s+\w+\s*{[^}]*}|
-        @\w+|
-        \([^)]*\)\s*:\s*[^;\n]*|
-        {[^}]*?:\s*[^;\n]*}|
-        \b(?:function|const|let|var)\s+[\w]+\s*<[^>]*>|
-        \b(?:public|private|protected)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:abstract|readonly|static)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:keyof|typeof)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:import|export)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:declare|type)\s+\w+\s*=\s*[^;\n]*|
-        \b(?:import|export)\s+[\w]+\s*=\s*[^;\n]*|
-        \b(?:namespace|module)\s+\w+\s*{[^}]*}|
-        \b(?:default|named)\s+\w+\s*=\s*[^;\n]*|
-        \b(?:const|let|var)\s+[\w]+\s*=\s*[^;\n]*?;\s*|
-        \b(?:function|const|let|var)\s+[\w]+\s*=\s*[^;\n]*?;\s*|
-        \b(?:typeof|keyof)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:type|interface)\s+\w+\s*=\s*[^;]*|
-        \b(?:abstract|protected|private|public|readonly|static)\s*[\w]+\s*:\s*[^;\n]*|
-        \b(?:new)\s*\([^)]*\)\s*:\s*[^;\n]*|
-        \b(?:typeof|keyof)\s+[\w]+\s*:\s*[^;\n]*|
-        \b(?:interface|type)\s+[\w]+\s*:\s*[^;\n]*"
-    ).unwrap()
-}
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, Globals, Mark, SourceMap, Spanned, GLOBALS};
+use swc_ecmascript::ast::{EsVersion, Program};
+use swc_ecmascript::codegen::text_writer::JsWriter;
+use swc_ecmascript::codegen::{self, Emitter};
+use swc_ecmascript::parser::error::Error as ParseError;
+use swc_ecmascript::parser::{parse_file_as_module, Syntax, TsSyntax};
+use swc_ecmascript::transforms::{fixer, resolver, typescript};
+use swc_ecmascript::visit::FoldWith;
 
-// Function to remove TypeScript type annotations from JavaScript code
-fn remove_type_annotations(js_code: &str) -> String {
-    let re = create_type_annotation_regex();
-    re.replace_all(js_code, "").to_string()
-}
+use crate::diagnostic::{Diagnostic, Span};
+use crate::plugin::TransformResult;
+use crate::sourcemap;
 
-// Function to print a test case result
-fn print_test_case_result(input: &str, expected: &str, result: &str) {
-    if result != expected {
-        println!("Test failed:");
-        println!("Input: {}", input);
-        println!("Expected: {}", expected);
-        println!("Got: {}", result);
-    } else {
-        println!("Test passed.");
+/// Removes the TypeScript from `code`, with a source map back to it. `.tsx` files keep their
+/// JSX. Syntax errors point at where the parser gave up.
+pub fn remove_type_annotations(file_path: &str, code: &str) -> Result<TransformResult, Diagnostic> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let comments = SingleThreadedComments::default();
+    let fm = cm.new_source_file(Lrc::new(FileName::Real(file_path.into())), code.to_string());
+    let syntax = Syntax::Typescript(TsSyntax { tsx: file_path.ends_with(".tsx"), ..Default::default() });
+    let parse_error = |err: ParseError| {
+        let lo = cm.lookup_char_pos(err.span().lo);
+        let hi = cm.lookup_char_pos(err.span().hi);
+        Diagnostic::error("tsc/parse", err.kind().msg().to_string())
+            .with_file(file_path)
+            .with_span(Span::new(lo.line, lo.col.0 + 1, hi.line, (hi.col.0 + 1).max(lo.col.0 + 2)))
+    };
+    let mut recovered = vec![];
+    let module = parse_file_as_module(&fm, syntax, EsVersion::latest(), Some(&comments), &mut recovered)
+        .map_err(parse_error)?;
+    // The parser carries on past some errors, but the output would not be what was written.
+    if let Some(err) = recovered.into_iter().next() {
+        return Err(parse_error(err));
     }
-}
 
-// Function to test the removal of type annotations with different scenarios
-fn test_remove_type_annotations() {
-    let cases = vec![
-        (
-            "type User = { id: number; name: string; age?: number; };",
-            "    { id: ; name: ; age?: ; };"
-        ),
-        (
-            "interface Product { name: string; price: number; }",
-            "    { name: ; price: ; }"
-        ),
-        (
-            "const getUser = (id: number): Promise<User> => { /* implementation */ };",
-            "const getUser = (id: ): => { /* implementation */ };"
-        ),
-        (
-            "function fetchProduct(): Promise<Product> { /* implementation */ }",
-            "function fetchProduct() { /* implementation */ }"
-        ),
-        (
-            "const isAvailable = (product: Product): boolean => { /* implementation */ };",
-            "const isAvailable = (product: ): => { /* implementation */ };"
-        ),
-        (
-            "const user: User = { id: 1, name: 'John', age: 30 };",
-            "const user:  = { id: 1, name: 'John', age: 30 };"
-        ),
-        (
-            "export default User;",
-            "export default ;"
-        ),
-        (
-            "type Person = { name: string; age: number; }; function greet(person: Person): void { console.log(person.name); }",
-            "    { name: ; age: ; }; function greet(person: ): void { console.log(person.name); }"
-        ),
-        (
-            "namespace MyNamespace { export interface MyInterface { id: number; } }",
-            "namespace MyNamespace { export interface MyInterface { id: ; } }"
-        ),
-        (
-            "const myConst: number = 42; let myVar: string = 'hello';",
-            "const myConst:  = 42; let myVar:  = 'hello';"
-        ),
-        (
-            "class MyClass { private id: number; constructor(id: number) { this.id = id; } }",
-            "class MyClass { private id: ; constructor(id: ) { this.id = id; } }"
-        ),
-        (
-            "interface A extends B { prop: string; }",
-            "interface A extends B { prop: ; }"
-        ),
-        (
-            "type Complex = { foo: number; bar: { baz: string; }; };",
-            "    { foo: ; bar: { baz: ; }; };"
-        ),
-        (
-            "function process<T>(input: T): T { return input; }",
-            "function process(input: ): { return input; }"
-        ),
-        (
-            "const myPromise: Promise<string> = new Promise(resolve => resolve('value'));",
-            "const myPromise:  = new Promise(resolve => resolve('value'));"
-        ),
-        (
-            "type LiteralType = 'foo' | 'bar';",
-            "    ;"
-        ),
-        (
-            "namespace Utils { export function helper(arg: number): void { /* implementation */ } }",
-            "namespace Utils { export function helper(arg: ): void { /* implementation */ } }"
-        ),
-        (
-            "type ComplexType = { a: string; b: number[]; c: { d: boolean; } };",
-            "    { a: ; b: []; c: { d: ; } };"
-        ),
-        (
-            "const result: { success: boolean; data: string; } = { success: true, data: 'example' };",
-            "const result: { success: ; data: ; } = { success: true, data: 'example' };"
-        ),
-        // Additional edge cases
-        (
-            "function genericFunction<T, U>(param1: T, param2: U): T { return param1; }",
-            "function genericFunction(param1: , param2: ): { return param1; }"
-        ),
-        (
-            "type UnionType = 'foo' | 'bar' | 42;",
-            "    ;"
-        ),
-        (
-            "type FunctionType = (x: number, y: string) => boolean;",
-            "    (x: , y: ) => ;"
-        ),
-        (
-            "interface Nested { outer: { inner: { key: string; }; }; };",
-            "    { outer: { inner: { key: ; }; }; };"
-        ),
-        (
-            "export class MyExportedClass { static value: number = 10; }",
-            "export class MyExportedClass { static value: = 10; }"
-        ),
-        (
-            "const mySet: Set<number> = new Set([1, 2, 3]);",
-            "const mySet:  = new Set([1, 2, 3]);"
-        ),
-        (
-            "const myMap: Map<string, number> = new Map();",
-            "const myMap:  = new Map();"
-        ),
-        (
-            "class BaseClass { protected baseValue: string; constructor(value: string) { this.baseValue = value; } }",
-            "class BaseClass { protected baseValue: ; constructor(value: ) { this.baseValue = value; } }"
-        ),
-    ];
+    let program = GLOBALS.set(&Globals::new(), || {
+        let unresolved_mark = Mark::new();
+        let top_level_mark = Mark::new();
+        Program::Module(module)
+            .fold_with(&mut resolver(unresolved_mark, top_level_mark, true))
+            .fold_with(&mut typescript::strip(unresolved_mark, top_level_mark))
+            .fold_with(&mut fixer(Some(&comments)))
+    });
 
-    for (input, expected) in cases.iter() {
-        let result = remove_type_annotations(input);
-        print_test_case_result(input, expected, &result);
-    }    
+    let codegen_error = |err: String| Diagnostic::error("tsc/codegen", err).with_file(file_path);
+    let mut buf = Vec::new();
+    let mut mappings = Vec::new();
+    {
+        let mut emitter = Emitter {
+            cfg: codegen::Config::default(),
+            cm: cm.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut mappings)),
+        };
+        emitter.emit_program(&program).map_err(|err| codegen_error(err.to_string()))?;
+    }
+    Ok(TransformResult {
+        code: String::from_utf8(buf).map_err(|err| codegen_error(err.to_string()))?,
+        map: Some(sourcemap::to_json(&cm.build_source_map_from(&mappings, None))),
+    })
 }
 
-fn main() {
-    test_remove_type_annotations();
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_types_and_reports_syntax_errors() {
+        let code = "interface User { id: number; }\n\
+                    type Id = User[\"id\"];\n\
+                    export function greet<T>(user: User, tag?: T): string {\n    return `hi ${user.id as Id}`;\n}\n\
+                    const ids: Set<number> = new Set([1, 2]);\n";
+        let output = remove_type_annotations("src/a.ts", code).unwrap().code;
+        assert_eq!(
+            output,
+            "export function greet(user, tag) {\n    return `hi ${user.id}`;\n}\nconst ids = new Set([\n    1,\n    2\n]);\n"
+        );
+
+        let err = remove_type_annotations("src/b.ts", "let a: number = 1;\nlet b: = 2;\n").unwrap_err();
+        assert_eq!((err.code.as_str(), err.span.map(|span| span.line)), ("tsc/parse", Some(2)));
+        assert_eq!(err.file.as_deref(), Some(std::path::Path::new("src/b.ts")));
+    }
+}
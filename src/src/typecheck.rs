@@ -1,221 +1,231 @@
+//! A small type checker for expressions over numbers, strings, booleans and functions. Errors are
+//! reported as [`Diagnostic`]s pointing at the expression that caused them.
+
 use std::collections::HashMap;
 use std::fmt;
 
-// Enum to represent the different types in the type system
+use crate::diagnostic::{Diagnostic, Span};
+
+/// The different types in the type system.
 #[derive(Debug, PartialEq, Clone)]
-enum Type {
-    Int, // Integer type
-    Float, // Floating-point type
-    String, // String type
-    Bool, // Boolean type
-    Function(Box<Type>, Box<Type>), // Function type: (input_type, return_type)
+pub enum Type {
+    Int,
+    Float,
+    String,
+    Bool,
+    /// A function from its input type to its return type.
+    Function(Box<Type>, Box<Type>),
 }
 
-// Represents a variable with a name and a type
-#[derive(Debug, Clone)]
-struct Variable {
-    name: String,
-    var_type: Type,
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::String => write!(f, "String"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Function(param_type, return_type) => write!(f, "Function({}, {})", param_type, return_type),
+        }
+    }
 }
 
-// Represents an expression in our language
+/// An expression in the checked language.
 #[derive(Debug, Clone)]
-enum Expression {
-    Variable(String), // Reference to a variable
-    Literal(Type), // A literal value with a type
-    BinaryOp(Box<Expression>, String, Box<Expression>), // Binary operation with left operand, operator, and right operand
-    FunctionCall(Box<Expression>, Vec<Expression>), // Function call with function expression and arguments
+pub enum Expression {
+    /// A reference to a variable.
+    Variable(String),
+    /// A literal value of the type.
+    Literal(Type),
+    /// The left operand, the operator and the right operand.
+    BinaryOp(Box<Expression>, String, Box<Expression>),
+    /// A call of the function the first expression names, with arguments.
+    FunctionCall(Box<Expression>, Vec<Expression>),
+    /// An expression at `Span` in its source. Errors inside it point there unless an inner
+    /// expression has a span of its own.
+    Spanned(Span, Box<Expression>),
 }
 
-// Represents a function with a name, parameters, and a return type
+/// A function with its parameters, in order, and its return type.
 #[derive(Debug, Clone)]
-struct Function {
-    name: String,
-    params: HashMap<String, Type>, // Parameters with their types
-    return_type: Type, // Return type of the function
+pub struct Function {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub return_type: Type,
 }
 
-// Represents the state of the type checker
-#[derive(Debug)]
-struct TypeChecker {
-    variables: HashMap<String, Type>, // Map of variable names to their types
-    functions: HashMap<String, Function>, // Map of function names to their definitions
+/// The variables and functions in scope.
+#[derive(Debug, Default)]
+pub struct TypeChecker {
+    pub variables: HashMap<String, Type>,
+    pub functions: HashMap<String, Function>,
 }
 
-// Error types for the type checker
-#[derive(Debug)]
-enum TypeError {
-    UndefinedVariable(String), // Error for using an undefined variable
-    TypeMismatch { expected: Type, found: Type }, // Error for type mismatch
-    UndefinedFunction(String), // Error for calling an undefined function
-    ArgumentMismatch { function: String, expected: Vec<Type>, found: Vec<Type> }, // Error for argument type mismatch
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    UndefinedVariable(String),
+    TypeMismatch { expected: Type, found: Type },
+    UndefinedFunction(String),
+    ArgumentMismatch { function: String, expected: Vec<Type>, found: Vec<Type> },
 }
 
-// Implementing Display for TypeError to provide human-readable error messages
 impl fmt::Display for TypeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |types: &[Type]| types.iter().map(Type::to_string).collect::<Vec<_>>().join(", ");
         match self {
             TypeError::UndefinedVariable(var) => write!(f, "Undefined variable: {}", var),
-            TypeError::TypeMismatch { expected, found } => write!(f, "Type mismatch: expected {:?}, found {:?}", expected, found),
+            TypeError::TypeMismatch { expected, found } => write!(f, "Type mismatch: expected {}, found {}", expected, found),
             TypeError::UndefinedFunction(func) => write!(f, "Undefined function: {}", func),
-            TypeError::ArgumentMismatch { function, expected, found } => write!(f, "Argument mismatch for function '{}': expected {:?}, found {:?}", function, expected, found),
+            TypeError::ArgumentMismatch { function, expected, found } => write!(
+                f,
+                "Argument mismatch for function '{}': expected ({}), found ({})",
+                function,
+                list(expected),
+                list(found)
+            ),
         }
     }
 }
 
+impl std::error::Error for TypeError {}
+
+impl From<TypeError> for Diagnostic {
+    fn from(err: TypeError) -> Self {
+        let code = match &err {
+            TypeError::UndefinedVariable(_) => "typecheck/undefined-variable",
+            TypeError::TypeMismatch { .. } => "typecheck/type-mismatch",
+            TypeError::UndefinedFunction(_) => "typecheck/undefined-function",
+            TypeError::ArgumentMismatch { .. } => "typecheck/argument-mismatch",
+        };
+        Diagnostic::error(code, err.to_string())
+    }
+}
+
 impl TypeChecker {
-    // Creates a new TypeChecker with empty variable and function maps
-    fn new() -> Self {
-        TypeChecker {
-            variables: HashMap::new(),
-            functions: HashMap::new(),
-        }
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // Checks an expression and infers its type
-    fn check_expression(&self, expr: &Expression) -> Result<Type, TypeError> {
+    /// Infers the type of `expr`.
+    pub fn check_expression(&self, expr: &Expression) -> Result<Type, Diagnostic> {
         match expr {
-            // Case for variable expressions
-            Expression::Variable(name) => self.variables.get(name)
-                .cloned()
-                .ok_or(TypeError::UndefinedVariable(name.clone())),
+            Expression::Variable(name) => {
+                self.variables.get(name).cloned().ok_or_else(|| TypeError::UndefinedVariable(name.clone()).into())
+            }
 
-            // Case for literal expressions
             Expression::Literal(lit_type) => Ok(lit_type.clone()),
 
-            // Case for binary operations
             Expression::BinaryOp(left, op, right) => {
-                // Recursively check the left and right operands
                 let left_type = self.check_expression(left)?;
                 let right_type = self.check_expression(right)?;
-
-                // Handle different operators
+                if left_type != right_type {
+                    return Err(TypeError::TypeMismatch { expected: left_type, found: right_type }.into());
+                }
                 match op.as_str() {
-                    "+" | "-" | "*" | "/" => {
-                        // Arithmetic operators require both operands to be of the same type
-                        if left_type != right_type {
-                            Err(TypeError::TypeMismatch { expected: left_type.clone(), found: right_type })
-                        } else {
-                            Ok(left_type) // Result type is the same as operands' type
-                        }
-                    },
-                    "==" | "!=" => {
-                        // Equality operators require both operands to be of the same type
-                        if left_type != right_type {
-                            Err(TypeError::TypeMismatch { expected: left_type.clone(), found: right_type })
-                        } else {
-                            Ok(Type::Bool) // Equality results in a boolean
-                        }
-                    },
-                    _ => Err(TypeError::TypeMismatch { expected: left_type, found: right_type }),
+                    // Arithmetic keeps the operands' type.
+                    "+" | "-" | "*" | "/" => Ok(left_type),
+                    "==" | "!=" => Ok(Type::Bool),
+                    _ => Err(Diagnostic::error("typecheck/operator", format!("Unknown operator '{}'", op))),
                 }
-            },
-
-            // Case for function calls
-            Expression::FunctionCall(func_expr, args) => {
-                // Check the type of the function expression
-                let func_type = self.check_expression(func_expr)?;
-                let func = self.functions.get(&func_type.to_string())
-                    .ok_or(TypeError::UndefinedFunction(func_type.to_string()))?;
+            }
 
-                // Ensure the number of arguments matches the function's parameter count
-                if args.len() != func.params.len() {
+            Expression::FunctionCall(callee, args) => {
+                let name = callee_name(callee).unwrap_or_default();
+                let func = self
+                    .functions
+                    .get(name)
+                    .ok_or_else(|| Diagnostic::from(TypeError::UndefinedFunction(name.to_string())))?;
+                let arg_types = args.iter().map(|arg| self.check_expression(arg)).collect::<Result<Vec<_>, _>>()?;
+                if arg_types.len() != func.params.len() {
                     return Err(TypeError::ArgumentMismatch {
                         function: func.name.clone(),
-                        expected: func.params.values().cloned().collect(),
-                        found: args.iter().map(|arg| self.check_expression(arg)).collect::<Result<_, _>>()?,
-                    });
+                        expected: func.params.iter().map(|(_, param_type)| param_type.clone()).collect(),
+                        found: arg_types,
+                    }
+                    .into());
                 }
-
-                // Check each argument against the corresponding parameter type
-                for (param, expected_type) in func.params.iter() {
-                    let arg_type = self.check_expression(args.iter().find(|&&ref arg| arg.to_string() == param).unwrap())?;
-                    if arg_type != *expected_type {
-                        return Err(TypeError::TypeMismatch { expected: expected_type.clone(), found: arg_type });
+                for ((arg, arg_type), (param, expected)) in args.iter().zip(arg_types).zip(&func.params) {
+                    if arg_type != *expected {
+                        let diagnostic = Diagnostic::from(TypeError::TypeMismatch { expected: expected.clone(), found: arg_type })
+                            .with_note(format!("for parameter '{}' of '{}'", param, func.name));
+                        return Err(with_span_of(arg, diagnostic));
                     }
                 }
-
-                // Return the function's return type
                 Ok(func.return_type.clone())
-            },
+            }
+
+            Expression::Spanned(span, inner) => {
+                self.check_expression(inner).map_err(|diagnostic| match diagnostic.span {
+                    Some(_) => diagnostic,
+                    None => diagnostic.with_span(*span),
+                })
+            }
         }
     }
 
-    // Checks a function definition for correct parameter types
-    fn check_function(&self, func: &Function) -> Result<(), TypeError> {
-        // Ensure each parameter is defined and has the correct type
+    /// Checks that each of the function's parameters is a variable in scope of the declared type.
+    pub fn check_function(&self, func: &Function) -> Result<(), Diagnostic> {
         for (param, param_type) in &func.params {
-            if !self.variables.contains_key(param) {
-                return Err(TypeError::UndefinedVariable(param.clone()));
-            }
-
-            let var_type = self.variables.get(param).unwrap();
-            if *var_type != *param_type {
-                return Err(TypeError::TypeMismatch { expected: param_type.clone(), found: var_type.clone() });
+            let var_type = self.variables.get(param).ok_or_else(|| TypeError::UndefinedVariable(param.clone()))?;
+            if var_type != param_type {
+                return Err(TypeError::TypeMismatch { expected: param_type.clone(), found: var_type.clone() }.into());
             }
         }
         Ok(())
     }
 }
 
-impl Type {
-    // Converts a Type to a string representation for function matching
-    fn to_string(&self) -> String {
-        match self {
-            Type::Int => "Int".to_string(),
-            Type::Float => "Float".to_string(),
-            Type::String => "String".to_string(),
-            Type::Bool => "Bool".to_string(),
-            Type::Function(param_type, return_type) => format!("Function({}, {})", param_type.to_string(), return_type.to_string()),
-        }
+/// The function a call's callee names, looking through spans.
+fn callee_name(callee: &Expression) -> Option<&str> {
+    match callee {
+        Expression::Variable(name) => Some(name),
+        Expression::Spanned(_, inner) => callee_name(inner),
+        _ => None,
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut type_checker = TypeChecker::new();
-
-    // Define some variables with their types
-    type_checker.variables.insert("x".to_string(), Type::Int);
-    type_checker.variables.insert("y".to_string(), Type::Float);
-
-    // Define a function with parameters and a return type
-    let func = Function {
-        name: "add".to_string(),
-        params: [("a".to_string(), Type::Int), ("b".to_string(), Type::Int)]
-            .iter().cloned().collect(),
-        return_type: Type::Int,
-    };
-
-    // Insert the function definition into the type checker
-    type_checker.functions.insert(func.name.clone(), func);
-
-    // Create an expression representing a binary operation
-    let expr = Expression::BinaryOp(
-        Box::new(Expression::Variable("x".to_string())),
-        "+".to_string(),
-        Box::new(Expression::Variable("y".to_string()))
-    );
-
-    // Check the type of the binary operation expression
-    match type_checker.check_expression(&expr) {
-        Ok(expr_type) => println!("Expression type: {:?}", expr_type),
-        Err(err) => eprintln!("Error: {}", err),
+/// `diagnostic` pointing at `expr`, if it has a span.
+fn with_span_of(expr: &Expression, diagnostic: Diagnostic) -> Diagnostic {
+    match expr {
+        Expression::Spanned(span, _) => diagnostic.with_span(*span),
+        _ => diagnostic,
     }
+}
 
-    // Define a function call with arguments
-    let func_call = Expression::FunctionCall(
-        Box::new(Expression::Variable("add".to_string())),
-        vec![
-            Expression::Literal(Type::Int),
-            Expression::Literal(Type::Int),
-        ]
-    );
-
-    // Check the type of the function call expression
-    match type_checker.check_expression(&func_call) {
-        Ok(call_type) => println!("Function call returns: {:?}", call_type),
-        Err(err) => eprintln!("Error: {}", err),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_type_errors_at_their_expression() {
+        let mut checker = TypeChecker::new();
+        checker.variables.insert("x".to_string(), Type::Int);
+        checker.variables.insert("y".to_string(), Type::Float);
+        checker.functions.insert(
+            "add".to_string(),
+            Function {
+                name: "add".to_string(),
+                params: vec![("a".to_string(), Type::Int), ("b".to_string(), Type::Int)],
+                return_type: Type::Int,
+            },
+        );
+        let at = |column, expr| Expression::Spanned(Span::new(1, column, 1, column + 1), Box::new(expr));
+        let call = |args| Expression::FunctionCall(Box::new(Expression::Variable("add".to_string())), args);
+
+        let variable = |name: &str| Expression::Variable(name.to_string());
+
+        let valid = call(vec![Expression::Literal(Type::Int), at(9, variable("x"))]);
+        assert_eq!(checker.check_expression(&valid).unwrap(), Type::Int);
+
+        let sum = Expression::BinaryOp(Box::new(variable("x")), "+".to_string(), Box::new(variable("y")));
+        let err = checker.check_expression(&at(1, sum)).unwrap_err();
+        assert_eq!(err.code, "typecheck/type-mismatch");
+        assert_eq!(err.message, "Type mismatch: expected Int, found Float");
+        assert_eq!(err.span.map(|span| span.column), Some(1));
+
+        let wrong_argument = call(vec![at(5, Expression::Literal(Type::Int)), at(8, variable("y"))]);
+        let err = checker.check_expression(&at(1, wrong_argument)).unwrap_err();
+        assert_eq!(err.span.map(|span| span.column), Some(8));
+        assert_eq!(err.notes, ["for parameter 'b' of 'add'"]);
+        assert_eq!(checker.check_expression(&call(vec![])).unwrap_err().code, "typecheck/argument-mismatch");
     }
-
-    Ok(())
-}
\ No newline at end of file
+}